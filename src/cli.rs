@@ -0,0 +1,188 @@
+use std::path::PathBuf;
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::source::ReplaySpeed;
+
+pub const USAGE: &str = "\
+Usage: txradar10 [OPTIONS]
+       txradar10 import-utxos <snapshot>
+       txradar10 db migrate [--dry-run]
+       txradar10 rescore [--from <time>] [--to <time>]
+
+Commands:
+  import-utxos <snapshot>  Seed the UTXO cache from a `bitcoin-cli dumptxoutset` file
+  db migrate [--dry-run]   Apply pending database migrations (or only list them)
+  rescore                  Score stored signals again with the current rules and weights;
+                           times are RFC 3339 or YYYY-MM-DD (default: the last 24 hours)
+
+Options:
+  --record <path>    Also write raw ZMQ messages to rotating capture files
+  --replay <path>    Replay a capture file (or a directory of them) instead of ZMQ
+  --speed <speed>    Replay speed: realtime (default), max, or a factor like 10x
+  -h, --help         Print this help";
+
+/// Command line options.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub speed: ReplaySpeed,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            record: None,
+            replay: None,
+            speed: ReplaySpeed::RealTime,
+        }
+    }
+}
+
+/// Parsed command line, or a request for the help text.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run(Args),
+    /// Import a UTXO snapshot into the cache, then exit.
+    ImportUtxos(PathBuf),
+    /// Apply pending schema migrations, or only report them with `dry_run`.
+    DbMigrate { dry_run: bool },
+    /// Score the signals stored in a time range again; `None` bounds use the defaults.
+    Rescore {
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    },
+    Help,
+}
+
+/// Parse arguments (without the program name).
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter().peekable();
+    if args.next_if(|a| a == "import-utxos").is_some() {
+        let path = args.next().ok_or("import-utxos requires a snapshot path")?;
+        if let Some(extra) = args.next() {
+            return Err(format!("unexpected argument '{extra}'"));
+        }
+        return Ok(Command::ImportUtxos(PathBuf::from(path)));
+    }
+    if args.next_if(|a| a == "db").is_some() {
+        if args.next().as_deref() != Some("migrate") {
+            return Err("usage: db migrate [--dry-run]".to_string());
+        }
+        let dry_run = args.next_if(|a| a == "--dry-run").is_some();
+        if let Some(extra) = args.next() {
+            return Err(format!("unexpected argument '{extra}'"));
+        }
+        return Ok(Command::DbMigrate { dry_run });
+    }
+    if args.next_if(|a| a == "rescore").is_some() {
+        let (mut from, mut to) = (None, None);
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{name} requires a value"));
+            match arg.as_str() {
+                "--from" => from = Some(parse_time(&value("--from")?)?),
+                "--to" => to = Some(parse_time(&value("--to")?)?),
+                other => return Err(format!("unexpected argument '{other}'")),
+            }
+        }
+        return Ok(Command::Rescore { from, to });
+    }
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("{name} requires a value"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--record" => parsed.record = Some(PathBuf::from(value("--record")?)),
+            "--replay" => parsed.replay = Some(PathBuf::from(value("--replay")?)),
+            "--speed" => parsed.speed = value("--speed")?.parse()?,
+            other => return Err(format!("unknown argument '{other}'")),
+        }
+    }
+    if parsed.record.is_some() && parsed.replay.is_some() {
+        return Err("--record and --replay can't be combined".to_string());
+    }
+    Ok(Command::Run(parsed))
+}
+
+/// An RFC 3339 timestamp, or a date meaning its start (UTC).
+fn parse_time(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Utc));
+    }
+    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| format!("invalid time '{s}' (expected RFC 3339 or YYYY-MM-DD)"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_strs(args: &[&str]) -> Result<Command, String> {
+        parse(args.iter().map(|s| s.to_string()))
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(parse_strs(&[]), Ok(Command::Run(Args::default())));
+    }
+
+    #[test]
+    fn replay_with_speed() {
+        let Ok(Command::Run(args)) = parse_strs(&["--replay", "incident.cap", "--speed", "max"]) else {
+            panic!("expected run command");
+        };
+        assert_eq!(args.replay, Some(PathBuf::from("incident.cap")));
+        assert_eq!(args.speed, ReplaySpeed::Max);
+    }
+
+    #[test]
+    fn record() {
+        let Ok(Command::Run(args)) = parse_strs(&["--record", "captures/"]) else {
+            panic!("expected run command");
+        };
+        assert_eq!(args.record, Some(PathBuf::from("captures/")));
+        assert!(parse_strs(&["--record", "a.cap", "--replay", "b.cap"]).is_err());
+    }
+
+    #[test]
+    fn import_utxos() {
+        assert_eq!(
+            parse_strs(&["import-utxos", "utxo.dat"]),
+            Ok(Command::ImportUtxos(PathBuf::from("utxo.dat")))
+        );
+        assert!(parse_strs(&["import-utxos"]).is_err());
+        assert!(parse_strs(&["import-utxos", "utxo.dat", "--replay", "x.cap"]).is_err());
+    }
+
+    #[test]
+    fn db_migrate() {
+        assert_eq!(parse_strs(&["db", "migrate"]), Ok(Command::DbMigrate { dry_run: false }));
+        assert_eq!(parse_strs(&["db", "migrate", "--dry-run"]), Ok(Command::DbMigrate { dry_run: true }));
+        assert!(parse_strs(&["db"]).is_err());
+        assert!(parse_strs(&["db", "migrate", "--force"]).is_err());
+    }
+
+    #[test]
+    fn rescore() {
+        assert_eq!(parse_strs(&["rescore"]), Ok(Command::Rescore { from: None, to: None }));
+        let Ok(Command::Rescore { from, to }) =
+            parse_strs(&["rescore", "--from", "2025-01-02", "--to", "2025-01-03T12:00:00+01:00"])
+        else {
+            panic!("expected rescore command");
+        };
+        assert_eq!(from.unwrap().to_rfc3339(), "2025-01-02T00:00:00+00:00");
+        assert_eq!(to.unwrap().to_rfc3339(), "2025-01-03T11:00:00+00:00");
+        assert!(parse_strs(&["rescore", "--from", "yesterday"]).is_err());
+        assert!(parse_strs(&["rescore", "--to"]).is_err());
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse_strs(&["--replay"]).is_err());
+        assert!(parse_strs(&["--speed", "warp"]).is_err());
+        assert!(parse_strs(&["--bogus"]).is_err());
+        assert_eq!(parse_strs(&["-h"]), Ok(Command::Help));
+    }
+}
@@ -10,6 +10,7 @@ pub struct Config {
     pub ui: UiConfig,
     pub database: DatabaseConfig,
     pub notifications: NotificationConfig,
+    pub capture: CaptureConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -22,6 +23,29 @@ pub struct BitcoinConfig {
     pub zmq_rawtx: String,
     pub zmq_hashblock: String,
     pub zmq_sequence: Option<String>,
+    /// Load the node's current mempool via RPC before consuming live ZMQ events.
+    pub sync_mempool_on_start: bool,
+    /// How prevouts missing from the cache are fetched.
+    pub prevout_resolver: PrevoutResolverMode,
+    /// Lookups (parent txs or outpoints) per JSON-RPC batch request during prevout resolution.
+    pub prevout_batch_size: usize,
+    /// Batch requests in flight at once per transaction.
+    pub prevout_concurrency: usize,
+    /// Time budget for resolving one transaction's prevouts (ms); the tx is scored with
+    /// whatever resolved by then.
+    pub prevout_budget_ms: u64,
+}
+
+/// RPC method used to resolve prevouts.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PrevoutResolverMode {
+    /// `getrawtransaction` on the parent tx. Needs `txindex=1` for confirmed parents.
+    #[default]
+    GetRawTransaction,
+    /// `gettxout` on the outpoint. Works on pruned nodes without txindex, but only for
+    /// outputs still unspent in the chain.
+    GetTxOut,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -29,6 +53,10 @@ pub struct BitcoinConfig {
 pub struct SignalConfig {
     pub weights: HashMap<String, f64>,
     pub min_score_persist: f64,
+    /// Also store the raw bytes of signaled txs (the analysis is always stored).
+    pub store_raw_tx: bool,
+    /// Days without an outgoing tx after which a tagged entity counts as dormant.
+    pub dormant_entity_days: u32,
     pub alert_thresholds: AlertThresholds,
 }
 
@@ -55,11 +83,23 @@ pub struct NotificationConfig {
     pub cooldown_seconds: u64,
 }
 
+/// Capture file rotation for `--record`.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct CaptureConfig {
+    /// Start a new file once the current one reaches this size (0 = no size limit).
+    pub max_file_mb: u64,
+    /// Start a new file after this many minutes (0 = no time limit).
+    pub rotate_minutes: u64,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 #[serde(default)]
 pub struct DatabaseConfig {
     pub path: String,
     pub exchange_csv: Option<String>,
+    /// Keep at most this many UTXO cache rows, evicting the least recently used (0 = unlimited).
+    pub utxo_cache_max_rows: u64,
 }
 
 impl Default for Config {
@@ -70,6 +110,7 @@ impl Default for Config {
             ui: UiConfig::default(),
             database: DatabaseConfig::default(),
             notifications: NotificationConfig::default(),
+            capture: CaptureConfig::default(),
         }
     }
 }
@@ -84,6 +125,11 @@ impl Default for BitcoinConfig {
             zmq_rawtx: "tcp://127.0.0.1:28333".into(),
             zmq_hashblock: "tcp://127.0.0.1:28332".into(),
             zmq_sequence: Some("tcp://127.0.0.1:28336".into()),
+            sync_mempool_on_start: true,
+            prevout_resolver: PrevoutResolverMode::default(),
+            prevout_batch_size: 25,
+            prevout_concurrency: 4,
+            prevout_budget_ms: 500,
         }
     }
 }
@@ -93,6 +139,8 @@ impl Default for SignalConfig {
         Self {
             weights: HashMap::new(),
             min_score_persist: 10.0,
+            store_raw_tx: false,
+            dormant_entity_days: 180,
             alert_thresholds: AlertThresholds::default(),
         }
     }
@@ -127,11 +175,21 @@ impl Default for NotificationConfig {
     }
 }
 
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            max_file_mb: 256,
+            rotate_minutes: 60,
+        }
+    }
+}
+
 impl Default for DatabaseConfig {
     fn default() -> Self {
         Self {
             path: "data/utxo_cache.db".into(),
             exchange_csv: Some("data/exchange_addresses.csv".into()),
+            utxo_cache_max_rows: 0,
         }
     }
 }
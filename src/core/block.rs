@@ -0,0 +1,132 @@
+use bitcoin::{OutPoint, Txid};
+use serde_json::Value;
+
+/// The parts of a connected block the pipeline needs, parsed from `getblock` verbosity 2.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockSummary {
+    pub hash: String,
+    pub height: u32,
+    pub time: i64,
+    /// Txids in block order (coinbase first).
+    pub txids: Vec<String>,
+    /// Sum of all tx fees in satoshis (0 if the node didn't report fees).
+    pub total_fees: u64,
+    /// Outpoints spent by the block's txs (verbosity 2 only, coinbase excluded).
+    pub spends: Vec<OutPoint>,
+}
+
+impl BlockSummary {
+    /// Parse a `getblock <hash> 2` result.
+    pub fn from_rpc(block: &Value) -> Option<Self> {
+        let hash = block.get("hash")?.as_str()?.to_string();
+        let height = block.get("height")?.as_u64()? as u32;
+        let time = block.get("time").and_then(|t| t.as_i64()).unwrap_or(0);
+        let txs = block.get("tx")?.as_array()?;
+
+        let mut txids = Vec::with_capacity(txs.len());
+        let mut total_fees = 0u64;
+        let mut spends = Vec::new();
+        for tx in txs {
+            // Verbosity 1 returns plain txid strings, verbosity 2 full objects
+            match tx {
+                Value::String(txid) => txids.push(txid.clone()),
+                Value::Object(_) => {
+                    txids.push(tx.get("txid")?.as_str()?.to_string());
+                    if let Some(fee_btc) = tx.get("fee").and_then(|f| f.as_f64()) {
+                        total_fees += (fee_btc * 100_000_000.0).round() as u64;
+                    }
+                    let vins = tx.get("vin").and_then(|v| v.as_array()).into_iter().flatten();
+                    spends.extend(vins.filter_map(parse_vin_outpoint));
+                }
+                _ => return None,
+            }
+        }
+
+        Some(Self { hash, height, time, txids, total_fees, spends })
+    }
+
+    pub fn tx_count(&self) -> usize {
+        self.txids.len()
+    }
+}
+
+/// Outpoint of a `getblock`/`getrawtransaction` vin entry; `None` for coinbase inputs.
+fn parse_vin_outpoint(vin: &Value) -> Option<OutPoint> {
+    let txid: Txid = vin.get("txid")?.as_str()?.parse().ok()?;
+    let vout = vin.get("vout")?.as_u64()? as u32;
+    Some(OutPoint { txid, vout })
+}
+
+/// Per-block statistics recorded when a block connects.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlockStats {
+    pub height: u32,
+    pub hash: String,
+    pub tx_count: usize,
+    pub total_fees: u64,
+    /// Block txs we were tracking as pending.
+    pub tracked_count: usize,
+    /// Block txs whose score passed the persist threshold.
+    pub signal_count: usize,
+}
+
+/// State of an ongoing chain reorganization, accumulated over consecutive disconnects.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ReorgInfo {
+    /// Number of blocks disconnected so far.
+    pub depth: u32,
+    /// Height of the last block still on the active chain.
+    pub fork_height: u32,
+    /// Signals flagged as reorged (seen above the fork point).
+    pub affected_signals: usize,
+    /// Confirmed txs moved back to Pending.
+    pub unconfirmed_txs: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PREV_TXID: &str = "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b";
+
+    #[test]
+    fn parse_verbosity_2_block() {
+        let block = json!({
+            "hash": "00000000000000000001",
+            "height": 850_000,
+            "time": 1_718_000_000,
+            "tx": [
+                { "txid": "coinbase", "vin": [{ "coinbase": "03" }] },
+                { "txid": "tx1", "fee": 0.0001, "vin": [{ "txid": PREV_TXID, "vout": 1 }] },
+                { "txid": "tx2", "fee": 0.00025 },
+            ]
+        });
+        let summary = BlockSummary::from_rpc(&block).unwrap();
+        assert_eq!(summary.height, 850_000);
+        assert_eq!(summary.time, 1_718_000_000);
+        assert_eq!(summary.txids, vec!["coinbase", "tx1", "tx2"]);
+        assert_eq!(summary.total_fees, 35_000);
+        assert_eq!(summary.tx_count(), 3);
+        assert_eq!(summary.spends, vec![OutPoint { txid: PREV_TXID.parse().unwrap(), vout: 1 }]);
+    }
+
+    #[test]
+    fn parse_verbosity_1_block() {
+        let block = json!({
+            "hash": "00000000000000000002",
+            "height": 1,
+            "tx": ["a", "b"]
+        });
+        let summary = BlockSummary::from_rpc(&block).unwrap();
+        assert_eq!(summary.txids, vec!["a", "b"]);
+        assert_eq!(summary.total_fees, 0);
+        assert_eq!(summary.time, 0);
+    }
+
+    #[test]
+    fn parse_rejects_missing_height() {
+        let block = json!({ "hash": "00", "tx": [] });
+        assert!(BlockSummary::from_rpc(&block).is_none());
+    }
+}
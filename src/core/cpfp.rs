@@ -0,0 +1,86 @@
+use serde::{Deserialize, Serialize};
+
+use super::mempool::MempoolEntry;
+use super::{AlertLevel, ScoredTx};
+
+/// A pending tx whose fee rate was raised by a child spending its outputs.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CpfpBump {
+    pub parent_txid: String,
+    pub child_txid: String,
+    pub parent_score: f64,
+    pub parent_alert_level: AlertLevel,
+    pub child_score: f64,
+    pub parent_fee_rate: f64,
+    /// Fee rate of the child together with its pending ancestors (sat/vB).
+    pub package_fee_rate: f64,
+}
+
+impl CpfpBump {
+    /// Describe the bump of a pending entry by a scored child.
+    pub fn compute(parent: &MempoolEntry, child: &ScoredTx) -> Self {
+        let parent_score = parent.score.unwrap_or(0.0);
+        Self {
+            parent_txid: parent.tx.txid.clone(),
+            child_txid: child.tx.txid.clone(),
+            parent_score,
+            parent_alert_level: parent.alert_level.unwrap_or(AlertLevel::Low),
+            child_score: child.composite_score,
+            parent_fee_rate: parent.tx.fee_rate,
+            package_fee_rate: child.tx.effective_fee_rate(),
+        }
+    }
+
+    /// Whether the parent was an alert (High or Critical).
+    pub fn is_alert(&self) -> bool {
+        matches!(self.parent_alert_level, AlertLevel::Critical | AlertLevel::High)
+    }
+
+    /// One-line description, e.g. "bumped by child: 1.0 → 12.5 sat/vB".
+    pub fn summary(&self) -> String {
+        format!(
+            "bumped by child: {:.1} → {:.1} sat/vB",
+            self.parent_fee_rate, self.package_fee_rate
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::AnalyzedTx;
+    use crate::core::mempool::TxState;
+    use crate::signals::rules::tests::make_test_tx;
+    use chrono::Utc;
+
+    #[test]
+    fn bump_of_alert() {
+        let parent = MempoolEntry {
+            tx: AnalyzedTx { txid: "parent".into(), fee: 200, fee_rate: 1.0, ..make_test_tx() },
+            state: TxState::Pending,
+            state_changed_at: Utc::now(),
+            replaced_by: None,
+            score: Some(72.0),
+            alert_level: Some(AlertLevel::High),
+            spends: Vec::new(),
+            outputs: Vec::new(),
+            parents: Vec::new(),
+            children: vec!["child".into()],
+        };
+        let child = ScoredTx {
+            tx: AnalyzedTx {
+                txid: "child".into(),
+                fee_rate: 24.0,
+                ancestor_fee_rate: Some(12.5),
+                ..make_test_tx()
+            },
+            composite_score: 20.0,
+            rule_scores: vec![],
+            alert_level: AlertLevel::from_score(20.0),
+        };
+        let bump = CpfpBump::compute(&parent, &child);
+        assert!(bump.is_alert());
+        assert_eq!(bump.package_fee_rate, 12.5);
+        assert_eq!(bump.summary(), "bumped by child: 1.0 → 12.5 sat/vB");
+    }
+}
@@ -1,8 +1,9 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
+use bitcoin::{OutPoint, Transaction, TxOut};
 use chrono::{DateTime, Utc};
 
-use super::{AnalyzedTx, RemovalReason};
+use super::{AlertLevel, AnalyzedTx, RemovalReason};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TxState {
@@ -10,6 +11,7 @@ pub enum TxState {
     Confirmed,
     Replaced,
     Evicted,
+    Conflicted,
 }
 
 #[derive(Debug)]
@@ -18,9 +20,34 @@ pub struct MempoolEntry {
     pub state: TxState,
     pub state_changed_at: DateTime<Utc>,
     /// If this tx was replaced, the txid of the replacement.
-    /// Used when ZMQ sequence topic provides replacement info.
-    #[allow(dead_code)]
     pub replaced_by: Option<String>,
+    /// Composite score at first sight, if the tx was scored.
+    pub score: Option<f64>,
+    /// Alert level of that score under the configured thresholds.
+    pub alert_level: Option<AlertLevel>,
+    /// Outpoints spent by this tx.
+    pub spends: Vec<OutPoint>,
+    /// Outputs of this tx (empty if added without the parsed tx).
+    pub outputs: Vec<TxOut>,
+    /// Tracked txs this tx spends outputs of, pending when it was added.
+    pub parents: Vec<String>,
+    /// Tracked txs spending outputs of this tx.
+    pub children: Vec<String>,
+}
+
+impl MempoolEntry {
+    /// The fee, if all prevouts were resolved.
+    fn known_fee(&self) -> Option<u64> {
+        self.tx.prevouts_resolved.then_some(self.tx.fee)
+    }
+}
+
+/// A pending tx that was just confirmed in a block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfirmedTx {
+    pub txid: String,
+    pub score: Option<f64>,
+    pub seen_at: DateTime<Utc>,
 }
 
 /// Fee histogram bucket definition.
@@ -33,18 +60,81 @@ const FEE_BUCKETS: &[(f64, f64, &str)] = &[
     (100.0, f64::MAX, "100+"),
 ];
 
+/// A child's package must pay at least this multiple of its parent's rate to count as a
+/// child-pays-for-parent bump rather than an ordinary chained payment.
+const CPFP_MIN_GAIN: f64 = 1.25;
+
+/// Pending ancestors of a tx and the fee rate of the package they form with it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AncestorInfo {
+    /// Pending txs the tx depends on, directly or through other pending txs.
+    pub count: usize,
+    /// Fee rate of the tx together with its pending ancestors (sat/vB), if every fee is
+    /// known.
+    pub package_fee_rate: Option<f64>,
+    /// Direct parents whose descendant package rate the package beats by `CPFP_MIN_GAIN`.
+    pub bumped_parents: Vec<String>,
+}
+
+/// Virtual size of a block's worth of txs (4M weight units).
+const BLOCK_VSIZE: usize = 1_000_000;
+
+/// Below this many pending txs with known fees, the fee snapshot is left empty: right
+/// after startup without a sync our view of the mempool says nothing about its state.
+const FEE_SNAPSHOT_MIN_TXS: usize = 100;
+
+/// Fee levels of the pending txs at one point in time, for scoring fee urgency relative
+/// to the mempool.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeSnapshot {
+    /// Effective fee rates of pending txs with known fees, ascending.
+    rates: Vec<f64>,
+    /// Lowest effective fee rate in a block filled with the best-paying pending txs; `None`
+    /// if they don't fill a block (or the snapshot is empty).
+    pub next_block_min_fee: Option<f64>,
+    /// Pending vsize in blocks; `None` if the snapshot is empty.
+    pub blocks_pending: Option<f64>,
+}
+
+impl FeeSnapshot {
+    /// Share of pending txs (0.0-1.0) paying a lower effective fee rate than `rate`.
+    pub fn percentile(&self, rate: f64) -> Option<f64> {
+        if self.rates.is_empty() {
+            return None;
+        }
+        let below = self.rates.partition_point(|&r| r < rate);
+        Some(below as f64 / self.rates.len() as f64)
+    }
+}
+
+/// How long an unexplained removal stays open to reclassification as replaced,
+/// confirmed or conflicted. The evidence (replacement `A`, block `C`) normally
+/// follows within milliseconds.
+const RECLASSIFY_WINDOW: chrono::Duration = chrono::Duration::minutes(2);
+
 /// Removal statistics by reason.
 #[derive(Debug, Clone, Default)]
 pub struct RemovalStats {
     pub confirmed: u64,
     pub replaced: u64,
     pub evicted: u64,
+    pub conflict: u64,
     pub unknown: u64,
 }
 
 impl RemovalStats {
     pub fn total(&self) -> u64 {
-        self.confirmed + self.replaced + self.evicted + self.unknown
+        self.confirmed + self.replaced + self.evicted + self.conflict + self.unknown
+    }
+
+    fn counter(&mut self, reason: RemovalReason) -> &mut u64 {
+        match reason {
+            RemovalReason::Confirmed => &mut self.confirmed,
+            RemovalReason::Replaced => &mut self.replaced,
+            RemovalReason::Evicted => &mut self.evicted,
+            RemovalReason::Conflict => &mut self.conflict,
+            RemovalReason::Unknown => &mut self.unknown,
+        }
     }
 }
 
@@ -54,6 +144,8 @@ pub struct MempoolState {
     entries: HashMap<String, MempoolEntry>,
     /// RBF replacement chains: replaced_txid → replacing_txid
     replacement_chain: HashMap<String, String>,
+    /// Spent outpoint → txid of the latest tracked tx spending it.
+    spent_by: HashMap<OutPoint, String>,
     /// Removal statistics by reason.
     removal_stats: RemovalStats,
 }
@@ -63,8 +155,61 @@ impl MempoolState {
         Self::default()
     }
 
+    /// Add an unscored tx without spend tracking (for testing).
+    #[cfg(test)]
     pub fn add_tx(&mut self, tx: AnalyzedTx) {
+        self.insert_entry(tx, None, Vec::new(), Vec::new());
+    }
+
+    /// Add a tx together with its composite score, its alert level and the parsed tx (for
+    /// its spent outpoints and outputs). Tracked txs spending any of the same outpoints are
+    /// recorded as replaced by it; their txids are returned.
+    pub fn add_scored_tx(
+        &mut self,
+        tx: AnalyzedTx,
+        score: f64,
+        alert_level: AlertLevel,
+        parsed: &Transaction,
+    ) -> Vec<String> {
+        let spends = parsed
+            .input
+            .iter()
+            .map(|i| i.previous_output)
+            .filter(|op| !op.is_null())
+            .collect();
+        self.insert_entry(tx, Some((score, alert_level)), spends, parsed.output.clone())
+    }
+
+    fn insert_entry(
+        &mut self,
+        tx: AnalyzedTx,
+        score: Option<(f64, AlertLevel)>,
+        spends: Vec<OutPoint>,
+        outputs: Vec<TxOut>,
+    ) -> Vec<String> {
         let txid = tx.txid.clone();
+        let mut replaced = Vec::new();
+        for outpoint in &spends {
+            if let Some(old) = self.spent_by.insert(*outpoint, txid.clone())
+                && old != txid
+                && !replaced.contains(&old)
+            {
+                replaced.push(old);
+            }
+        }
+        replaced.retain(|old| self.record_replacement(old, &txid));
+
+        let parents = self.pending_parents(&spends);
+        for parent in &parents {
+            if let Some(entry) = self.entries.get_mut(parent)
+                && !entry.children.contains(&txid)
+            {
+                entry.children.push(txid.clone());
+            }
+        }
+        // Children seen while this tx was tracked before (e.g. evicted and re-added) stay linked
+        let children = self.entries.get(&txid).map(|e| e.children.clone()).unwrap_or_default();
+
         self.entries.insert(
             txid,
             MempoolEntry {
@@ -72,8 +217,121 @@ impl MempoolState {
                 state: TxState::Pending,
                 state_changed_at: Utc::now(),
                 replaced_by: None,
+                score: score.map(|(s, _)| s),
+                alert_level: score.map(|(_, l)| l),
+                spends,
+                outputs,
+                parents,
+                children,
             },
         );
+        replaced
+    }
+
+    /// Tracked txs spending any outpoint `parsed` (not yet added) spends: the txs it
+    /// would replace.
+    pub fn conflicts(&self, parsed: &Transaction) -> Vec<&MempoolEntry> {
+        let txid = parsed.compute_txid().to_string();
+        let mut conflicts: Vec<&MempoolEntry> = Vec::new();
+        for input in &parsed.input {
+            if let Some(entry) = self.spent_by.get(&input.previous_output).and_then(|t| self.entries.get(t))
+                && entry.tx.txid != txid
+                && !conflicts.iter().any(|c| c.tx.txid == entry.tx.txid)
+            {
+                conflicts.push(entry);
+            }
+        }
+        conflicts
+    }
+
+    /// Distinct pending txs whose outputs `spends` includes.
+    fn pending_parents<'a>(&self, spends: impl IntoIterator<Item = &'a OutPoint>) -> Vec<String> {
+        let mut parents: Vec<String> = Vec::new();
+        for outpoint in spends {
+            let txid = outpoint.txid.to_string();
+            if self.is_pending(&txid) && !parents.contains(&txid) {
+                parents.push(txid);
+            }
+        }
+        parents
+    }
+
+    /// Pending ancestors of `parsed` (not yet added) and its package fee rate. `fee` is
+    /// `None` if its prevouts weren't all resolved.
+    pub fn ancestor_info(&self, parsed: &Transaction, fee: Option<u64>, vsize: usize) -> AncestorInfo {
+        let parents = self.pending_parents(parsed.input.iter().map(|i| &i.previous_output));
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack: Vec<&str> = parents.iter().map(String::as_str).collect();
+        let (mut package_fee, mut package_vsize) = (fee, vsize);
+        while let Some(txid) = stack.pop() {
+            let Some(entry) = self.entries.get(txid).filter(|e| e.state == TxState::Pending) else {
+                continue;
+            };
+            if !seen.insert(txid) {
+                continue;
+            }
+            package_fee = package_fee.zip(entry.known_fee()).map(|(a, b)| a + b);
+            package_vsize += entry.tx.vsize;
+            stack.extend(entry.parents.iter().map(String::as_str));
+        }
+        let package_fee_rate = package_fee
+            .filter(|_| package_vsize > 0)
+            .map(|f| f as f64 / package_vsize as f64);
+
+        let bumped_parents = match package_fee_rate {
+            Some(rate) => parents
+                .iter()
+                .filter(|p| {
+                    let own = self.entries[p.as_str()].tx.fee_rate;
+                    let current = self.descendant_fee_rate(p).unwrap_or(own);
+                    rate >= current * CPFP_MIN_GAIN
+                })
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+        AncestorInfo { count: seen.len(), package_fee_rate, bumped_parents }
+    }
+
+    /// Fee rate of a pending tx together with its pending descendants (sat/vB), if every
+    /// fee is known.
+    pub fn descendant_fee_rate(&self, txid: &str) -> Option<f64> {
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut stack = vec![txid];
+        let (mut fee, mut vsize) = (0u64, 0usize);
+        while let Some(txid) = stack.pop() {
+            let Some(entry) = self.entries.get(txid).filter(|e| e.state == TxState::Pending) else {
+                continue;
+            };
+            if !seen.insert(txid) {
+                continue;
+            }
+            fee += entry.known_fee()?;
+            vsize += entry.tx.vsize;
+            stack.extend(entry.children.iter().map(String::as_str));
+        }
+        (vsize > 0).then(|| fee as f64 / vsize as f64)
+    }
+
+    /// Whether the txid is currently tracked as pending.
+    pub fn is_pending(&self, txid: &str) -> bool {
+        self.entries
+            .get(txid)
+            .is_some_and(|e| e.state == TxState::Pending)
+    }
+
+    /// Whether the txid is tracked in any state (pending or recently removed).
+    pub fn contains(&self, txid: &str) -> bool {
+        self.entries.contains_key(txid)
+    }
+
+    /// Txids of all pending entries.
+    pub fn pending_txids(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .filter(|(_, e)| e.state == TxState::Pending)
+            .map(|(txid, _)| txid.clone())
+            .collect()
     }
 
     /// Transition a tx out of Pending state.
@@ -81,77 +339,171 @@ impl MempoolState {
         let new_state = match reason {
             RemovalReason::Confirmed => TxState::Confirmed,
             RemovalReason::Replaced => TxState::Replaced,
+            RemovalReason::Conflict => TxState::Conflicted,
             _ => TxState::Evicted,
         };
         if let Some(entry) = self.entries.get_mut(txid) {
             if entry.state == TxState::Pending {
                 // Only count stats for transitions from Pending
-                match reason {
-                    RemovalReason::Confirmed => self.removal_stats.confirmed += 1,
-                    RemovalReason::Replaced => self.removal_stats.replaced += 1,
-                    RemovalReason::Evicted => self.removal_stats.evicted += 1,
-                    RemovalReason::Conflict | RemovalReason::Unknown => self.removal_stats.unknown += 1,
-                }
+                *self.removal_stats.counter(reason) += 1;
             }
             entry.state = new_state;
             entry.state_changed_at = Utc::now();
         } else {
             // Tx not tracked (arrived before we started), still count it
-            match reason {
-                RemovalReason::Confirmed => self.removal_stats.confirmed += 1,
-                RemovalReason::Replaced => self.removal_stats.replaced += 1,
-                RemovalReason::Evicted => self.removal_stats.evicted += 1,
-                RemovalReason::Conflict | RemovalReason::Unknown => self.removal_stats.unknown += 1,
+            *self.removal_stats.counter(reason) += 1;
+        }
+    }
+
+    /// Handle a removal the node didn't give a reason for (sequence `R`).
+    /// A tracked tx already double-spent by a pending tx counts as replaced; otherwise it
+    /// is provisionally evicted until a replacement or block says otherwise.
+    /// Untracked txs are counted as unknown. Returns the reason applied.
+    pub fn remove_unexplained(&mut self, txid: &str) -> RemovalReason {
+        let Some(entry) = self.entries.get(txid) else {
+            self.remove_tx(txid, RemovalReason::Unknown);
+            return RemovalReason::Unknown;
+        };
+        if entry.state != TxState::Pending {
+            return RemovalReason::Unknown;
+        }
+
+        let replacement = entry
+            .spends
+            .iter()
+            .filter_map(|op| self.spent_by.get(op))
+            .find(|spender| spender.as_str() != txid && self.is_pending(spender))
+            .cloned();
+        match replacement {
+            Some(new_txid) => {
+                self.record_replacement(txid, &new_txid);
+                RemovalReason::Replaced
+            }
+            None => {
+                self.remove_tx(txid, RemovalReason::Evicted);
+                RemovalReason::Evicted
             }
         }
     }
 
+    /// Move a recently evicted entry to a more specific removal reason.
+    fn reclassify_eviction(&mut self, txid: &str, reason: RemovalReason, new_state: TxState) -> bool {
+        let now = Utc::now();
+        let Some(entry) = self.entries.get_mut(txid) else {
+            return false;
+        };
+        if entry.state != TxState::Evicted || now - entry.state_changed_at > RECLASSIFY_WINDOW {
+            return false;
+        }
+        entry.state = new_state;
+        entry.state_changed_at = now;
+        self.removal_stats.evicted = self.removal_stats.evicted.saturating_sub(1);
+        *self.removal_stats.counter(reason) += 1;
+        true
+    }
+
     /// Get removal statistics.
     pub fn removal_stats(&self) -> &RemovalStats {
         &self.removal_stats
     }
 
     /// Record an RBF replacement: `old_txid` was replaced by `new_txid`.
-    /// Will be called once ZMQ sequence topic is wired up.
-    #[allow(dead_code)]
-    pub fn record_replacement(&mut self, old_txid: &str, new_txid: &str) {
-        self.replacement_chain
-            .insert(old_txid.to_string(), new_txid.to_string());
-        if let Some(entry) = self.entries.get_mut(old_txid) {
-            entry.replaced_by = Some(new_txid.to_string());
-            entry.state = TxState::Replaced;
-            entry.state_changed_at = Utc::now();
+    /// Applies to pending and recently evicted entries; returns whether one was updated.
+    pub fn record_replacement(&mut self, old_txid: &str, new_txid: &str) -> bool {
+        let updated = match self.entries.get(old_txid).map(|e| e.state) {
+            Some(TxState::Pending) => {
+                self.remove_tx(old_txid, RemovalReason::Replaced);
+                true
+            }
+            Some(TxState::Evicted) => {
+                self.reclassify_eviction(old_txid, RemovalReason::Replaced, TxState::Replaced)
+            }
+            _ => false,
+        };
+        if updated {
+            self.replacement_chain
+                .insert(old_txid.to_string(), new_txid.to_string());
+            if let Some(entry) = self.entries.get_mut(old_txid) {
+                entry.replaced_by = Some(new_txid.to_string());
+            }
         }
+        updated
     }
 
-    /// Mark all currently-pending txs as confirmed (used after a block).
-    /// Returns the number of txs marked.
-    #[allow(dead_code)]
-    pub fn confirm_all_pending(&mut self) -> usize {
-        let now = Utc::now();
-        let mut count = 0;
-        for entry in self.entries.values_mut() {
-            if entry.state == TxState::Pending {
-                // We can't know which txs were in the block, so we don't
-                // confirm all pending here. This is called selectively.
+    /// Mark the txids of a connected block as confirmed.
+    /// Returns the pending (or just evicted) txs that transitioned; unknown txids are ignored.
+    pub fn confirm_txids(&mut self, txids: &[String]) -> Vec<ConfirmedTx> {
+        let mut confirmed = Vec::new();
+        for txid in txids {
+            let transitioned = match self.entries.get(txid.as_str()).map(|e| e.state) {
+                Some(TxState::Pending) => {
+                    self.remove_tx(txid, RemovalReason::Confirmed);
+                    true
+                }
+                Some(TxState::Evicted) => {
+                    self.reclassify_eviction(txid, RemovalReason::Confirmed, TxState::Confirmed)
+                }
+                _ => false,
+            };
+            if transitioned {
+                let entry = &self.entries[txid.as_str()];
+                confirmed.push(ConfirmedTx {
+                    txid: txid.clone(),
+                    score: entry.score,
+                    seen_at: entry.tx.seen_at,
+                });
             }
-            let _ = (entry, now, &mut count); // suppress unused
         }
-        count
+        confirmed
     }
 
-    /// Mark specific txids as confirmed (txids that disappeared after a block).
-    #[allow(dead_code)]
-    pub fn confirm_txids(&mut self, txids: &[String]) {
+    /// Mark tracked txs that double-spend a connected block's inputs as conflicted.
+    /// Applies to pending and recently evicted entries; call after `confirm_txids`.
+    /// Returns the txids that transitioned.
+    pub fn mark_block_conflicts(&mut self, block_spends: &HashSet<OutPoint>) -> Vec<String> {
+        let candidates: Vec<String> = block_spends
+            .iter()
+            .filter_map(|op| self.spent_by.get(op))
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let mut conflicted = Vec::new();
+        for txid in candidates {
+            let transitioned = match self.entries.get(&txid).map(|e| e.state) {
+                Some(TxState::Pending) => {
+                    self.remove_tx(&txid, RemovalReason::Conflict);
+                    true
+                }
+                Some(TxState::Evicted) => {
+                    self.reclassify_eviction(&txid, RemovalReason::Conflict, TxState::Conflicted)
+                }
+                _ => false,
+            };
+            if transitioned {
+                conflicted.push(txid);
+            }
+        }
+        conflicted
+    }
+
+    /// Move txs of a disconnected block back to Pending. Returns how many were confirmed.
+    /// Txs the block conflicted stay Conflicted: the node puts the block's txs back in its
+    /// mempool, including the conflicting one, but never the txs they displaced.
+    pub fn unconfirm_txids(&mut self, txids: &[String]) -> usize {
         let now = Utc::now();
+        let mut count = 0;
         for txid in txids {
-            if let Some(entry) = self.entries.get_mut(txid.as_str()) {
-                if entry.state == TxState::Pending {
-                    entry.state = TxState::Confirmed;
-                    entry.state_changed_at = now;
-                }
+            if let Some(entry) = self.entries.get_mut(txid.as_str())
+                && entry.state == TxState::Confirmed
+            {
+                entry.state = TxState::Pending;
+                entry.state_changed_at = now;
+                self.removal_stats.confirmed = self.removal_stats.confirmed.saturating_sub(1);
+                count += 1;
             }
         }
+        count
     }
 
     // --- Statistics ---
@@ -179,6 +531,30 @@ impl MempoolState {
             .sum()
     }
 
+    /// Effective fee rates of the pending txs and the projected next block. Txs without a
+    /// known fee count toward the pending size only.
+    pub fn fee_snapshot(&self) -> FeeSnapshot {
+        let mut known: Vec<(f64, usize)> = self
+            .entries
+            .values()
+            .filter(|e| e.state == TxState::Pending && e.tx.prevouts_resolved)
+            .map(|e| (e.tx.effective_fee_rate(), e.tx.vsize))
+            .collect();
+        if known.len() < FEE_SNAPSHOT_MIN_TXS {
+            return FeeSnapshot::default();
+        }
+        known.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        let mut filled = 0;
+        let next_block_min_fee = known.iter().find_map(|&(rate, vsize)| {
+            filled += vsize;
+            (filled >= BLOCK_VSIZE).then_some(rate)
+        });
+        let blocks_pending = self.total_vsize() as f64 / BLOCK_VSIZE as f64;
+        let rates = known.into_iter().rev().map(|(rate, _)| rate).collect();
+        FeeSnapshot { rates, next_block_min_fee, blocks_pending: Some(blocks_pending) }
+    }
+
     /// Fee histogram: counts of pending txs per fee-rate bucket.
     pub fn fee_histogram(&self) -> Vec<(String, usize)> {
         let mut counts = vec![0usize; FEE_BUCKETS.len()];
@@ -201,8 +577,17 @@ impl MempoolState {
             .collect()
     }
 
-    /// Get an entry by txid (for testing).
-    #[cfg(test)]
+    /// A pending tx and the output at `outpoint`, so children spending an unconfirmed
+    /// parent can be resolved without RPC.
+    pub fn pending_output(&self, outpoint: &OutPoint) -> Option<(&MempoolEntry, &TxOut)> {
+        let entry = self.entries.get(&outpoint.txid.to_string())?;
+        if entry.state != TxState::Pending {
+            return None;
+        }
+        Some((entry, entry.outputs.get(outpoint.vout as usize)?))
+    }
+
+    /// Get an entry by txid, in any state.
     pub fn get(&self, txid: &str) -> Option<&MempoolEntry> {
         self.entries.get(txid)
     }
@@ -217,7 +602,23 @@ impl MempoolState {
             .map(|(k, _)| k.clone())
             .collect();
         for txid in &removed_txids {
-            self.entries.remove(txid);
+            if let Some(entry) = self.entries.remove(txid) {
+                for outpoint in &entry.spends {
+                    if self.spent_by.get(outpoint) == Some(txid) {
+                        self.spent_by.remove(outpoint);
+                    }
+                }
+                for parent in &entry.parents {
+                    if let Some(parent) = self.entries.get_mut(parent) {
+                        parent.children.retain(|c| c != txid);
+                    }
+                }
+                for child in &entry.children {
+                    if let Some(child) = self.entries.get_mut(child) {
+                        child.parents.retain(|p| p != txid);
+                    }
+                }
+            }
             self.replacement_chain.remove(txid);
         }
     }
@@ -227,30 +628,17 @@ impl MempoolState {
 mod tests {
     use super::*;
     use crate::core::AnalyzedTx;
+    use crate::signals::rules::tests::make_test_tx;
 
     fn make_tx(txid: &str, fee: u64, fee_rate: f64, vsize: usize) -> AnalyzedTx {
         AnalyzedTx {
             txid: txid.to_string(),
-            raw_size: 250,
             vsize,
             total_input_value: 100_000,
             total_output_value: 100_000 - fee,
             fee,
             fee_rate,
-            input_count: 1,
-            output_count: 2,
-            oldest_input_height: None,
-            oldest_input_time: None,
-            coin_days_destroyed: None,
-            is_rbf_signaling: false,
-            seen_at: Utc::now(),
-            prevouts_resolved: false,
-            to_exchange: false,
-            to_exchange_confidence: 0.0,
-            from_exchange: false,
-            from_exchange_confidence: 0.0,
-            is_coinjoin: false,
-            coinjoin_confidence: 0.0,
+            ..make_test_tx()
         }
     }
 
@@ -264,6 +652,35 @@ mod tests {
         assert_eq!(state.pending_count(), 2);
     }
 
+    #[test]
+    fn is_pending_tracks_state() {
+        let mut state = MempoolState::new();
+        assert!(!state.is_pending("tx1"));
+        state.add_tx(make_tx("tx1", 1000, 5.0, 200));
+        assert!(state.is_pending("tx1"));
+        state.remove_tx("tx1", RemovalReason::Evicted);
+        assert!(!state.is_pending("tx1"));
+    }
+
+    #[test]
+    fn contains_includes_removed() {
+        let mut state = MempoolState::new();
+        state.add_tx(make_tx("tx1", 1000, 5.0, 200));
+        state.remove_tx("tx1", RemovalReason::Confirmed);
+        assert!(state.contains("tx1"));
+        assert!(!state.is_pending("tx1"));
+        assert!(!state.contains("tx2"));
+    }
+
+    #[test]
+    fn pending_txids_excludes_removed() {
+        let mut state = MempoolState::new();
+        state.add_tx(make_tx("tx1", 1000, 5.0, 200));
+        state.add_tx(make_tx("tx2", 1000, 5.0, 200));
+        state.remove_tx("tx1", RemovalReason::Evicted);
+        assert_eq!(state.pending_txids(), vec!["tx2".to_string()]);
+    }
+
     #[test]
     fn remove_tx_confirmed() {
         let mut state = MempoolState::new();
@@ -375,6 +792,233 @@ mod tests {
         assert!(state.get("tx1").is_some());
     }
 
+    #[test]
+    fn confirm_txids_marks_pending_only() {
+        let mut state = MempoolState::new();
+        state.add_scored_tx(make_tx("tx1", 1000, 5.0, 200), 42.0, AlertLevel::from_score(42.0), &spending(&[]));
+        state.add_tx(make_tx("tx2", 1000, 5.0, 200));
+        state.add_tx(make_tx("tx3", 1000, 5.0, 200));
+        state.remove_tx("tx3", RemovalReason::Replaced);
+
+        let confirmed = state.confirm_txids(&[
+            "tx1".to_string(),
+            "tx3".to_string(),
+            "not_tracked".to_string(),
+        ]);
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(confirmed[0].txid, "tx1");
+        assert_eq!(confirmed[0].score, Some(42.0));
+        assert_eq!(state.get("tx1").unwrap().state, TxState::Confirmed);
+        assert_eq!(state.get("tx2").unwrap().state, TxState::Pending);
+        assert_eq!(state.get("tx3").unwrap().state, TxState::Replaced);
+        assert_eq!(state.removal_stats().confirmed, 1);
+        assert_eq!(state.pending_count(), 1);
+    }
+
+    fn outpoint(n: u8) -> OutPoint {
+        OutPoint {
+            txid: bitcoin::hashes::Hash::from_byte_array([n; 32]),
+            vout: 0,
+        }
+    }
+
+    /// A parsed tx spending the given outpoints, with a single output.
+    fn spending(outpoints: &[OutPoint]) -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: outpoints
+                .iter()
+                .map(|op| bitcoin::TxIn { previous_output: *op, ..Default::default() })
+                .collect(),
+            output: vec![TxOut {
+                value: bitcoin::Amount::from_sat(50_000),
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn removal_then_replacement_reclassifies() {
+        // Core sends `R` for the original before `A` for the replacement
+        let mut state = MempoolState::new();
+        state.add_scored_tx(make_tx("tx_old", 1000, 5.0, 200), 80.0, AlertLevel::from_score(80.0), &spending(&[outpoint(1), outpoint(2)]));
+        assert_eq!(state.remove_unexplained("tx_old"), RemovalReason::Evicted);
+        assert_eq!(state.removal_stats().evicted, 1);
+
+        let replaced = state.add_scored_tx(make_tx("tx_new", 2000, 10.0, 200), 30.0, AlertLevel::from_score(30.0), &spending(&[outpoint(2)]));
+        assert_eq!(replaced, vec!["tx_old".to_string()]);
+        let entry = state.get("tx_old").unwrap();
+        assert_eq!(entry.state, TxState::Replaced);
+        assert_eq!(entry.replaced_by.as_deref(), Some("tx_new"));
+        assert_eq!(state.removal_stats().evicted, 0);
+        assert_eq!(state.removal_stats().replaced, 1);
+    }
+
+    #[test]
+    fn replacement_then_removal() {
+        let mut state = MempoolState::new();
+        state.add_scored_tx(make_tx("tx_old", 1000, 5.0, 200), 80.0, AlertLevel::from_score(80.0), &spending(&[outpoint(1)]));
+        state.add_scored_tx(make_tx("tx_new", 2000, 10.0, 200), 30.0, AlertLevel::from_score(30.0), &spending(&[outpoint(1)]));
+        assert_eq!(state.get("tx_old").unwrap().state, TxState::Replaced);
+        // The late `R` doesn't count the removal twice
+        assert_eq!(state.remove_unexplained("tx_old"), RemovalReason::Unknown);
+        assert_eq!(state.removal_stats().total(), 1);
+        assert_eq!(state.removal_stats().replaced, 1);
+    }
+
+    #[test]
+    fn unexplained_removal_of_untracked_tx() {
+        let mut state = MempoolState::new();
+        assert_eq!(state.remove_unexplained("never_seen"), RemovalReason::Unknown);
+        assert_eq!(state.removal_stats().unknown, 1);
+    }
+
+    #[test]
+    fn removal_before_block_becomes_confirmed() {
+        let mut state = MempoolState::new();
+        state.add_scored_tx(make_tx("tx1", 1000, 5.0, 200), 50.0, AlertLevel::from_score(50.0), &spending(&[outpoint(1)]));
+        state.remove_unexplained("tx1");
+
+        let confirmed = state.confirm_txids(&["tx1".to_string()]);
+        assert_eq!(confirmed.len(), 1);
+        assert_eq!(state.get("tx1").unwrap().state, TxState::Confirmed);
+        assert_eq!(state.removal_stats().evicted, 0);
+        assert_eq!(state.removal_stats().confirmed, 1);
+    }
+
+    #[test]
+    fn block_conflicts() {
+        let mut state = MempoolState::new();
+        state.add_scored_tx(make_tx("in_block", 1000, 5.0, 200), 10.0, AlertLevel::from_score(10.0), &spending(&[outpoint(1)]));
+        state.add_scored_tx(make_tx("removed", 1000, 5.0, 200), 10.0, AlertLevel::from_score(10.0), &spending(&[outpoint(2)]));
+        state.add_scored_tx(make_tx("still_pending", 1000, 5.0, 200), 10.0, AlertLevel::from_score(10.0), &spending(&[outpoint(3)]));
+        state.add_scored_tx(make_tx("unrelated", 1000, 5.0, 200), 10.0, AlertLevel::from_score(10.0), &spending(&[outpoint(4)]));
+        state.remove_unexplained("removed");
+
+        state.confirm_txids(&["in_block".to_string()]);
+        let block_spends: HashSet<OutPoint> = [outpoint(1), outpoint(2), outpoint(3)].into();
+        let mut conflicted = state.mark_block_conflicts(&block_spends);
+        conflicted.sort();
+        assert_eq!(conflicted, vec!["removed".to_string(), "still_pending".to_string()]);
+        assert_eq!(state.get("in_block").unwrap().state, TxState::Confirmed);
+        assert_eq!(state.get("removed").unwrap().state, TxState::Conflicted);
+        assert_eq!(state.get("unrelated").unwrap().state, TxState::Pending);
+        let stats = state.removal_stats();
+        assert_eq!((stats.confirmed, stats.conflict, stats.evicted), (1, 2, 0));
+    }
+
+    #[test]
+    fn unconfirm_txids_restores_pending() {
+        let mut state = MempoolState::new();
+        state.add_tx(make_tx("tx1", 1000, 5.0, 200));
+        state.add_tx(make_tx("tx2", 1000, 5.0, 200));
+        state.add_scored_tx(make_tx("double_spend", 1000, 5.0, 200), 10.0, AlertLevel::from_score(10.0), &spending(&[outpoint(1)]));
+        state.confirm_txids(&["tx1".to_string()]);
+        state.mark_block_conflicts(&[outpoint(1)].into());
+
+        let restored = state.unconfirm_txids(&["tx1".to_string(), "tx2".to_string(), "not_tracked".to_string()]);
+        assert_eq!(restored, 1);
+        assert_eq!(state.get("tx1").unwrap().state, TxState::Pending);
+        // The node doesn't take back a tx the block displaced
+        assert_eq!(state.get("double_spend").unwrap().state, TxState::Conflicted);
+        let stats = state.removal_stats();
+        assert_eq!((stats.confirmed, stats.conflict), (0, 1));
+        assert_eq!(state.pending_count(), 2);
+    }
+
+    /// A tx whose prevouts, and so its fee, are known.
+    fn resolved(txid: &str, fee: u64, vsize: usize) -> AnalyzedTx {
+        let mut tx = make_tx(txid, fee, fee as f64 / vsize as f64, vsize);
+        tx.prevouts_resolved = true;
+        tx
+    }
+
+    #[test]
+    fn cpfp_package_rates() {
+        let mut state = MempoolState::new();
+        let parent = outpoint(1).txid.to_string();
+        let child = outpoint(2).txid.to_string();
+        // Stuck parent at 1 sat/vB
+        state.add_scored_tx(resolved(&parent, 200, 200), 70.0, AlertLevel::from_score(70.0), &spending(&[outpoint(9)]));
+
+        let child_tx = spending(&[outpoint(1)]);
+        let info = state.ancestor_info(&child_tx, Some(4_800), 200);
+        assert_eq!(info.count, 1);
+        assert_eq!(info.package_fee_rate, Some(12.5));
+        assert_eq!(info.bumped_parents, vec![parent.clone()]);
+
+        state.add_scored_tx(resolved(&child, 4_800, 200), 10.0, AlertLevel::from_score(10.0), &child_tx);
+        assert_eq!(state.get(&parent).unwrap().children, vec![child.clone()]);
+        assert_eq!(state.descendant_fee_rate(&parent), Some(12.5));
+
+        // A cheap grandchild doesn't bump the child that already pays 24 sat/vB
+        let info = state.ancestor_info(&spending(&[outpoint(2)]), Some(100), 100);
+        assert_eq!(info.count, 2);
+        assert_eq!(info.package_fee_rate, Some(5_100.0 / 500.0));
+        assert!(info.bumped_parents.is_empty());
+    }
+
+    #[test]
+    fn chained_payment_is_not_a_bump() {
+        let mut state = MempoolState::new();
+        let parent = outpoint(1).txid.to_string();
+        state.add_scored_tx(resolved(&parent, 2_000, 200), 70.0, AlertLevel::from_score(70.0), &spending(&[outpoint(9)]));
+        let info = state.ancestor_info(&spending(&[outpoint(1)]), Some(2_200), 200);
+        assert_eq!(info.package_fee_rate, Some(10.5));
+        assert!(info.bumped_parents.is_empty());
+
+        // Unknown fees leave the package rate open
+        let info = state.ancestor_info(&spending(&[outpoint(1)]), None, 200);
+        assert_eq!((info.count, info.package_fee_rate), (1, None));
+        let mut unresolved = resolved("unresolved", 0, 200);
+        unresolved.prevouts_resolved = false;
+        state.add_scored_tx(unresolved, 70.0, AlertLevel::from_score(70.0), &spending(&[outpoint(8)]));
+        assert_eq!(state.descendant_fee_rate("unresolved"), None);
+    }
+
+    #[test]
+    fn confirmed_parent_is_no_ancestor() {
+        let mut state = MempoolState::new();
+        let parent = outpoint(1).txid.to_string();
+        let child = outpoint(2).txid.to_string();
+        state.add_scored_tx(resolved(&parent, 200, 200), 70.0, AlertLevel::from_score(70.0), &spending(&[outpoint(9)]));
+        state.add_scored_tx(resolved(&child, 4_800, 200), 10.0, AlertLevel::from_score(10.0), &spending(&[outpoint(1)]));
+        state.confirm_txids(std::slice::from_ref(&parent));
+
+        let info = state.ancestor_info(&spending(&[outpoint(2)]), Some(2_000), 100);
+        assert_eq!(info.count, 1);
+        assert_eq!(state.descendant_fee_rate(&parent), None);
+
+        // Pruning unlinks the parent
+        state.entries.get_mut(&parent).unwrap().state_changed_at = Utc::now() - chrono::Duration::hours(2);
+        state.prune_old(chrono::Duration::hours(1));
+        assert!(state.get(&child).unwrap().parents.is_empty());
+    }
+
+    #[test]
+    fn fee_snapshot_projects_next_block() {
+        let mut state = MempoolState::new();
+        for i in 0..99 {
+            state.add_tx(resolved(&format!("small{i}"), 1_000, 100));
+        }
+        assert_eq!(state.fee_snapshot(), FeeSnapshot::default());
+
+        // 2 blocks at 50 sat/vB on top of the 10 sat/vB txs
+        for i in 0..4 {
+            let mut big = make_tx(&format!("big{i}"), 50_000, 50.0, 500_000);
+            big.prevouts_resolved = true;
+            state.add_tx(big);
+        }
+        state.add_tx(make_tx("unresolved", 0, 0.0, 90_100));
+        let snapshot = state.fee_snapshot();
+        assert_eq!(snapshot.next_block_min_fee, Some(50.0));
+        assert_eq!(snapshot.blocks_pending, Some(2.1));
+        assert_eq!(snapshot.percentile(10.0), Some(0.0));
+        assert_eq!(snapshot.percentile(20.0), Some(99.0 / 103.0));
+        assert_eq!(snapshot.percentile(60.0), Some(1.0));
+    }
+
     #[test]
     fn record_replacement() {
         let mut state = MempoolState::new();
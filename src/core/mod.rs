@@ -1,18 +1,56 @@
+pub mod block;
+pub mod cpfp;
 pub mod mempool;
 pub mod pipeline;
+pub mod prevout;
+pub mod rbf;
 pub mod tx;
 
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
 /// A mempool lifecycle event from ZMQ sequence topic.
+///
+/// Hashes are in display byte order (as shown by RPC / block explorers).
+/// `mempool_sequence` is the node's mempool sequence number when known
+/// (sequence topic 'A'/'R' events), used to skip events already covered by a resync.
 #[derive(Debug, Clone)]
 #[allow(dead_code)]
 pub enum MempoolEvent {
-    TxAdded { txid: [u8; 32], raw: Vec<u8> },
-    TxRemoved { txid: [u8; 32], reason: RemovalReason },
+    TxAdded { txid: [u8; 32], raw: Vec<u8>, mempool_sequence: Option<u64> },
+    TxRemoved { txid: [u8; 32], reason: RemovalReason, mempool_sequence: Option<u64> },
+    /// A tx published on `rawtx` as part of a connected block that never announced
+    /// mempool acceptance (either already known from the mempool or block-only).
+    TxSeenInBlock { txid: [u8; 32], raw: Vec<u8> },
     BlockConnected { block_hash: [u8; 32], height: u32 },
     BlockDisconnected { block_hash: [u8; 32], height: u32 },
+    /// The event stream lost messages; mempool state must be reconciled with the node.
+    ResyncRequired { reason: ResyncReason },
+}
+
+/// Why the event stream can no longer be trusted to be complete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResyncReason {
+    /// A ZMQ topic's message sequence number skipped ahead (or reset).
+    SequenceGap { topic: &'static str, expected: u64, got: u64 },
+    /// A ZMQ socket reconnected after a disconnect.
+    Reconnected,
+}
+
+impl std::fmt::Display for ResyncReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResyncReason::SequenceGap { topic, expected, got } => {
+                write!(f, "{topic} sequence gap (expected {expected}, got {got})")
+            }
+            ResyncReason::Reconnected => write!(f, "ZMQ reconnect"),
+        }
+    }
+}
+
+/// Hex-encode a 32-byte hash that is already in display byte order.
+pub fn hash_to_hex(hash: &[u8; 32]) -> String {
+    hash.iter().map(|b| format!("{b:02x}")).collect()
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -33,6 +71,10 @@ pub struct AnalyzedTx {
     pub vsize: usize,
     pub total_input_value: u64,
     pub total_output_value: u64,
+    /// Output value excluding likely change (`signals::change`), in satoshis. `None` in
+    /// analyses stored before change detection; see `economic_value()`.
+    #[serde(default)]
+    pub economic_value: Option<u64>,
     pub fee: u64,
     pub fee_rate: f64, // sat/vB
     pub input_count: usize,
@@ -43,6 +85,13 @@ pub struct AnalyzedTx {
     pub is_rbf_signaling: bool,
     pub seen_at: DateTime<Utc>,
     pub prevouts_resolved: bool,
+    /// Inputs whose prevout was resolved; below `input_count` for a partially resolved tx.
+    #[serde(default)]
+    pub resolved_inputs: usize,
+    /// Resolved inputs spending outputs of unconfirmed txs (chained payments). Their age
+    /// is inherited from the parent's oldest input and they add no coin days destroyed.
+    #[serde(default)]
+    pub unconfirmed_parent_inputs: usize,
     /// Whether any output goes to a known exchange address.
     pub to_exchange: bool,
     /// Highest confidence of exchange tag matches on outputs.
@@ -55,6 +104,56 @@ pub struct AnalyzedTx {
     pub is_coinjoin: bool,
     /// Confidence of CoinJoin detection (0.0-1.0).
     pub coinjoin_confidence: f64,
+    /// Tagged entity spending in this tx after the longest silence, if any.
+    #[serde(default)]
+    pub dormant_entity: Option<String>,
+    /// Days since that entity's previous outgoing tx.
+    #[serde(default)]
+    pub dormancy_days: Option<f64>,
+    /// Distinct script types of the spent outputs, as far as known (Bitcoin Core's
+    /// `scriptPubKey.type` names).
+    #[serde(default)]
+    pub input_script_types: Vec<String>,
+    /// Distinct script types of the outputs.
+    #[serde(default)]
+    pub output_script_types: Vec<String>,
+    /// Pending txs this tx depends on, directly or through other pending txs.
+    #[serde(default)]
+    pub ancestor_count: usize,
+    /// Fee rate of this tx together with its pending ancestors (sat/vB), if every fee
+    /// in the package is known.
+    #[serde(default)]
+    pub ancestor_fee_rate: Option<f64>,
+    /// Pending parents whose fee rate this tx's package raises substantially
+    /// (child-pays-for-parent).
+    #[serde(default)]
+    pub cpfp_parents: Vec<String>,
+    /// Highest composite score among those parents.
+    #[serde(default)]
+    pub cpfp_parent_score: Option<f64>,
+    /// Share of pending txs (0.0-1.0) paying a lower effective fee rate, when first seen.
+    #[serde(default)]
+    pub fee_percentile: Option<f64>,
+    /// Projected minimum fee rate of the next block when first seen (sat/vB); `None` if
+    /// the mempool didn't fill a block or its state was unknown.
+    #[serde(default)]
+    pub next_block_min_fee: Option<f64>,
+    /// Pending vsize in blocks when first seen.
+    #[serde(default)]
+    pub blocks_pending: Option<f64>,
+}
+
+impl AnalyzedTx {
+    /// Output value excluding likely change, or all outputs if it wasn't computed.
+    pub fn economic_value(&self) -> u64 {
+        self.economic_value.unwrap_or(self.total_output_value)
+    }
+
+    /// The fee rate miners see for this tx: a child can't be mined before its
+    /// ancestors, so a cheaper package drags it down.
+    pub fn effective_fee_rate(&self) -> f64 {
+        self.ancestor_fee_rate.map_or(self.fee_rate, |r| r.min(self.fee_rate))
+    }
 }
 
 /// A scored transaction ready for UI display.
@@ -72,6 +171,9 @@ pub struct RuleScore {
     pub raw_value: f64,
     pub weight: f64,
     pub weighted_score: f64,
+    /// What the rule found, for display (e.g. how long an entity was dormant).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -146,6 +248,17 @@ mod tests {
         assert_eq!(AlertLevel::Low.emoji(), "⚪");
     }
 
+    #[test]
+    fn hash_to_hex_keeps_byte_order() {
+        let mut hash = [0u8; 32];
+        hash[0] = 0xab;
+        hash[31] = 0x01;
+        let hex = hash_to_hex(&hash);
+        assert_eq!(hex.len(), 64);
+        assert!(hex.starts_with("ab00"));
+        assert!(hex.ends_with("0001"));
+    }
+
     #[test]
     fn alert_level_custom_thresholds() {
         assert_eq!(AlertLevel::from_score_with_thresholds(90.0, 90.0, 70.0, 50.0), AlertLevel::Critical);
@@ -1,172 +1,29 @@
-use chrono::{DateTime, TimeZone, Utc};
+use bitcoin::hex::FromHex;
+use chrono::Utc;
 use tokio::sync::mpsc;
 #[allow(unused_imports)]
 use tracing::{debug, error, info, warn};
 
 use std::sync::{Arc, Mutex};
 
-use crate::core::mempool::{MempoolState, RemovalStats};
-use crate::core::tx::{is_rbf_signaling, parse_raw_tx, vsize};
-use crate::core::{AnalyzedTx, MempoolEvent, RemovalReason, ScoredTx};
-use crate::db::{SharedDatabase, SignalBatchEntry};
+use crate::core::block::{BlockStats, BlockSummary, ReorgInfo};
+use crate::core::cpfp::CpfpBump;
+use crate::core::mempool::{FeeSnapshot, MempoolState, RemovalStats, TxState};
+use crate::core::prevout::{InputStatus, PrevoutResolver, PrevoutStats};
+use crate::core::rbf::ReplacementDiff;
+use crate::core::tx::{
+    analyze_input, input_addresses, input_script_types, is_rbf_signaling, output_script_types, parse_raw_tx, vsize,
+};
+use crate::core::{hash_to_hex, AnalyzedTx, MempoolEvent, RemovalReason, ResyncReason, ScoredTx};
+use crate::db::{SharedDatabase, SignalBatchEntry, SignalOutcome, UtxoCacheStats};
 use crate::config::Config;
 use crate::notifications::Notifier;
 use crate::rpc::BitcoinRpc;
 use crate::signals::SignalEngine;
+use crate::signals::change::{detect_change, InputContext};
 use crate::signals::coinjoin::detect_coinjoin;
 use crate::tags::TagLookup;
-
-/// Resolved prevout info for a single input.
-#[derive(Debug)]
-struct ResolvedPrevout {
-    value: u64,           // satoshis
-    block_height: u32,
-    block_time: i64,      // unix timestamp
-    address: Option<String>,
-}
-
-/// Resolve a single prevout: cache first, then RPC.
-async fn resolve_prevout(
-    prev_txid: &str,
-    prev_vout: u32,
-    db: &SharedDatabase,
-    rpc: &BitcoinRpc,
-) -> Option<ResolvedPrevout> {
-    // 1) Check SQLite cache
-    match db.get_utxo(prev_txid, prev_vout) {
-        Ok(Some((value, _script_type, block_height, block_time))) => {
-            return Some(ResolvedPrevout { value, block_height, block_time, address: None });
-        }
-        Ok(None) => {} // not cached
-        Err(e) => {
-            debug!("DB cache lookup error for {prev_txid}:{prev_vout}: {e}");
-        }
-    }
-
-    // 2) RPC call
-    let result = rpc.getrawtransaction(prev_txid, true).await;
-    match result {
-        Ok(tx_json) => {
-            let vouts = tx_json.get("vout")?;
-            let vout_obj = vouts.get(prev_vout as usize)?;
-            let value_btc = vout_obj.get("value")?.as_f64()?;
-            let value_sats = (value_btc * 100_000_000.0).round() as u64;
-
-            let script_pub_key = vout_obj.get("scriptPubKey");
-
-            let script_type = script_pub_key
-                .and_then(|s| s.get("type"))
-                .and_then(|t| t.as_str())
-                .unwrap_or("unknown")
-                .to_string();
-
-            let address = script_pub_key
-                .and_then(|s| s.get("address"))
-                .and_then(|a| a.as_str())
-                .map(|s| s.to_string());
-
-            // Block info (may be null for unconfirmed)
-            let block_height = tx_json
-                .get("blockheight")
-                .or_else(|| tx_json.get("height"))
-                .and_then(|h| h.as_u64())
-                .unwrap_or(0) as u32;
-            let block_time = tx_json
-                .get("blocktime")
-                .and_then(|t| t.as_i64())
-                .unwrap_or(0);
-
-            // Cache it
-            if let Err(e) = db.cache_utxo(prev_txid, prev_vout, value_sats, &script_type, block_height, block_time) {
-                debug!("Failed to cache UTXO {prev_txid}:{prev_vout}: {e}");
-            }
-
-            Some(ResolvedPrevout {
-                value: value_sats,
-                block_height,
-                block_time,
-                address,
-            })
-        }
-        Err(e) => {
-            debug!("RPC getrawtransaction failed for {prev_txid}: {e}");
-            None
-        }
-    }
-}
-
-/// Resolve all prevouts for a parsed transaction. Returns enriched fields.
-async fn resolve_all_prevouts(
-    parsed: &bitcoin::Transaction,
-    db: &SharedDatabase,
-    rpc: &BitcoinRpc,
-) -> (u64, Option<DateTime<Utc>>, Option<u32>, Option<f64>, usize, Vec<String>) {
-    // Returns: (total_input_value, oldest_input_time, oldest_input_height, cdd, resolved_count, input_addresses)
-    let mut total_input_value: u64 = 0;
-    let mut oldest_time: Option<i64> = None;
-    let mut oldest_height: Option<u32> = None;
-    let mut cdd: f64 = 0.0;
-    let mut resolved_count: usize = 0;
-    let mut input_addresses: Vec<String> = Vec::new();
-    let now = Utc::now();
-
-    for input in &parsed.input {
-        // Skip coinbase inputs
-        let null_txid: [u8; 32] = [0u8; 32];
-        if AsRef::<[u8; 32]>::as_ref(&input.previous_output.txid) == &null_txid {
-            continue;
-        }
-
-        let prev_txid = input.previous_output.txid.to_string();
-        let prev_vout = input.previous_output.vout;
-
-        if let Some(prevout) = resolve_prevout(&prev_txid, prev_vout, db, rpc).await {
-            total_input_value += prevout.value;
-            resolved_count += 1;
-
-            if let Some(addr) = prevout.address {
-                input_addresses.push(addr);
-            }
-
-            if prevout.block_time > 0 {
-                // Track oldest
-                match oldest_time {
-                    Some(ot) if prevout.block_time < ot => {
-                        oldest_time = Some(prevout.block_time);
-                    }
-                    None => {
-                        oldest_time = Some(prevout.block_time);
-                    }
-                    _ => {}
-                }
-                match oldest_height {
-                    Some(oh) if prevout.block_height < oh => {
-                        oldest_height = Some(prevout.block_height);
-                    }
-                    None if prevout.block_height > 0 => {
-                        oldest_height = Some(prevout.block_height);
-                    }
-                    _ => {}
-                }
-
-                // CDD: value_btc * age_days
-                let input_time = Utc.timestamp_opt(prevout.block_time, 0).single();
-                if let Some(it) = input_time {
-                    let age_days = (now - it).num_seconds() as f64 / 86400.0;
-                    if age_days > 0.0 {
-                        let value_btc = prevout.value as f64 / 100_000_000.0;
-                        cdd += value_btc * age_days;
-                    }
-                }
-            }
-        }
-    }
-
-    let oldest_dt = oldest_time.and_then(|t| Utc.timestamp_opt(t, 0).single());
-    let cdd_opt = if resolved_count > 0 && cdd > 0.0 { Some(cdd) } else { None };
-
-    (total_input_value, oldest_dt, oldest_height, cdd_opt, resolved_count, input_addresses)
-}
+use crate::tags::activity::{Dormancy, EntityActivity};
 
 /// How often to send stats to UI (every N txs or every N seconds).
 #[allow(dead_code)]
@@ -176,19 +33,65 @@ const STATS_TIME_INTERVAL: std::time::Duration = std::time::Duration::from_secs(
 const PRUNE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
 const PRUNE_MAX_AGE: chrono::Duration = chrono::Duration::minutes(5);
 
+/// Number of recently processed block hashes remembered to drop duplicate notifications
+/// (`hashblock` and sequence 'C' both announce every block).
+const RECENT_BLOCKS: usize = 16;
+
+/// Mempool txs fetched per batch request during a sync.
+const SYNC_BATCH_SIZE: usize = 1000;
+
 /// Min score to persist a signal (noise filter).
 #[allow(dead_code)]
 const SIGNAL_MIN_SCORE: f64 = 10.0;
 /// Flush signal batch to DB every N seconds.
 const SIGNAL_FLUSH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
-fn send_stats(state: &MempoolState, ui_tx: &mpsc::UnboundedSender<PipelineOutput>) {
+fn send_stats(state: &MempoolState, prevout_stats: &PrevoutStats, ui_tx: &mpsc::UnboundedSender<PipelineOutput>) {
     let _ = ui_tx.send(PipelineOutput::MempoolStats {
         pending_count: state.pending_count(),
         total_vsize: state.total_vsize(),
         total_fees: state.total_fees(),
         fee_histogram: state.fee_histogram(),
         removal_stats: state.removal_stats().clone(),
+        prevout_stats: prevout_stats.clone(),
+    });
+}
+
+/// Drop cache rows for outpoints spent by a block, then evict down to the size budget
+/// (`max_rows` 0 = unlimited) and report the cache size. Runs off the pipeline task.
+fn spawn_cache_maintenance(
+    db: SharedDatabase,
+    spent: Vec<(String, u32)>,
+    max_rows: u64,
+    ui_tx: mpsc::UnboundedSender<PipelineOutput>,
+) {
+    tokio::spawn(async move {
+        let (spent_removed, evicted) = db
+            .write_async(move |db| {
+                let spent_removed = db.delete_utxos(&spent).unwrap_or_else(|e| {
+                    error!("Failed to drop spent UTXO cache rows: {e}");
+                    0
+                });
+                let evicted = if max_rows > 0 {
+                    db.evict_utxos(max_rows).unwrap_or_else(|e| {
+                        error!("UTXO cache eviction failed: {e}");
+                        0
+                    })
+                } else {
+                    0
+                };
+                (spent_removed, evicted)
+            })
+            .await;
+        if spent_removed + evicted > 0 {
+            debug!("UTXO cache: {spent_removed} spent rows removed, {evicted} evicted");
+        }
+        match db.read_async(|db| db.utxo_cache_stats()).await {
+            Ok(stats) => {
+                let _ = ui_tx.send(PipelineOutput::UtxoCacheStats(UtxoCacheStats { spent_removed, evicted, ..stats }));
+            }
+            Err(e) => warn!("Failed to read UTXO cache stats: {e}"),
+        }
     });
 }
 
@@ -209,7 +112,7 @@ fn spawn_signal_flusher(
                         None => {
                             // Channel closed, flush remaining
                             if !buffer.is_empty() {
-                                if let Err(e) = db.store_signals_batch(&buffer) {
+                                if let Err(e) = db.store_signals_batch(buffer).await {
                                     error!("Failed to flush final signal batch: {e}");
                                 }
                             }
@@ -222,7 +125,7 @@ fn spawn_signal_flusher(
                     if !buffer.is_empty() {
                         let batch: Vec<SignalBatchEntry> = buffer.drain(..).collect();
                         let count = batch.len();
-                        if let Err(e) = db.store_signals_batch(&batch) {
+                        if let Err(e) = db.store_signals_batch(batch).await {
                             error!("Failed to flush {count} signals to DB: {e}");
                         } else {
                             debug!("Flushed {count} signals to DB");
@@ -234,219 +137,1134 @@ fn spawn_signal_flusher(
     });
 }
 
-/// Run the pipeline: receive MempoolEvents, analyze, score, forward to UI.
-pub async fn run_pipeline(
-    mut rx: mpsc::UnboundedReceiver<MempoolEvent>,
+/// Where a transaction handed to the pipeline came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TxOrigin {
+    /// Live arrival from the ZMQ stream.
+    Live,
+    /// Already in the node's mempool when we synced via RPC. `stored` if a signal was
+    /// persisted for it before, e.g. ahead of a restart; `entered` is when it entered
+    /// the node's mempool (unix seconds), if the node still had its entry.
+    Backlog { stored: bool, entered: Option<i64> },
+}
+
+/// How much of the analysis a transaction gets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Analysis {
+    /// Mempool txs: prevouts from the node if needed, cluster expansion and entity
+    /// activity recorded.
+    Full,
+    /// Txs seen only in a block: prevouts from the cache only and no lasting effects on
+    /// tags, entity activity or resolution stats.
+    BlockOnly,
+}
+
+/// Counts from reconciling our mempool state with the node.
+#[derive(Debug, Clone, Copy, Default)]
+struct SyncSummary {
+    added: usize,
+    removed: usize,
+}
+
+/// Outcome of a mempool sync.
+enum SyncOutcome {
+    Synced(SyncSummary),
+    /// The node couldn't be queried; state is left as is.
+    Skipped,
+    UiClosed,
+}
+
+/// Long-lived pipeline state shared by live event handling and mempool syncs.
+struct Pipeline {
     ui_tx: mpsc::UnboundedSender<PipelineOutput>,
     db: SharedDatabase,
     rpc: BitcoinRpc,
+    /// Replaying a capture: the node's current mempool and chain say nothing about the
+    /// recorded stream, so resyncs and block fetches are skipped.
+    offline: bool,
+    prevout_resolver: PrevoutResolver,
     tag_lookup: Arc<Mutex<TagLookup>>,
-    config: Config,
-) {
-    let notifier = Notifier::new(&config.notifications);
-    let engine = SignalEngine::with_config(
-        config.signals.weights.clone(),
-        config.signals.alert_thresholds.clone(),
-    );
-    let signal_min_score = config.signals.min_score_persist;
-    let stats_tx_interval = config.ui.stats_update_interval_txs as u64;
-    let mut mempool = MempoolState::new();
-    let mut tx_count: u64 = 0;
-    let mut block_count: u64 = 0;
-    let mut resolved_total: u64 = 0;
-    let mut unresolved_total: u64 = 0;
-    let mut last_stats_time = std::time::Instant::now();
-    let mut last_prune_time = std::time::Instant::now();
-    let mut current_block_height: u32 = 0;
-
-    // Signal batch flusher (non-blocking DB writes)
-    let (signal_tx, signal_rx) = mpsc::unbounded_channel::<SignalBatchEntry>();
-    spawn_signal_flusher(db.clone(), signal_rx);
+    /// Last outgoing tx per tagged entity, for dormancy.
+    entity_activity: EntityActivity,
+    notifier: Notifier,
+    engine: SignalEngine,
+    signal_tx: mpsc::UnboundedSender<SignalBatchEntry>,
+    signal_min_score: f64,
+    /// Store raw tx bytes with persisted signals.
+    store_raw_tx: bool,
+    stats_tx_interval: u64,
+    mempool: MempoolState,
+    /// Fee levels of the mempool as of the last stats update, for relative fee scoring.
+    fee_snapshot: FeeSnapshot,
+    tx_count: u64,
+    block_count: u64,
+    /// Txs first seen in a block, never announced to our mempool.
+    block_only_count: u64,
+    /// Per-input prevout resolution outcomes.
+    prevout_stats: PrevoutStats,
+    /// UTXO cache size budget in rows (0 = unlimited).
+    utxo_cache_max_rows: u64,
+    /// Txs scored with partial prevouts because resolution ran out of time.
+    budget_exceeded_total: u64,
+    last_stats_time: std::time::Instant,
+    last_prune_time: std::time::Instant,
+    current_block_height: u32,
+    /// Node mempool sequence at the last sync; events at or below it are already reflected.
+    last_sync_sequence: Option<u64>,
+    /// Hashes of the last processed blocks, newest last.
+    recent_blocks: std::collections::VecDeque<String>,
+    /// Reorg in progress (consecutive disconnects); cleared on the next connect.
+    reorg: Option<ReorgInfo>,
+}
 
-    info!("Pipeline started with prevout resolution, mempool state tracking, and signal persistence");
+impl Pipeline {
+    fn new(
+        ui_tx: mpsc::UnboundedSender<PipelineOutput>,
+        db: SharedDatabase,
+        rpc: BitcoinRpc,
+        tag_lookup: Arc<Mutex<TagLookup>>,
+        config: &Config,
+        offline: bool,
+    ) -> Self {
+        // Signal batch flusher (non-blocking DB writes)
+        let (signal_tx, signal_rx) = mpsc::unbounded_channel::<SignalBatchEntry>();
+        spawn_signal_flusher(db.clone(), signal_rx);
+        let prevout_resolver = PrevoutResolver::new(db.clone(), rpc.clone(), &config.bitcoin);
+        let entity_activity = EntityActivity::load_from_db(&db, !offline);
 
-    while let Some(event) = rx.recv().await {
-        match event {
-            MempoolEvent::TxAdded { txid: _, raw } => {
-                let parsed = match parse_raw_tx(&raw) {
-                    Ok(tx) => tx,
-                    Err(e) => {
-                        debug!("Failed to parse raw tx: {e}");
-                        continue;
-                    }
-                };
+        Self {
+            ui_tx,
+            db,
+            rpc,
+            offline,
+            prevout_resolver,
+            tag_lookup,
+            entity_activity,
+            notifier: Notifier::new(&config.notifications),
+            engine: SignalEngine::with_config(&config.signals),
+            signal_tx,
+            signal_min_score: config.signals.min_score_persist,
+            store_raw_tx: config.signals.store_raw_tx,
+            stats_tx_interval: (config.ui.stats_update_interval_txs as u64).max(1),
+            mempool: MempoolState::new(),
+            fee_snapshot: FeeSnapshot::default(),
+            tx_count: 0,
+            block_count: 0,
+            block_only_count: 0,
+            prevout_stats: PrevoutStats::default(),
+            utxo_cache_max_rows: config.database.utxo_cache_max_rows,
+            budget_exceeded_total: 0,
+            last_stats_time: std::time::Instant::now(),
+            last_prune_time: std::time::Instant::now(),
+            current_block_height: 0,
+            last_sync_sequence: None,
+            recent_blocks: std::collections::VecDeque::with_capacity(RECENT_BLOCKS),
+            reorg: None,
+        }
+    }
 
-                let tx_vsize = vsize(&parsed);
-                let rbf = is_rbf_signaling(&parsed);
-                let txid_str = parsed.compute_txid().to_string();
-                let total_output_value: u64 = parsed.output.iter().map(|o| o.value.to_sat()).sum();
-                let input_count = parsed.input.len();
-                let output_count = parsed.output.len();
+    /// Take a new fee snapshot for scoring and send the mempool stats to the UI.
+    fn refresh_mempool_stats(&mut self) {
+        self.fee_snapshot = self.mempool.fee_snapshot();
+        send_stats(&self.mempool, &self.prevout_stats, &self.ui_tx);
+    }
 
-                // Resolve prevouts
-                let (total_input_value, oldest_input_time, oldest_input_height, coin_days_destroyed, resolved_count, input_addresses) =
-                    resolve_all_prevouts(&parsed, &db, &rpc).await;
+    /// Whether an event with this mempool sequence predates the last sync snapshot.
+    fn is_stale(&self, mempool_sequence: Option<u64>) -> bool {
+        matches!((mempool_sequence, self.last_sync_sequence), (Some(seq), Some(synced)) if seq <= synced)
+    }
 
-                let prevouts_resolved = resolved_count == input_count;
-                resolved_total += resolved_count as u64;
-                unresolved_total += (input_count - resolved_count) as u64;
+    /// Parse a raw transaction and enrich it with prevouts, exchange tags and CoinJoin detection.
+    /// Returns `None` if the tx can't be parsed or is already pending in our state.
+    /// `time` (unix seconds) is when the tx was broadcast, as far as we know.
+    async fn analyze(
+        &mut self,
+        raw: &[u8],
+        analysis: Analysis,
+        time: i64,
+    ) -> Option<(AnalyzedTx, bitcoin::Transaction)> {
+        let parsed = match parse_raw_tx(raw) {
+            Ok(tx) => tx,
+            Err(e) => {
+                debug!("Failed to parse raw tx: {e}");
+                return None;
+            }
+        };
 
-                // Calculate fee (only if we have input values)
-                let fee = if total_input_value > 0 {
-                    total_input_value.saturating_sub(total_output_value)
-                } else {
-                    0
-                };
-                let fee_rate = if total_input_value > 0 && tx_vsize > 0 {
-                    fee as f64 / tx_vsize as f64
-                } else {
-                    0.0
-                };
+        let txid_str = parsed.compute_txid().to_string();
+        if self.mempool.is_pending(&txid_str) {
+            // Already ingested (e.g. delivered by both the startup sync and ZMQ)
+            return None;
+        }
 
-                // CoinJoin detection (before tag operations so we can guard clustering)
-                let coinjoin_result = detect_coinjoin(&parsed);
+        let tx_vsize = vsize(&parsed);
+        let rbf = is_rbf_signaling(&parsed);
+        let total_output_value: u64 = parsed.output.iter().map(|o| o.value.to_sat()).sum();
+        let input_count = parsed.input.len();
+        let output_count = parsed.output.len();
 
-                // Check outputs against known exchange addresses
-                let (output_matches, input_matches) = {
-                    let tl = tag_lookup.lock().unwrap();
-                    (tl.check_outputs(&parsed), tl.check_input_addresses(&input_addresses))
-                };
-                let to_exchange = !output_matches.is_empty();
-                let to_exchange_confidence = output_matches
-                    .iter()
-                    .map(|m| m.tag.confidence)
-                    .fold(0.0_f64, f64::max);
+        // Resolve prevouts (bounded by the time budget; partial results are scored as-is)
+        let resolution = match analysis {
+            Analysis::Full => self.prevout_resolver.resolve(&parsed, &self.mempool).await,
+            Analysis::BlockOnly => self.prevout_resolver.resolve_cached(&parsed, &self.mempool).await,
+        };
+        let total_input_value = resolution.total_input_value;
+        let resolved_count = resolution.resolved_count;
 
-                let from_exchange = !input_matches.is_empty();
-                let from_exchange_confidence = input_matches
-                    .iter()
-                    .map(|m| m.tag.confidence)
-                    .fold(0.0_f64, f64::max);
+        let prevouts_resolved = resolved_count == input_count;
+        if analysis == Analysis::Full {
+            self.prevout_stats.record(&resolution.input_status);
+        }
+        if analysis == Analysis::Full && resolved_count < input_count {
+            let unresolved: Vec<String> = resolution
+                .input_status
+                .iter()
+                .enumerate()
+                .filter(|(_, s)| !s.is_resolved() && **s != InputStatus::Coinbase)
+                .map(|(i, s)| match analyze_input(&parsed.input[i]) {
+                    Some(input) => format!("{i}: {s:?} ({})", input.script_type),
+                    None => format!("{i}: {s:?}"),
+                })
+                .collect();
+            if !unresolved.is_empty() {
+                debug!("Unresolved inputs of {txid_str}: {}", unresolved.join(", "));
+            }
+        }
+        if analysis == Analysis::Full && resolution.timed_out() {
+            self.budget_exceeded_total += 1;
+            debug!(
+                "Prevout budget exceeded for {txid_str}: {resolved_count}/{input_count} inputs resolved"
+            );
+        }
+
+        // Calculate fee (only if we have all input values; a partial sum would understate it)
+        let fee = if prevouts_resolved && total_input_value > 0 {
+            total_input_value.saturating_sub(total_output_value)
+        } else {
+            0
+        };
+        let fee_rate = if fee > 0 && tx_vsize > 0 {
+            fee as f64 / tx_vsize as f64
+        } else {
+            0.0
+        };
+
+        // Unconfirmed ancestors: package fee rate and parents this tx pays for (CPFP)
+        let ancestors = self.mempool.ancestor_info(&parsed, prevouts_resolved.then_some(fee), tx_vsize);
+        let cpfp_parent_score = ancestors
+            .bumped_parents
+            .iter()
+            .filter_map(|p| self.mempool.get(p).and_then(|e| e.score))
+            .reduce(f64::max);
+
+        // CoinJoin detection (before tag operations so we can guard clustering)
+        let coinjoin_result = detect_coinjoin(&parsed);
+
+        // Input addresses from the inputs themselves where possible, else from prevouts
+        let input_addresses = input_addresses(&parsed, &resolution.input_addresses);
+
+        let input_script_types = input_script_types(&parsed, &resolution.input_script_types);
+        let output_script_types = output_script_types(&parsed);
+
+        // Change detection: the economic value leaves out outputs returning to the sender.
+        // Its heuristics assume one sender, so CoinJoins count all outputs.
+        let economic_value = if coinjoin_result.is_coinjoin {
+            total_output_value
+        } else {
+            let change = detect_change(&parsed, &InputContext {
+                script_types: &input_script_types,
+                addresses: &input_addresses,
+                smallest_value: resolution.smallest_input_value.filter(|_| prevouts_resolved),
+            });
+            let change_outputs: Vec<usize> = change.change_outputs().collect();
+            if !change_outputs.is_empty() {
+                debug!("Likely change outputs of {txid_str}: {change_outputs:?}");
+            }
+            change.economic_value
+        };
+
+        // Check outputs against known exchange addresses
+        let (output_matches, input_matches) = {
+            let tl = self.tag_lookup.lock().unwrap();
+            (tl.check_outputs(&parsed), tl.check_input_addresses(&input_addresses))
+        };
+        let to_exchange = !output_matches.is_empty();
+        let to_exchange_confidence = output_matches
+            .iter()
+            .map(|m| m.tag.confidence)
+            .fold(0.0_f64, f64::max);
+
+        let from_exchange = !input_matches.is_empty();
+        let from_exchange_confidence = input_matches
+            .iter()
+            .map(|m| m.tag.confidence)
+            .fold(0.0_f64, f64::max);
+
+        // Cluster expansion: tag unknown input addresses via CIOH
+        if analysis == Analysis::Full && !input_addresses.is_empty() {
+            let mut tl = self.tag_lookup.lock().unwrap();
+            tl.expand_from_tx(&input_addresses, coinjoin_result.is_coinjoin);
+        }
+
+        // Tagged entities spending here: how long since their last outgoing tx
+        let mut entities: Vec<&str> = input_matches.iter().map(|m| m.tag.entity.as_str()).collect();
+        entities.sort_unstable();
+        entities.dedup();
+        let newest_input_time = resolution.newest_input_time.filter(|_| prevouts_resolved);
+        let dormancy = match analysis {
+            Analysis::Full => self.entity_activity.record_spend(&entities, &txid_str, time, newest_input_time),
+            Analysis::BlockOnly => self.entity_activity.dormancy(&entities, time, newest_input_time),
+        };
+        // An RBF replacement ends the same silence as the tx it replaces, which already
+        // recorded the entity as active
+        let dormancy = self
+            .mempool
+            .conflicts(&parsed)
+            .into_iter()
+            .filter_map(|e| Some(Dormancy { entity: e.tx.dormant_entity.clone()?, days: e.tx.dormancy_days? }))
+            .chain(dormancy)
+            .max_by(|a, b| a.days.total_cmp(&b.days));
+        let (dormant_entity, dormancy_days) = match dormancy {
+            Some(d) => (Some(d.entity), Some(d.days)),
+            None => (None, None),
+        };
+
+        let mut analyzed = AnalyzedTx {
+            txid: txid_str,
+            raw_size: raw.len(),
+            vsize: tx_vsize,
+            total_input_value,
+            total_output_value,
+            economic_value: Some(economic_value),
+            fee,
+            fee_rate,
+            input_count,
+            output_count,
+            oldest_input_height: resolution.oldest_input_height,
+            oldest_input_time: resolution.oldest_input_time,
+            coin_days_destroyed: resolution.coin_days_destroyed,
+            is_rbf_signaling: rbf,
+            seen_at: Utc::now(),
+            prevouts_resolved,
+            resolved_inputs: resolved_count,
+            unconfirmed_parent_inputs: resolution.unconfirmed_parent_count,
+            to_exchange,
+            to_exchange_confidence,
+            from_exchange,
+            from_exchange_confidence,
+            is_coinjoin: coinjoin_result.is_coinjoin,
+            coinjoin_confidence: coinjoin_result.confidence,
+            dormant_entity,
+            dormancy_days,
+            input_script_types,
+            output_script_types,
+            ancestor_count: ancestors.count,
+            ancestor_fee_rate: ancestors.package_fee_rate,
+            cpfp_parents: ancestors.bumped_parents,
+            cpfp_parent_score,
+            fee_percentile: None,
+            next_block_min_fee: self.fee_snapshot.next_block_min_fee,
+            blocks_pending: self.fee_snapshot.blocks_pending,
+        };
+        if prevouts_resolved {
+            analyzed.fee_percentile = self.fee_snapshot.percentile(analyzed.effective_fee_rate());
+        }
+        Some((analyzed, parsed))
+    }
+
+    /// Analyze, score and record a mempool transaction, then forward it to the UI.
+    /// Returns false once the UI channel is closed.
+    async fn ingest_tx(&mut self, raw: &[u8], origin: TxOrigin) -> bool {
+        let time = match origin {
+            TxOrigin::Backlog { entered: Some(entered), .. } => entered,
+            _ => Utc::now().timestamp(),
+        };
+        let Some((analyzed, parsed)) = self.analyze(raw, Analysis::Full, time).await else {
+            return true;
+        };
+
+        let scored = self.engine.score(&analyzed);
+        self.tx_count += 1;
+
+        // Add to mempool state; txs double-spending the same outpoints count as replaced
+        let replaced = self.mempool.add_scored_tx(analyzed, scored.composite_score, scored.alert_level, &parsed);
+        for old_txid in &replaced {
+            self.handle_replacement(old_txid, &scored, &parsed.output, origin);
+            self.record_outcome(old_txid, None);
+        }
+        for parent_txid in &scored.tx.cpfp_parents {
+            self.handle_cpfp(parent_txid, &scored, origin);
+        }
+
+        // Desktop notification (fire-and-forget, cooldown-protected).
+        // Backlog txs were broadcast while we weren't watching — don't alert on them.
+        if origin == TxOrigin::Live {
+            self.notifier.notify(&scored);
+        }
+
+        // Persist signal if score is above noise threshold (non-blocking).
+        // Backlog txs may already have been stored before a restart.
+        if scored.composite_score > self.signal_min_score && !matches!(origin, TxOrigin::Backlog { stored: true, .. }) {
+            let rule_scores_json = serde_json::to_string(&scored.rule_scores).unwrap_or_default();
+            let analyzed_json = serde_json::to_string(&scored.tx).unwrap_or_default();
+            let _ = self.signal_tx.send(SignalBatchEntry {
+                txid: scored.tx.txid.clone(),
+                score: scored.composite_score,
+                alert_level: format!("{:?}", scored.alert_level),
+                rule_scores_json,
+                to_exchange: scored.tx.to_exchange,
+                total_input_value: scored.tx.total_input_value,
+                fee_rate: scored.tx.fee_rate,
+                coin_days_destroyed: scored.tx.coin_days_destroyed,
+                block_height_seen: self.current_block_height,
+                analyzed_json,
+                raw_tx: self.store_raw_tx.then(|| raw.to_vec()),
+            });
+        }
+
+        if self.tx_count % 1000 == 0 {
+            info!(
+                "Pipeline: {} txs, {} blocks, prevouts resolved: {}, unresolved: {}, budget exceeded: {} txs, mempool pending: {}",
+                self.tx_count,
+                self.block_count,
+                self.prevout_stats.resolved(),
+                self.prevout_stats.unresolved(),
+                self.budget_exceeded_total,
+                self.mempool.pending_count()
+            );
+        }
+
+        if self.ui_tx.send(PipelineOutput::NewTx(scored)).is_err() {
+            info!("UI channel closed, stopping pipeline");
+            return false;
+        }
+
+        // Periodically send stats
+        let now = std::time::Instant::now();
+        if self.tx_count % self.stats_tx_interval == 0
+            || now.duration_since(self.last_stats_time) >= STATS_TIME_INTERVAL
+        {
+            self.refresh_mempool_stats();
+            self.last_stats_time = now;
+        }
+
+        // Periodically prune old entries
+        if now.duration_since(self.last_prune_time) >= PRUNE_INTERVAL {
+            self.mempool.prune_old(PRUNE_MAX_AGE);
+            self.last_prune_time = now;
+        }
+
+        true
+    }
+
+    /// Diff a replaced tx against its replacement; persist the link if the original was a
+    /// signal and alert if it was High/Critical.
+    fn handle_replacement(&mut self, old_txid: &str, new: &ScoredTx, new_outputs: &[bitcoin::TxOut], origin: TxOrigin) {
+        let Some(old) = self.mempool.get(old_txid) else {
+            return;
+        };
+        let diff = ReplacementDiff::compute(old, new, new_outputs);
+        debug!("Tx {old_txid} {}", diff.summary());
+
+        if diff.old_score > self.signal_min_score {
+            let stored = diff.clone();
+            self.db.write_detached("store replacement", move |db| db.store_replacement(&stored));
+        }
+        if diff.is_alert() {
+            info!("Alert tx {old_txid} {}", diff.summary());
+            if origin == TxOrigin::Live {
+                self.notifier.notify_replacement(&diff);
+            }
+            let _ = self.ui_tx.send(PipelineOutput::Replacement(diff));
+        }
+    }
+
+    /// A tx bumped a pending parent via CPFP; persist it on the parent's signal and show
+    /// it on the parent's alert if the child is a live arrival.
+    fn handle_cpfp(&self, parent_txid: &str, child: &ScoredTx, origin: TxOrigin) {
+        let Some(parent) = self.mempool.get(parent_txid) else {
+            return;
+        };
+        let bump = CpfpBump::compute(parent, child);
+        debug!("Tx {parent_txid} {} ({})", bump.summary(), bump.child_txid);
+
+        if bump.parent_score > self.signal_min_score {
+            let stored = bump.clone();
+            self.db.write_detached("store cpfp bump", move |db| db.store_cpfp_bump(&stored));
+        }
+        if bump.is_alert() && origin == TxOrigin::Live {
+            info!("Alert tx {parent_txid} {}", bump.summary());
+            let _ = self.ui_tx.send(PipelineOutput::CpfpBump(bump));
+        }
+    }
 
-                // Cluster expansion: tag unknown input addresses via CIOH
-                if !input_addresses.is_empty() {
-                    let mut tl = tag_lookup.lock().unwrap();
-                    tl.expand_from_tx(&input_addresses, coinjoin_result.is_coinjoin);
+    /// Refresh the current chain height from the node.
+    async fn refresh_chain_tip(&mut self) {
+        match self.rpc.getblockchaininfo().await {
+            Ok(info) => {
+                if let Some(height) = info.get("blocks").and_then(|b| b.as_u64()) {
+                    self.current_block_height = height as u32;
+                    let _ = self.ui_tx.send(PipelineOutput::BlockConnected { height: height as u32 });
                 }
+            }
+            Err(e) => warn!("getblockchaininfo failed: {e}"),
+        }
+    }
+
+    /// Reconcile our state with the node's mempool via RPC: pending txids the node no
+    /// longer has are marked removed, and every tx we don't track yet is ingested.
+    ///
+    /// Used both at startup and after a gap in the ZMQ stream. Events still queued in the
+    /// channel carry a mempool sequence at or below the snapshot's and are skipped
+    /// afterwards; queued txs without a sequence are deduplicated by txid in `analyze`.
+    async fn reconcile_mempool(&mut self) -> SyncOutcome {
+        let snapshot = match self.rpc.getrawmempool(true).await {
+            Ok(v) => v,
+            Err(e) => {
+                warn!("Mempool sync skipped, getrawmempool failed: {e}");
+                return SyncOutcome::Skipped;
+            }
+        };
+        let txids: Vec<String> = snapshot
+            .get("txids")
+            .and_then(|t| t.as_array())
+            .map(|a| a.iter().filter_map(|t| t.as_str().map(String::from)).collect())
+            .unwrap_or_default();
+        if let Some(seq) = snapshot.get("mempool_sequence").and_then(|s| s.as_u64()) {
+            self.last_sync_sequence = Some(seq);
+        }
 
-                let analyzed = AnalyzedTx {
-                    txid: txid_str,
-                    raw_size: raw.len(),
-                    vsize: tx_vsize,
-                    total_input_value,
-                    total_output_value,
-                    fee,
-                    fee_rate,
-                    input_count,
-                    output_count,
-                    oldest_input_height,
-                    oldest_input_time,
-                    coin_days_destroyed,
-                    is_rbf_signaling: rbf,
-                    seen_at: Utc::now(),
-                    prevouts_resolved,
-                    to_exchange,
-                    to_exchange_confidence,
-                    from_exchange,
-                    from_exchange_confidence,
-                    is_coinjoin: coinjoin_result.is_coinjoin,
-                    coinjoin_confidence: coinjoin_result.confidence,
+        info!("Syncing {} mempool txs from node", txids.len());
+        let started = std::time::Instant::now();
+        let mut summary = SyncSummary::default();
+
+        // Drop pending entries the node no longer knows about
+        let node_txids: std::collections::HashSet<&str> = txids.iter().map(|t| t.as_str()).collect();
+        for txid in self.mempool.pending_txids() {
+            if !node_txids.contains(txid.as_str()) {
+                self.mempool.remove_unexplained(&txid);
+                self.record_outcome(&txid, None);
+                summary.removed += 1;
+            }
+        }
+
+        // Which of the new ones were persisted before, in one read
+        let new_txids: Vec<String> = txids.iter().filter(|t| !self.mempool.is_pending(t)).cloned().collect();
+        let stored = self.db.stored_signals_async(new_txids.clone()).await.unwrap_or_else(|e| {
+            warn!("Failed to look up stored signals: {e}");
+            std::collections::HashSet::new()
+        });
+
+        let mut fetched = 0;
+        for chunk in new_txids.chunks(SYNC_BATCH_SIZE) {
+            let (responses, entries) =
+                tokio::join!(self.rpc.getrawtransactions(chunk), self.rpc.getmempoolentries(chunk));
+            let responses = match responses {
+                Ok(responses) => responses,
+                Err(e) => {
+                    warn!("getrawtransaction batch of {} mempool txs failed: {e}", chunk.len());
+                    continue;
+                }
+            };
+            // Entry times date the backlog's entity activity; without one it counts as now
+            let entered: Vec<Option<i64>> = match entries {
+                Ok(entries) => entries.into_iter().map(|e| e.ok()?.get("time")?.as_i64()).collect(),
+                Err(e) => {
+                    debug!("getmempoolentry batch of {} mempool txs failed: {e}", chunk.len());
+                    vec![None; chunk.len()]
+                }
+            };
+            for ((txid, response), entered) in chunk.iter().zip(responses).zip(entered) {
+                // The tx may have left the mempool since the snapshot; just skip it.
+                let raw = match response {
+                    Ok(v) => v.get("hex").and_then(|h| h.as_str()).and_then(|h| Vec::<u8>::from_hex(h).ok()),
+                    Err(e) => {
+                        debug!("getrawtransaction failed for mempool tx {txid}: {e}");
+                        None
+                    }
                 };
+                let Some(raw) = raw else { continue };
 
-                // Add to mempool state
-                mempool.add_tx(analyzed.clone());
-
-                let scored = engine.score(&analyzed);
-                tx_count += 1;
-
-                // Desktop notification (fire-and-forget, cooldown-protected)
-                notifier.notify(&scored);
-
-                // Persist signal if score is above noise threshold (non-blocking)
-                if scored.composite_score > signal_min_score {
-                    let rule_scores_json = serde_json::to_string(&scored.rule_scores).unwrap_or_default();
-                    let _ = signal_tx.send(SignalBatchEntry {
-                        txid: scored.tx.txid.clone(),
-                        score: scored.composite_score,
-                        alert_level: format!("{:?}", scored.alert_level),
-                        rule_scores_json,
-                        to_exchange: scored.tx.to_exchange,
-                        total_input_value: scored.tx.total_input_value,
-                        fee_rate: scored.tx.fee_rate,
-                        coin_days_destroyed: scored.tx.coin_days_destroyed,
-                        block_height_seen: current_block_height,
-                    });
+                if !self.ingest_tx(&raw, TxOrigin::Backlog { stored: stored.contains(txid), entered }).await {
+                    return SyncOutcome::UiClosed;
                 }
+                summary.added += 1;
+            }
+            fetched += chunk.len();
+            info!("Mempool sync: {fetched}/{} txs", new_txids.len());
+        }
+
+        info!(
+            "Mempool sync done in {:.1}s: {} added, {} removed, pending: {}",
+            started.elapsed().as_secs_f64(),
+            summary.added,
+            summary.removed,
+            self.mempool.pending_count()
+        );
+        self.refresh_mempool_stats();
+        self.last_stats_time = std::time::Instant::now();
+        SyncOutcome::Synced(summary)
+    }
+
+    /// Resync after the event stream reported missed messages.
+    /// Returns false once the UI channel is closed.
+    async fn handle_resync(&mut self, reason: ResyncReason) -> bool {
+        if self.offline {
+            warn!("Ignoring resync request during replay: {reason}");
+            return true;
+        }
+        warn!("Resyncing mempool state: {reason}");
+        self.refresh_chain_tip().await;
+        match self.reconcile_mempool().await {
+            SyncOutcome::Synced(summary) => self
+                .ui_tx
+                .send(PipelineOutput::Resynced {
+                    reason: reason.to_string(),
+                    added: summary.added,
+                    removed: summary.removed,
+                })
+                .is_ok(),
+            SyncOutcome::UiClosed => false,
+            SyncOutcome::Skipped => true,
+        }
+    }
+
+    /// A tx delivered on `rawtx` as part of a block without a mempool announcement.
+    /// Txs we already scored in the mempool are replays and ignored; the rest were never
+    /// in our mempool and are scored for the "seen only in block" view only — they stay
+    /// out of the live feed, notifications and the `signals` table. They get the light
+    /// `Analysis::BlockOnly`.
+    /// Returns false once the UI channel is closed.
+    async fn handle_block_only_tx(&mut self, txid: [u8; 32], raw: &[u8]) -> bool {
+        if self.mempool.contains(&hash_to_hex(&txid)) {
+            return true;
+        }
+        let Some((analyzed, _)) = self.analyze(raw, Analysis::BlockOnly, Utc::now().timestamp()).await else {
+            return true;
+        };
+        let scored = self.engine.score(&analyzed);
+        self.block_only_count += 1;
+        debug!("Block-only tx {} scored {:.0}", scored.tx.txid, scored.composite_score);
+        self.ui_tx.send(PipelineOutput::BlockOnlyTx(scored)).is_ok()
+    }
+
+    /// Fetch a newly connected block, mark its txs confirmed and record per-block stats.
+    async fn handle_block_connected(&mut self, block_hash: [u8; 32], height: u32) {
+        let hash = hash_to_hex(&block_hash);
+        if self.recent_blocks.contains(&hash) {
+            return;
+        }
+        if self.recent_blocks.len() == RECENT_BLOCKS {
+            self.recent_blocks.pop_front();
+        }
+        self.recent_blocks.push_back(hash.clone());
+        self.block_count += 1;
+        if let Some(reorg) = self.reorg.take() {
+            info!(
+                "Reorg complete: depth={} fork_height={} affected_signals={}",
+                reorg.depth, reorg.fork_height, reorg.affected_signals
+            );
+        }
 
-                if tx_count % 1000 == 0 {
-                    info!(
-                        "Pipeline: {tx_count} txs, {block_count} blocks, prevouts resolved: {resolved_total}, unresolved: {unresolved_total}, mempool pending: {}",
-                        mempool.pending_count()
-                    );
+        let block = if self.offline {
+            None
+        } else {
+            match self.rpc.getblock(&hash, 2).await {
+                Ok(v) => BlockSummary::from_rpc(&v),
+                Err(e) => {
+                    warn!("getblock failed for {hash}: {e}");
+                    None
                 }
+            }
+        };
 
-                if ui_tx.send(PipelineOutput::NewTx(scored)).is_err() {
-                    info!("UI channel closed, stopping pipeline");
-                    break;
+        match block {
+            Some(block) => {
+                self.current_block_height = block.height;
+                let confirmed = self.mempool.confirm_txids(&block.txids);
+                let conflicted = self.mempool.mark_block_conflicts(&block.spends.iter().copied().collect());
+                if !conflicted.is_empty() {
+                    debug!("{} tracked txs conflict with block {hash}", conflicted.len());
+                }
+                for c in &confirmed {
+                    self.record_outcome(&c.txid, Some((block.height, block.time)));
+                }
+                // With the block's height, so disconnecting it rolls these back too
+                for txid in &conflicted {
+                    self.record_outcome(txid, Some((block.height, block.time)));
+                }
+                let signal_count = confirmed
+                    .iter()
+                    .filter(|c| c.score.is_some_and(|s| s > self.signal_min_score))
+                    .count();
+                let stats = BlockStats {
+                    height: block.height,
+                    hash: block.hash.clone(),
+                    tx_count: block.tx_count(),
+                    total_fees: block.total_fees,
+                    tracked_count: confirmed.len(),
+                    signal_count,
+                };
+                let stored = stats.clone();
+                self.db.write_detached("store block stats", move |db| db.store_block_stats(&stored));
+                info!(
+                    "Block connected: height={} txs={} tracked={} signals={} (total blocks seen: {})",
+                    stats.height, stats.tx_count, stats.tracked_count, stats.signal_count, self.block_count
+                );
+                let _ = self.ui_tx.send(PipelineOutput::BlockConnected { height: block.height });
+                let _ = self.ui_tx.send(PipelineOutput::BlockStats(stats));
+                let spent = block.spends.iter().map(|op| (op.txid.to_string(), op.vout)).collect();
+                spawn_cache_maintenance(self.db.clone(), spent, self.utxo_cache_max_rows, self.ui_tx.clone());
+            }
+            None => {
+                if height > 0 {
+                    self.current_block_height = height;
                 }
+                info!("Block connected: {hash} (not fetched, total blocks seen: {})", self.block_count);
+                let _ = self.ui_tx.send(PipelineOutput::BlockConnected { height });
+            }
+        }
 
-                // Periodically send stats
-                let now = std::time::Instant::now();
-                if tx_count % stats_tx_interval == 0
-                    || now.duration_since(last_stats_time) >= STATS_TIME_INTERVAL
-                {
-                    send_stats(&mempool, &ui_tx);
-                    last_stats_time = now;
+        // After a block, send updated stats
+        self.refresh_mempool_stats();
+    }
+
+    /// Roll back a disconnected block: its txs return to Pending, signals seen above the
+    /// fork point are flagged and cached UTXOs from it are invalidated.
+    async fn handle_block_disconnected(&mut self, block_hash: [u8; 32], height: u32) {
+        let hash = hash_to_hex(&block_hash);
+        self.recent_blocks.retain(|h| h != &hash);
+
+        let block = if self.offline {
+            None
+        } else {
+            match self.rpc.getblock(&hash, 1).await {
+                Ok(v) => BlockSummary::from_rpc(&v),
+                Err(e) => {
+                    warn!("getblock failed for disconnected block {hash}: {e}");
+                    None
                 }
+            }
+        };
+
+        // The disconnected block is our tip unless the node told us otherwise
+        let (height, unconfirmed) = match &block {
+            Some(block) => (block.height, self.mempool.unconfirm_txids(&block.txids)),
+            None if height > 0 => (height, 0),
+            None => (self.current_block_height, 0),
+        };
+        if height == 0 {
+            warn!("Block disconnected: {hash} at unknown height, skipping rollback");
+            return;
+        }
+
+        let affected = match self.db.apply_block_disconnect(&hash, height).await {
+            Ok(n) => n,
+            Err(e) => {
+                error!("Failed to roll back block {hash} in DB: {e}");
+                0
+            }
+        };
+        self.current_block_height = height - 1;
+
+        let reorg = self.reorg.get_or_insert_with(ReorgInfo::default);
+        reorg.depth += 1;
+        reorg.fork_height = height - 1;
+        reorg.affected_signals += affected;
+        reorg.unconfirmed_txs += unconfirmed;
+        warn!(
+            "Block disconnected: {hash} height={height} (reorg depth={}, {unconfirmed} txs back to pending, {affected} signals flagged)",
+            reorg.depth
+        );
+        let _ = self.ui_tx.send(PipelineOutput::Reorg(reorg.clone()));
+        self.refresh_mempool_stats();
+    }
+
+    /// Sequence `R` carries no reason; `MempoolState` infers it from spent outpoints and
+    /// reclassifies it when the replacement or the confirming block shows up.
+    fn handle_tx_removed(&mut self, txid: [u8; 32], reason: RemovalReason) {
+        let txid_hex = hash_to_hex(&txid);
+        let was_pending = self.mempool.is_pending(&txid_hex);
+        let reason = if reason == RemovalReason::Unknown {
+            self.mempool.remove_unexplained(&txid_hex)
+        } else {
+            self.mempool.remove_tx(&txid_hex, reason);
+            reason
+        };
+        debug!("Tx removed: {txid_hex} reason={reason:?}");
+        if was_pending {
+            self.record_outcome(&txid_hex, None);
+        }
+    }
+
+    /// Queue the outcome of a tracked tx that just left Pending, if it was persisted as a
+    /// signal. `block` is the height and time of the block that confirmed or conflicted it.
+    fn record_outcome(&self, txid: &str, block: Option<(u32, i64)>) {
+        let Some(entry) = self.mempool.get(txid) else {
+            return;
+        };
+        if !entry.score.is_some_and(|s| s > self.signal_min_score) {
+            return;
+        }
+        let outcome = match entry.state {
+            TxState::Pending => return,
+            TxState::Confirmed => "confirmed",
+            TxState::Replaced => "replaced",
+            TxState::Evicted => "evicted",
+            TxState::Conflicted => "conflicted",
+        };
+        let outcome = SignalOutcome {
+            outcome: outcome.to_string(),
+            confirmed_height: block.map(|(height, _)| height),
+            confirmed_time: block.map(|(_, time)| time),
+            mempool_secs: (entry.state_changed_at - entry.tx.seen_at).num_seconds().max(0),
+            replaced_by: entry.replaced_by.clone(),
+        };
+        let txid = txid.to_string();
+        self.db.write_detached("record signal outcome", move |db| db.record_signal_outcome(&txid, &outcome));
+    }
+}
+
+/// Run the pipeline: receive MempoolEvents, analyze, score, forward to UI.
+/// `offline` (capture replays) keeps the node out of mempool and block reconciliation and
+/// entity activity out of the database; prevouts are still resolved from the node when
+/// reachable.
+pub async fn run_pipeline(
+    mut rx: mpsc::UnboundedReceiver<MempoolEvent>,
+    ui_tx: mpsc::UnboundedSender<PipelineOutput>,
+    db: SharedDatabase,
+    rpc: BitcoinRpc,
+    tag_lookup: Arc<Mutex<TagLookup>>,
+    config: Config,
+    offline: bool,
+) {
+    let mut pipeline = Pipeline::new(ui_tx, db, rpc, tag_lookup, &config, offline);
 
-                // Periodically prune old entries
-                if now.duration_since(last_prune_time) >= PRUNE_INTERVAL {
-                    mempool.prune_old(PRUNE_MAX_AGE);
-                    last_prune_time = now;
+    info!("Pipeline started with prevout resolution, mempool state tracking, and signal persistence");
+    // Apply the size budget (it may have shrunk) and show the cache size right away
+    spawn_cache_maintenance(
+        pipeline.db.clone(),
+        Vec::new(),
+        pipeline.utxo_cache_max_rows,
+        pipeline.ui_tx.clone(),
+    );
+
+    if config.bitcoin.sync_mempool_on_start && !offline {
+        pipeline.refresh_chain_tip().await;
+        if let SyncOutcome::UiClosed = pipeline.reconcile_mempool().await {
+            return;
+        }
+    }
+
+    while let Some(event) = rx.recv().await {
+        match event {
+            MempoolEvent::TxAdded { txid: _, raw, mempool_sequence } => {
+                if pipeline.is_stale(mempool_sequence) {
+                    continue;
+                }
+                if !pipeline.ingest_tx(&raw, TxOrigin::Live).await {
+                    break;
                 }
             }
-            MempoolEvent::BlockConnected { block_hash: _, height } => {
-                block_count += 1;
-                if height > 0 {
-                    current_block_height = height;
+            MempoolEvent::TxSeenInBlock { txid, raw } => {
+                if !pipeline.handle_block_only_tx(txid, &raw).await {
+                    break;
                 }
-                info!("Block connected: height={height} (total blocks seen: {block_count})");
-                let _ = ui_tx.send(PipelineOutput::BlockConnected { height });
-                // After a block, send updated stats
-                send_stats(&mempool, &ui_tx);
             }
-            MempoolEvent::BlockDisconnected { block_hash: _, height } => {
-                warn!("Block disconnected: height={height}");
+            MempoolEvent::BlockConnected { block_hash, height } => {
+                pipeline.handle_block_connected(block_hash, height).await;
+            }
+            MempoolEvent::BlockDisconnected { block_hash, height } => {
+                pipeline.handle_block_disconnected(block_hash, height).await;
             }
-            MempoolEvent::TxRemoved { txid, reason } => {
-                // Convert txid bytes to hex string (reversed for bitcoin display order)
-                let txid_hex: String = txid.iter().rev().map(|b| format!("{b:02x}")).collect();
-                debug!("Tx removed: {txid_hex} reason={reason:?}");
-                mempool.remove_tx(&txid_hex, reason);
-
-                // If replaced, try to record the replacement chain
-                if reason == RemovalReason::Replaced {
-                    // We don't know the replacing txid from ZMQ alone;
-                    // the replacement tracking requires the `sequence` topic.
-                    // TODO: wire up ZMQ sequence topic for full RBF tracking
+            MempoolEvent::TxRemoved { txid, reason, mempool_sequence } => {
+                if pipeline.is_stale(mempool_sequence) {
+                    continue;
+                }
+                pipeline.handle_tx_removed(txid, reason);
+            }
+            MempoolEvent::ResyncRequired { reason } => {
+                if !pipeline.handle_resync(reason).await {
+                    break;
                 }
             }
         }
     }
 
-    info!("Pipeline shutting down after {tx_count} txs, {block_count} blocks");
+    info!(
+        "Pipeline shutting down after {} txs ({} block-only), {} blocks",
+        pipeline.tx_count, pipeline.block_only_count, pipeline.block_count
+    );
 }
 
 /// Messages from pipeline to UI.
 #[derive(Debug, Clone)]
 pub enum PipelineOutput {
     NewTx(ScoredTx),
+    /// A tx first seen in a block (never in our mempool); not part of the live feed.
+    BlockOnlyTx(ScoredTx),
     BlockConnected { height: u32 },
+    /// Statistics of a fetched and processed block.
+    BlockStats(BlockStats),
+    /// A block was disconnected; carries the reorg state so far.
+    Reorg(ReorgInfo),
+    /// A High/Critical tx was replaced via RBF.
+    Replacement(ReplacementDiff),
+    /// A High/Critical tx was bumped by a child paying for it (CPFP).
+    CpfpBump(CpfpBump),
     MempoolStats {
         pending_count: usize,
         total_vsize: usize,
         total_fees: u64,
         fee_histogram: Vec<(String, usize)>,
         removal_stats: RemovalStats,
+        prevout_stats: PrevoutStats,
     },
+    /// UTXO cache size after a maintenance run (startup and every connected block).
+    UtxoCacheStats(UtxoCacheStats),
+    /// Mempool state was reconciled with the node after missed events.
+    Resynced { reason: String, added: usize, removed: usize },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::test_server;
+    use crate::tags::AddressTag;
+    use bitcoin::hashes::Hash;
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    /// Address of the compressed generator point, spent by `witness_spend`.
+    const WHALE_ADDRESS: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    /// A pipeline on a fresh database and an unreachable node.
+    fn test_pipeline(
+        name: &str,
+        config: Config,
+        setup: impl FnOnce(&SharedDatabase),
+    ) -> (Pipeline, mpsc::UnboundedReceiver<PipelineOutput>) {
+        test_pipeline_with_node(name, config, BitcoinRpc::new("127.0.0.1", 1, "user", "pass"), setup)
+    }
+
+    /// A pipeline on a fresh database and the given node.
+    fn test_pipeline_with_node(
+        name: &str,
+        mut config: Config,
+        rpc: BitcoinRpc,
+        setup: impl FnOnce(&SharedDatabase),
+    ) -> (Pipeline, mpsc::UnboundedReceiver<PipelineOutput>) {
+        let path = std::env::temp_dir().join(format!("txradar_pipeline_{name}_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = SharedDatabase::open(&path).unwrap();
+        setup(&db);
+        let tag_lookup = Arc::new(Mutex::new(TagLookup::load_from_db(&db)));
+        config.notifications.enabled = false;
+        let (ui_tx, ui_rx) = mpsc::unbounded_channel();
+        (Pipeline::new(ui_tx, db, rpc, tag_lookup, &config, false), ui_rx)
+    }
+
+    /// A P2WPKH spend of `WHALE_ADDRESS`'s output `prev:0`, paying `value` sats.
+    fn witness_spend(prev: u8, value: u64) -> bitcoin::Transaction {
+        let pubkey: Vec<u8> =
+            Vec::from_hex("0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798").unwrap();
+        let mut sig = vec![0x30; 71];
+        sig[70] = 0x01;
+        bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint { txid: bitcoin::Txid::from_byte_array([prev; 32]), vout: 0 },
+                sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: bitcoin::Witness::from_slice(&[sig, pubkey]),
+                ..Default::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(value),
+                script_pubkey: bitcoin::ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([3; 20])),
+            }],
+        }
+    }
+
+    /// Tags `WHALE_ADDRESS` as "Whale", last active 400 days ago.
+    fn dormant_whale(db: &SharedDatabase) {
+        let last_seen = (Utc::now() - chrono::Duration::days(400)).timestamp();
+        db.write(move |db| {
+            db.insert_tag(&AddressTag {
+                address: WHALE_ADDRESS.into(),
+                entity: "Whale".into(),
+                entity_type: "individual".into(),
+                confidence: 0.9,
+                source: None,
+            })
+            .unwrap();
+            db.record_entity_activity("Whale", last_seen, "earlier").unwrap();
+        });
+    }
+
+    #[tokio::test]
+    async fn replacement_keeps_dormancy_of_original() {
+        let (mut pipeline, _ui_rx) = test_pipeline("rbf_dormancy", Config::default(), dormant_whale);
+        let original = witness_spend(1, 90_000);
+        let replacement = witness_spend(1, 80_000);
+        let raw = |tx: &bitcoin::Transaction| bitcoin::consensus::serialize(tx);
+
+        assert!(pipeline.ingest_tx(&raw(&original), TxOrigin::Live).await);
+        assert!(pipeline.ingest_tx(&raw(&replacement), TxOrigin::Live).await);
+
+        let days = |tx: &bitcoin::Transaction| {
+            let entry = pipeline.mempool.get(&tx.compute_txid().to_string()).unwrap();
+            (entry.tx.dormant_entity.clone(), entry.tx.dormancy_days.unwrap())
+        };
+        let (entity, original_days) = days(&original);
+        assert_eq!(entity.as_deref(), Some("Whale"));
+        assert!((original_days - 400.0).abs() < 0.1);
+        assert_eq!(days(&replacement), (entity, original_days));
+        assert_eq!(pipeline.mempool.get(&original.compute_txid().to_string()).unwrap().state, TxState::Replaced);
+    }
+
+    #[tokio::test]
+    async fn cpfp_bump_of_backlog_tx_not_shown() {
+        // Every score is an alert; the parent's input is cached so its fee is known
+        let mut config = Config::default();
+        config.signals.alert_thresholds = crate::config::AlertThresholds { critical: 0.0, high: 0.0, medium: 0.0 };
+        let parent = witness_spend(7, 99_900);
+        let mut child = witness_spend(0, 90_000);
+        child.input[0].previous_output = bitcoin::OutPoint { txid: parent.compute_txid(), vout: 0 };
+
+        let mut shown = Vec::new();
+        for (name, origin) in [("cpfp_backlog", TxOrigin::Backlog { stored: false, entered: None }), ("cpfp_live", TxOrigin::Live)] {
+            let (mut pipeline, mut ui_rx) = test_pipeline(name, config.clone(), |db| {
+                let funding = bitcoin::Txid::from_byte_array([7; 32]).to_string();
+                let utxo = crate::db::CachedUtxo {
+                    value: 100_000,
+                    script_type: "witness_v0_keyhash".into(),
+                    script_pubkey: Vec::new(),
+                    address: Some(WHALE_ADDRESS.into()),
+                    block_height: 800_000,
+                    block_time: 1_700_000_000,
+                };
+                db.cache_utxo(&funding, 0, &utxo).unwrap();
+            });
+            assert!(pipeline.ingest_tx(&bitcoin::consensus::serialize(&parent), origin).await);
+            assert!(pipeline.ingest_tx(&bitcoin::consensus::serialize(&child), origin).await);
+            let child_entry = pipeline.mempool.get(&child.compute_txid().to_string()).unwrap();
+            assert_eq!(child_entry.tx.cpfp_parents, vec![parent.compute_txid().to_string()]);
+
+            let mut bumps = 0;
+            while let Ok(output) = ui_rx.try_recv() {
+                if let PipelineOutput::CpfpBump(_) = output {
+                    bumps += 1;
+                }
+            }
+            shown.push(bumps);
+        }
+        assert_eq!(shown, vec![0, 1]);
+    }
+
+    #[tokio::test]
+    async fn block_only_tx_leaves_no_trace() {
+        let (mut pipeline, mut ui_rx) = test_pipeline("block_only", Config::default(), dormant_whale);
+        let in_block = witness_spend(1, 90_000);
+        let txid = in_block.compute_txid().to_byte_array();
+        assert!(pipeline.handle_block_only_tx(txid, &bitcoin::consensus::serialize(&in_block)).await);
+
+        let Ok(PipelineOutput::BlockOnlyTx(scored)) = ui_rx.try_recv() else {
+            panic!("block-only tx not shown");
+        };
+        assert_eq!(scored.tx.dormant_entity.as_deref(), Some("Whale"));
+        assert_eq!(pipeline.prevout_stats, PrevoutStats::default());
+
+        // The whale's silence is only ended by what we see in the mempool
+        let live = witness_spend(2, 90_000);
+        assert!(pipeline.ingest_tx(&bitcoin::consensus::serialize(&live), TxOrigin::Live).await);
+        let entry = pipeline.mempool.get(&live.compute_txid().to_string()).unwrap();
+        assert!((entry.tx.dormancy_days.unwrap() - 400.0).abs() < 0.1);
+    }
+
+    #[tokio::test]
+    async fn mempool_sync_fetches_in_batches() {
+        let txs: Vec<bitcoin::Transaction> = (1..=3).map(|n| witness_spend(n, 90_000)).collect();
+        let by_txid: HashMap<String, String> = txs
+            .iter()
+            .map(|tx| (tx.compute_txid().to_string(), bitcoin::consensus::encode::serialize_hex(tx)))
+            .collect();
+        let txids: Vec<String> = by_txid.keys().cloned().collect();
+        let entered = (Utc::now() - chrono::Duration::days(100)).timestamp();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        let rpc = test_server::spawn(Duration::ZERO, move |request| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            match request.as_array() {
+                Some(batch) => Value::Array(
+                    batch
+                        .iter()
+                        .map(|call| match by_txid.get(call["params"][0].as_str().unwrap_or_default()) {
+                            Some(hex) if call["method"] == "getrawtransaction" => {
+                                json!({"id": call["id"], "result": {"hex": hex}, "error": null})
+                            }
+                            Some(_) if call["method"] == "getmempoolentry" => {
+                                json!({"id": call["id"], "result": {"time": entered}, "error": null})
+                            }
+                            _ => json!({"id": call["id"], "result": null, "error": {"code": -5}}),
+                        })
+                        .collect(),
+                ),
+                None => {
+                    let result = json!({"txids": txids, "mempool_sequence": 10});
+                    json!({"id": request["id"], "result": result, "error": null})
+                }
+            }
+        })
+        .await;
+        let (mut pipeline, _ui_rx) = test_pipeline_with_node("sync_batches", Config::default(), rpc, dormant_whale);
+
+        let SyncOutcome::Synced(summary) = pipeline.reconcile_mempool().await else {
+            panic!("sync failed");
+        };
+        assert_eq!(summary.added, 3);
+        assert_eq!(pipeline.mempool.pending_count(), 3);
+        // getrawmempool, one batch each for the txs and their entries, one prevout batch per tx
+        assert_eq!(requests.load(Ordering::SeqCst), 1 + 2 + 3);
+        // The whale's silence ended when the first of them entered the mempool
+        let mut days: Vec<f64> = txs
+            .iter()
+            .map(|tx| pipeline.mempool.get(&tx.compute_txid().to_string()).unwrap().tx.dormancy_days.unwrap())
+            .collect();
+        days.sort_by(f64::total_cmp);
+        assert_eq!(days[..2], [0.0, 0.0]);
+        assert!((days[2] - 300.0).abs() < 0.1);
+    }
+
+    #[tokio::test]
+    async fn mempool_sync_records_outcome_of_dropped_signal() {
+        let rpc = test_server::spawn(Duration::ZERO, |request| match request.as_array() {
+            Some(batch) => Value::Array(
+                batch.iter().map(|call| json!({"id": call["id"], "result": null, "error": {"code": -5}})).collect(),
+            ),
+            None => json!({"id": request["id"], "result": {"txids": [], "mempool_sequence": 10}, "error": null}),
+        })
+        .await;
+        let mut config = Config::default();
+        config.signals.min_score_persist = -1.0;
+        let (mut pipeline, _ui_rx) = test_pipeline_with_node("sync_outcome", config, rpc, |_| {});
+        let tx = witness_spend(1, 90_000);
+        let txid = tx.compute_txid().to_string();
+        assert!(pipeline.ingest_tx(&bitcoin::consensus::serialize(&tx), TxOrigin::Live).await);
+
+        // The node no longer has it and nothing replaced it
+        let SyncOutcome::Synced(summary) = pipeline.reconcile_mempool().await else {
+            panic!("sync failed");
+        };
+        assert_eq!(summary.removed, 1);
+        assert_eq!(pipeline.mempool.get(&txid).unwrap().state, TxState::Evicted);
+
+        pipeline.db.store_signal(&txid, 50.0, "Medium", "{}", false, 1, 1.0, None, 849_999).unwrap();
+        pipeline.db.flush();
+        let signals = pipeline.db.get_signals_above_score(0.0, 10).unwrap();
+        let outcome = signals.into_iter().find(|s| s.txid == txid).unwrap().outcome.unwrap();
+        assert_eq!(outcome.outcome, "evicted");
+    }
 }
@@ -0,0 +1,1059 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use bitcoin::{Address, Network, OutPoint, ScriptBuf, TxOut};
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::config::{BitcoinConfig, PrevoutResolverMode};
+use crate::core::mempool::{MempoolEntry, MempoolState};
+use crate::core::tx::script_type;
+use crate::db::{CachedUtxo, SharedDatabase};
+use crate::rpc::{BitcoinRpc, RpcError};
+
+/// Bitcoin Core's RPC error code for an unknown tx (`RPC_INVALID_ADDRESS_OR_KEY`).
+const RPC_NOT_FOUND: i64 = -5;
+
+/// Resolved prevout info for a single input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ResolvedPrevout {
+    pub value: u64,      // satoshis
+    pub block_height: u32,
+    pub block_time: i64, // unix timestamp
+    pub address: Option<String>,
+    /// Bitcoin Core's `scriptPubKey.type` ("unknown" if the node didn't say).
+    pub script_type: String,
+    pub script_pubkey: ScriptBuf,
+    /// The funding tx is unconfirmed. Height/time are then inherited from its oldest
+    /// input if known (0 otherwise) and the input destroys no coin days.
+    pub unconfirmed_parent: bool,
+}
+
+impl ResolvedPrevout {
+    /// Resolve from a pending mempool parent's output, inheriting the parent's age.
+    fn from_pending_parent(parent: &MempoolEntry, output: &TxOut) -> Self {
+        Self {
+            value: output.value.to_sat(),
+            block_height: parent.tx.oldest_input_height.unwrap_or(0),
+            block_time: parent.tx.oldest_input_time.map(|t| t.timestamp()).unwrap_or(0),
+            address: address_of(&output.script_pubkey),
+            script_type: script_type(&output.script_pubkey).to_string(),
+            script_pubkey: output.script_pubkey.clone(),
+            unconfirmed_parent: true,
+        }
+    }
+
+    /// Resolve from a UTXO cache row. Cached outputs are always confirmed.
+    fn from_cached(utxo: CachedUtxo) -> Self {
+        let script_pubkey = ScriptBuf::from_bytes(utxo.script_pubkey);
+        Self {
+            value: utxo.value,
+            block_height: utxo.block_height,
+            block_time: utxo.block_time,
+            address: utxo.address.or_else(|| address_of(&script_pubkey)),
+            script_type: utxo.script_type,
+            script_pubkey,
+            unconfirmed_parent: false,
+        }
+    }
+
+    fn to_cached(&self) -> CachedUtxo {
+        CachedUtxo {
+            value: self.value,
+            script_type: self.script_type.clone(),
+            script_pubkey: self.script_pubkey.to_bytes(),
+            address: self.address.clone(),
+            block_height: self.block_height,
+            block_time: self.block_time,
+        }
+    }
+}
+
+/// Outcome of resolving one input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputStatus {
+    /// Found in the UTXO cache.
+    Cached,
+    /// Output of a pending tx in our mempool state.
+    Mempool,
+    /// Fetched from the node.
+    Fetched,
+    /// Coinbase input; there is no prevout.
+    Coinbase,
+    /// `gettxout`: the output is not in the node's UTXO set, i.e. already spent in a block
+    /// or created by an unconfirmed tx we don't track. Only `getrawtransaction` with
+    /// txindex can resolve it.
+    Spent,
+    /// `getrawtransaction`: the node doesn't know the parent (no txindex, or pruned).
+    NotFound,
+    /// The RPC request failed or returned something unusable.
+    Failed,
+    /// The time budget ran out before the lookup finished.
+    TimedOut,
+}
+
+impl InputStatus {
+    pub fn is_resolved(self) -> bool {
+        matches!(self, InputStatus::Cached | InputStatus::Mempool | InputStatus::Fetched)
+    }
+}
+
+/// Running per-input resolution counts, shown in the stats panel.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PrevoutStats {
+    pub cached: u64,
+    pub mempool: u64,
+    pub fetched: u64,
+    pub coinbase: u64,
+    pub spent: u64,
+    pub not_found: u64,
+    pub failed: u64,
+    pub timed_out: u64,
+}
+
+impl PrevoutStats {
+    pub fn record(&mut self, statuses: &[InputStatus]) {
+        for status in statuses {
+            *match status {
+                InputStatus::Cached => &mut self.cached,
+                InputStatus::Mempool => &mut self.mempool,
+                InputStatus::Fetched => &mut self.fetched,
+                InputStatus::Coinbase => &mut self.coinbase,
+                InputStatus::Spent => &mut self.spent,
+                InputStatus::NotFound => &mut self.not_found,
+                InputStatus::Failed => &mut self.failed,
+                InputStatus::TimedOut => &mut self.timed_out,
+            } += 1;
+        }
+    }
+
+    pub fn resolved(&self) -> u64 {
+        self.cached + self.mempool + self.fetched
+    }
+
+    pub fn unresolved(&self) -> u64 {
+        self.coinbase + self.spent + self.not_found + self.failed + self.timed_out
+    }
+
+    /// Share of UTXO cache lookups that hit; mempool parents and coinbase inputs never
+    /// reach the cache. `None` before the first lookup.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cached + self.fetched + self.spent + self.not_found + self.failed + self.timed_out;
+        (lookups > 0).then(|| self.cached as f64 / lookups as f64)
+    }
+}
+
+/// How hard to try resolving one transaction's prevouts.
+#[derive(Debug, Clone, Copy)]
+pub struct ResolveLimits {
+    /// Lookups per JSON-RPC batch request.
+    pub batch_size: usize,
+    /// Batch requests in flight at once.
+    pub concurrency: usize,
+    /// Give up on the remaining lookups after this long.
+    pub budget: Duration,
+}
+
+impl ResolveLimits {
+    pub fn from_config(config: &BitcoinConfig) -> Self {
+        Self {
+            batch_size: config.prevout_batch_size.max(1),
+            concurrency: config.prevout_concurrency.max(1),
+            budget: Duration::from_millis(config.prevout_budget_ms),
+        }
+    }
+}
+
+/// Aggregated prevout data for a transaction.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrevoutResolution {
+    pub total_input_value: u64,
+    pub oldest_input_time: Option<DateTime<Utc>>,
+    pub oldest_input_height: Option<u32>,
+    pub coin_days_destroyed: Option<f64>,
+    /// Inputs whose prevout was resolved.
+    pub resolved_count: usize,
+    pub input_addresses: Vec<String>,
+    /// Script types of the resolved prevouts.
+    pub input_script_types: Vec<String>,
+    /// Smallest resolved prevout value.
+    pub smallest_input_value: Option<u64>,
+    /// Block time of the most recently created input; now for unconfirmed parents.
+    pub newest_input_time: Option<i64>,
+    /// Resolved inputs spending outputs of unconfirmed txs.
+    pub unconfirmed_parent_count: usize,
+    /// Status of each input, in input order.
+    pub input_status: Vec<InputStatus>,
+}
+
+impl PrevoutResolution {
+    /// The time budget ran out with lookups still pending; the tx is partially resolved.
+    pub fn timed_out(&self) -> bool {
+        self.input_status.contains(&InputStatus::TimedOut)
+    }
+}
+
+/// One RPC lookup: a parent tx (`getrawtransaction`, all spent outputs at once) or a
+/// single outpoint (`gettxout`), with the input indexes waiting for it.
+#[derive(Debug, Clone)]
+struct Lookup {
+    txid: String,
+    /// (input index, output index)
+    wanted: Vec<(usize, u32)>,
+}
+
+type LookupResult = Vec<(usize, Result<ResolvedPrevout, InputStatus>)>;
+
+/// Resolves the prevouts of incoming txs: pending parents from the mempool state, then
+/// the UTXO cache, then the node via batched RPC (`[bitcoin] prevout_resolver`).
+#[derive(Clone)]
+pub struct PrevoutResolver {
+    db: SharedDatabase,
+    rpc: BitcoinRpc,
+    mode: PrevoutResolverMode,
+    limits: ResolveLimits,
+}
+
+impl PrevoutResolver {
+    pub fn new(db: SharedDatabase, rpc: BitcoinRpc, config: &BitcoinConfig) -> Self {
+        Self {
+            db,
+            rpc,
+            mode: config.prevout_resolver,
+            limits: ResolveLimits::from_config(config),
+        }
+    }
+
+    /// Resolve all prevouts of a parsed transaction. Lookups missing from the mempool
+    /// state and cache are batched, with each parent tx fetched once however many of its
+    /// outputs are spent (`getrawtransaction` mode). Returns what resolved within the
+    /// time budget.
+    pub async fn resolve(&self, parsed: &bitcoin::Transaction, mempool: &MempoolState) -> PrevoutResolution {
+        self.resolve_from(parsed, mempool, true).await
+    }
+
+    /// Like `resolve`, but without asking the node: inputs missing from the mempool state
+    /// and cache are left `TimedOut`, as if the budget were spent.
+    pub async fn resolve_cached(&self, parsed: &bitcoin::Transaction, mempool: &MempoolState) -> PrevoutResolution {
+        self.resolve_from(parsed, mempool, false).await
+    }
+
+    async fn resolve_from(
+        &self,
+        parsed: &bitcoin::Transaction,
+        mempool: &MempoolState,
+        fetch: bool,
+    ) -> PrevoutResolution {
+        let mut status = vec![InputStatus::TimedOut; parsed.input.len()];
+        let mut resolved: Vec<ResolvedPrevout> = Vec::with_capacity(parsed.input.len());
+        let mut uncached: Vec<(usize, OutPoint)> = Vec::new();
+
+        for (index, input) in parsed.input.iter().enumerate() {
+            if input.previous_output.is_null() {
+                status[index] = InputStatus::Coinbase;
+                continue;
+            }
+
+            // Chained payment: the parent is still in our mempool state
+            if let Some((parent, output)) = mempool.pending_output(&input.previous_output) {
+                resolved.push(ResolvedPrevout::from_pending_parent(parent, output));
+                status[index] = InputStatus::Mempool;
+                continue;
+            }
+
+            uncached.push((index, input.previous_output));
+        }
+
+        // One cache read for all remaining inputs
+        let keys = uncached.iter().map(|(_, op)| (op.txid.to_string(), op.vout)).collect();
+        let cached = match self.db.get_utxos_async(keys).await {
+            Ok(cached) => cached,
+            Err(e) => {
+                debug!("DB cache lookup error: {e}");
+                vec![None; uncached.len()]
+            }
+        };
+        let mut missing: Vec<(usize, OutPoint)> = Vec::new();
+        for ((index, outpoint), utxo) in uncached.into_iter().zip(cached) {
+            match utxo {
+                Some(utxo) => {
+                    resolved.push(ResolvedPrevout::from_cached(utxo));
+                    status[index] = InputStatus::Cached;
+                }
+                None => missing.push((index, outpoint)),
+            }
+        }
+
+        if fetch && !missing.is_empty() {
+            for (index, result) in self.fetch(self.lookups(missing)).await {
+                match result {
+                    Ok(prevout) => {
+                        resolved.push(prevout);
+                        status[index] = InputStatus::Fetched;
+                    }
+                    Err(s) => status[index] = s,
+                }
+            }
+        }
+
+        let mut resolution = summarize(&resolved, Utc::now());
+        resolution.input_status = status;
+        resolution
+    }
+
+    /// Group missing outpoints into lookups for the configured RPC method.
+    fn lookups(&self, missing: Vec<(usize, OutPoint)>) -> Vec<Lookup> {
+        match self.mode {
+            PrevoutResolverMode::GetRawTransaction => {
+                let mut by_parent: HashMap<String, Vec<(usize, u32)>> = HashMap::new();
+                for (index, outpoint) in missing {
+                    by_parent.entry(outpoint.txid.to_string()).or_default().push((index, outpoint.vout));
+                }
+                by_parent.into_iter().map(|(txid, wanted)| Lookup { txid, wanted }).collect()
+            }
+            PrevoutResolverMode::GetTxOut => missing
+                .into_iter()
+                .map(|(index, outpoint)| Lookup {
+                    txid: outpoint.txid.to_string(),
+                    wanted: vec![(index, outpoint.vout)],
+                })
+                .collect(),
+        }
+    }
+
+    /// Run lookups in concurrent batches until done or out of time. Batches still in
+    /// flight at the deadline keep running detached, so their results land in the cache
+    /// for later lookups; their inputs are reported as timed out.
+    async fn fetch(&self, lookups: Vec<Lookup>) -> LookupResult {
+        let deadline = Instant::now() + self.limits.budget;
+        let mut batches = lookups.chunks(self.limits.batch_size).map(<[_]>::to_vec);
+        let mut in_flight = JoinSet::new();
+        let mut results = Vec::new();
+
+        loop {
+            while in_flight.len() < self.limits.concurrency {
+                let Some(batch) = batches.next() else {
+                    break;
+                };
+                let resolver = self.clone();
+                in_flight.spawn(async move { resolver.fetch_batch(batch).await });
+            }
+            match tokio::time::timeout_at(deadline, in_flight.join_next()).await {
+                Ok(Some(Ok(batch_results))) => results.extend(batch_results),
+                Ok(Some(Err(e))) => warn!("Prevout batch task failed: {e}"),
+                Ok(None) => return results,
+                Err(_) => {
+                    in_flight.detach_all();
+                    return results;
+                }
+            }
+        }
+    }
+
+    /// Run one batch of lookups.
+    async fn fetch_batch(&self, batch: Vec<Lookup>) -> LookupResult {
+        match self.mode {
+            PrevoutResolverMode::GetRawTransaction => self.fetch_parent_txs(&batch).await,
+            PrevoutResolverMode::GetTxOut => self.fetch_txouts(&batch).await,
+        }
+    }
+
+    /// `getrawtransaction` for each parent tx, taking all wanted outputs from it, with
+    /// `getblockcount` in the same batch: the verbose result has confirmations but no height.
+    async fn fetch_parent_txs(&self, batch: &[Lookup]) -> LookupResult {
+        let mut calls: Vec<(&str, Vec<Value>)> = vec![("getblockcount", vec![])];
+        calls.extend(batch.iter().map(|l| ("getrawtransaction", vec![json!(l.txid), json!(true)])));
+        let mut responses = match self.rpc.call_batch(&calls).await {
+            Ok(responses) => responses.into_iter(),
+            Err(e) => {
+                debug!("RPC getrawtransaction batch of {} failed: {e}", batch.len());
+                return fail_all(batch, InputStatus::Failed);
+            }
+        };
+        let tip = responses
+            .next()
+            .and_then(|r| r.ok())
+            .and_then(|count| count.as_u64())
+            .unwrap_or(0) as u32;
+
+        let mut results = Vec::new();
+        for (lookup, response) in batch.iter().zip(responses) {
+            let tx_json = match response {
+                Ok(tx_json) => tx_json,
+                Err(e) => {
+                    debug!("RPC getrawtransaction failed for {}: {e}", lookup.txid);
+                    let status = if is_not_found(&e) { InputStatus::NotFound } else { InputStatus::Failed };
+                    results.extend(lookup.wanted.iter().map(|&(index, _)| (index, Err(status))));
+                    continue;
+                }
+            };
+            for &(index, vout) in &lookup.wanted {
+                let result = match parse_prevout(&tx_json, vout, tip) {
+                    Some(prevout) => {
+                        self.cache(&lookup.txid, vout, &prevout);
+                        Ok(prevout)
+                    }
+                    None => Err(InputStatus::Failed),
+                };
+                results.push((index, result));
+            }
+        }
+        results
+    }
+
+    /// `gettxout` for each outpoint, with `getblockcount` in the same batch to turn
+    /// confirmations into heights. Mempool spends are ignored (`include_mempool` false):
+    /// the tx being resolved already spends these outputs in the mempool.
+    async fn fetch_txouts(&self, batch: &[Lookup]) -> LookupResult {
+        let mut calls: Vec<(&str, Vec<Value>)> = vec![("getblockcount", vec![])];
+        calls.extend(batch.iter().map(|l| {
+            ("gettxout", vec![json!(l.txid), json!(l.wanted[0].1), json!(false)])
+        }));
+        let mut responses = match self.rpc.call_batch(&calls).await {
+            Ok(responses) => responses.into_iter(),
+            Err(e) => {
+                debug!("RPC gettxout batch of {} failed: {e}", batch.len());
+                return fail_all(batch, InputStatus::Failed);
+            }
+        };
+        let tip = responses
+            .next()
+            .and_then(|r| r.ok())
+            .and_then(|count| count.as_u64())
+            .unwrap_or(0) as u32;
+
+        let mut results = Vec::new();
+        let mut found = Vec::new();
+        for (lookup, response) in batch.iter().zip(responses) {
+            let (index, vout) = lookup.wanted[0];
+            match response {
+                Ok(Value::Null) => results.push((index, Err(InputStatus::Spent))),
+                Ok(txout) => match parse_txout(&txout, tip) {
+                    Some(parsed) => found.push((lookup, index, vout, parsed)),
+                    None => results.push((index, Err(InputStatus::Failed))),
+                },
+                Err(e) => {
+                    debug!("RPC gettxout failed for {}:{vout}: {e}", lookup.txid);
+                    results.push((index, Err(InputStatus::Failed)));
+                }
+            }
+        }
+
+        let heights: Vec<u32> = found.iter().map(|(_, _, _, p)| p.block_height).collect();
+        let times = self.block_times(&heights).await;
+        for (lookup, index, vout, mut prevout) in found {
+            prevout.block_time = times.get(&prevout.block_height).copied().unwrap_or(0);
+            self.cache(&lookup.txid, vout, &prevout);
+            results.push((index, Ok(prevout)));
+        }
+        results
+    }
+
+    /// Times of the blocks at `heights` (0 = skipped), from the `block_times` table or via
+    /// RPC (then stored). Heights that can't be looked up are left out.
+    async fn block_times(&self, heights: &[u32]) -> HashMap<u32, i64> {
+        let mut wanted: Vec<u32> = heights.iter().copied().filter(|&h| h > 0).collect();
+        wanted.sort_unstable();
+        wanted.dedup();
+        let (wanted, known) = self
+            .db
+            .read_async(move |db| {
+                let known = db.get_block_times(&wanted);
+                (wanted, known)
+            })
+            .await;
+        let mut times = known.unwrap_or_else(|e| {
+            debug!("Block time lookup failed: {e}");
+            HashMap::new()
+        });
+        let unknown: Vec<u32> = wanted.into_iter().filter(|h| !times.contains_key(h)).collect();
+        if unknown.is_empty() {
+            return times;
+        }
+
+        match self.rpc.block_times(&unknown).await {
+            Ok(fetched) => {
+                let rows: Vec<(u32, i64)> = fetched.iter().map(|(&h, &t)| (h, t)).collect();
+                self.db.write_detached("store block times", move |db| db.store_block_times(&rows));
+                times.extend(fetched);
+            }
+            Err(e) => debug!("RPC block time lookup failed: {e}"),
+        }
+        times
+    }
+
+    /// Cache a fetched output. Only confirmed outputs with a known block time are cached:
+    /// anything else would pin an unknown age for good.
+    fn cache(&self, txid: &str, vout: u32, prevout: &ResolvedPrevout) {
+        if prevout.block_height == 0 || prevout.block_time == 0 {
+            return;
+        }
+        let (txid, utxo) = (txid.to_string(), prevout.to_cached());
+        self.db.write_detached("cache UTXO", move |db| db.cache_utxo(&txid, vout, &utxo));
+    }
+}
+
+/// The same status for every input of the batch.
+fn fail_all(batch: &[Lookup], status: InputStatus) -> LookupResult {
+    batch
+        .iter()
+        .flat_map(|l| l.wanted.iter().map(move |&(index, _)| (index, Err(status))))
+        .collect()
+}
+
+fn is_not_found(e: &RpcError) -> bool {
+    matches!(e, RpcError::Rpc(err) if err.get("code").and_then(|c| c.as_i64()) == Some(RPC_NOT_FOUND))
+}
+
+/// Derive the mainnet address of a script, if it has one.
+fn address_of(script: &ScriptBuf) -> Option<String> {
+    Address::from_script(script, Network::Bitcoin).ok().map(|a| a.to_string())
+}
+
+/// Value, script and address of a `scriptPubKey`-carrying output object, as returned by
+/// both `getrawtransaction` (verbose) and `gettxout`.
+fn parse_output(output: &Value) -> Option<(u64, String, ScriptBuf, Option<String>)> {
+    let value_btc = output.get("value")?.as_f64()?;
+    let script_pub_key = output.get("scriptPubKey");
+    let script_pubkey = script_pub_key
+        .and_then(|s| s.get("hex"))
+        .and_then(|h| h.as_str())
+        .and_then(|h| ScriptBuf::from_hex(h).ok())
+        .unwrap_or_default();
+    let script_type = script_pub_key
+        .and_then(|s| s.get("type"))
+        .and_then(|t| t.as_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let address = script_pub_key
+        .and_then(|s| s.get("address"))
+        .and_then(|a| a.as_str())
+        .map(|s| s.to_string())
+        .or_else(|| address_of(&script_pubkey));
+    Some(((value_btc * 100_000_000.0).round() as u64, script_type, script_pubkey, address))
+}
+
+/// Extract one output of a verbose `getrawtransaction` result. Core reports the block
+/// by hash and confirmations only; the height follows from those and the chain tip.
+/// A result without block info is a mempool tx we don't track, e.g. one that arrived
+/// before startup: it is marked as an unconfirmed parent with unknown age.
+fn parse_prevout(tx_json: &Value, vout: u32, tip: u32) -> Option<ResolvedPrevout> {
+    let (value, script_type, script_pubkey, address) = parse_output(tx_json.get("vout")?.get(vout as usize)?)?;
+
+    // Block info (absent for unconfirmed)
+    let confirmations = tx_json.get("confirmations").and_then(|c| c.as_u64()).unwrap_or(0) as u32;
+    let block_height = if confirmations > 0 && tip > 0 {
+        (tip + 1).saturating_sub(confirmations)
+    } else {
+        0
+    };
+    let block_time = tx_json
+        .get("blocktime")
+        .and_then(|t| t.as_i64())
+        .unwrap_or(0);
+
+    Some(ResolvedPrevout {
+        value,
+        block_height,
+        block_time,
+        address,
+        script_type,
+        script_pubkey,
+        unconfirmed_parent: confirmations == 0,
+    })
+}
+
+/// Extract a `gettxout` result. The height follows from the confirmations and the
+/// chain tip; the block time is filled in by the caller.
+fn parse_txout(txout: &Value, tip: u32) -> Option<ResolvedPrevout> {
+    let (value, script_type, script_pubkey, address) = parse_output(txout)?;
+    let confirmations = txout.get("confirmations").and_then(|c| c.as_u64()).unwrap_or(0) as u32;
+    let block_height = if confirmations > 0 && tip > 0 {
+        (tip + 1).saturating_sub(confirmations)
+    } else {
+        0
+    };
+
+    Some(ResolvedPrevout {
+        value,
+        block_height,
+        block_time: 0,
+        address,
+        script_type,
+        script_pubkey,
+        unconfirmed_parent: confirmations == 0,
+    })
+}
+
+/// Aggregate resolved prevouts into input value, oldest input and coin days destroyed.
+fn summarize(resolved: &[ResolvedPrevout], now: DateTime<Utc>) -> PrevoutResolution {
+    let mut total_input_value: u64 = 0;
+    let mut oldest_time: Option<i64> = None;
+    let mut oldest_height: Option<u32> = None;
+    let mut newest_time: Option<i64> = None;
+    let mut cdd: f64 = 0.0;
+    let mut input_addresses: Vec<String> = Vec::new();
+    let mut input_script_types: Vec<String> = Vec::new();
+    let mut unconfirmed_parent_count = 0;
+
+    for prevout in resolved {
+        total_input_value += prevout.value;
+        if !input_script_types.contains(&prevout.script_type) {
+            input_script_types.push(prevout.script_type.clone());
+        }
+        if prevout.unconfirmed_parent {
+            unconfirmed_parent_count += 1;
+            newest_time = Some(now.timestamp());
+        } else if prevout.block_time > 0 && newest_time.is_none_or(|nt| prevout.block_time > nt) {
+            newest_time = Some(prevout.block_time);
+        }
+
+        if let Some(addr) = &prevout.address {
+            input_addresses.push(addr.clone());
+        }
+
+        if prevout.block_time > 0 {
+            // Track oldest
+            if oldest_time.is_none_or(|ot| prevout.block_time < ot) {
+                oldest_time = Some(prevout.block_time);
+            }
+            if prevout.block_height > 0 && oldest_height.is_none_or(|oh| prevout.block_height < oh) {
+                oldest_height = Some(prevout.block_height);
+            }
+
+            // CDD: value_btc * age_days. An unconfirmed parent already destroyed the
+            // coin days of its inputs; counting them again here would double count.
+            if !prevout.unconfirmed_parent
+                && let Some(it) = Utc.timestamp_opt(prevout.block_time, 0).single()
+            {
+                let age_days = (now - it).num_seconds() as f64 / 86400.0;
+                if age_days > 0.0 {
+                    let value_btc = prevout.value as f64 / 100_000_000.0;
+                    cdd += value_btc * age_days;
+                }
+            }
+        }
+    }
+
+    PrevoutResolution {
+        total_input_value,
+        oldest_input_time: oldest_time.and_then(|t| Utc.timestamp_opt(t, 0).single()),
+        oldest_input_height: oldest_height,
+        coin_days_destroyed: (!resolved.is_empty() && cdd > 0.0).then_some(cdd),
+        resolved_count: resolved.len(),
+        input_addresses,
+        input_script_types,
+        smallest_input_value: resolved.iter().map(|p| p.value).min(),
+        newest_input_time: newest_time,
+        unconfirmed_parent_count,
+        input_status: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::AlertLevel;
+    use crate::db::SharedDatabase;
+    use crate::rpc::test_server;
+    use crate::signals::rules::tests::make_test_tx;
+    use bitcoin::hashes::Hash;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn resolver(db: &SharedDatabase, rpc: BitcoinRpc, mode: PrevoutResolverMode, budget_ms: u64) -> PrevoutResolver {
+        let config = BitcoinConfig {
+            prevout_resolver: mode,
+            prevout_batch_size: 2,
+            prevout_concurrency: 2,
+            prevout_budget_ms: budget_ms,
+            ..BitcoinConfig::default()
+        };
+        PrevoutResolver::new(db.clone(), rpc, &config)
+    }
+
+    fn spending(parents: &[(u8, u32)]) -> bitcoin::Transaction {
+        bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: parents
+                .iter()
+                .map(|&(n, vout)| bitcoin::TxIn {
+                    previous_output: bitcoin::OutPoint {
+                        txid: bitcoin::Txid::from_byte_array([n; 32]),
+                        vout,
+                    },
+                    ..Default::default()
+                })
+                .collect(),
+            output: vec![],
+        }
+    }
+
+    /// Chain tip reported by the node stubs.
+    const TIP: u32 = 800_009;
+
+    /// A verbose `getrawtransaction` result as Core returns it (no height), confirmed at
+    /// height 800000 below `TIP`.
+    fn confirmed_tx(outputs: Vec<Value>) -> Value {
+        json!({
+            "vout": outputs,
+            "blockhash": "00000000000000000002",
+            "confirmations": TIP - 800_000 + 1,
+            "time": 1_700_000_000,
+            "blocktime": 1_700_000_000,
+        })
+    }
+
+    /// A parent tx with three 1 BTC outputs, confirmed at height 800000.
+    fn parent_json() -> Value {
+        let out = json!({"value": 1.0, "scriptPubKey": {"type": "witness_v0_keyhash"}});
+        confirmed_tx(vec![out.clone(), out.clone(), out])
+    }
+
+    /// Answer a batch: `getblockcount` with `TIP`, anything else with `result`.
+    fn answer_batch(request: &Value, result: &Value) -> Value {
+        let calls = request.as_array().cloned().unwrap_or_default();
+        Value::Array(
+            calls
+                .iter()
+                .map(|call| match call["method"].as_str() {
+                    Some("getblockcount") => json!({"id": call["id"], "result": TIP, "error": null}),
+                    _ => json!({"id": call["id"], "result": result, "error": null}),
+                })
+                .collect(),
+        )
+    }
+
+    /// A cache row confirmed at height 1.
+    fn cached(value: u64) -> CachedUtxo {
+        CachedUtxo {
+            value,
+            script_type: "witness_v1_taproot".to_string(),
+            script_pubkey: vec![0x51, 0x20],
+            address: None,
+            block_height: 1,
+            block_time: 1,
+        }
+    }
+
+    fn temp_db(name: &str) -> SharedDatabase {
+        let path = std::env::temp_dir().join(format!("txradar_{name}_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        SharedDatabase::open(&path).unwrap()
+    }
+
+    /// Node stub answering getrawtransaction batches; counts requested txids.
+    async fn node(delay: Duration, requested: Arc<AtomicUsize>) -> BitcoinRpc {
+        test_server::spawn(delay, move |request| {
+            let calls = request.as_array().cloned().unwrap_or_default();
+            let txids = calls.iter().filter(|c| c["method"] == "getrawtransaction").count();
+            requested.fetch_add(txids, Ordering::SeqCst);
+            answer_batch(&request, &parent_json())
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn fetches_each_parent_once() {
+        let db = temp_db("prevout_dedup");
+        let requested = Arc::new(AtomicUsize::new(0));
+        let rpc = node(Duration::ZERO, requested.clone()).await;
+        // Five inputs from three parents, one of them already cached
+        db.cache_utxo(&bitcoin::Txid::from_byte_array([3; 32]).to_string(), 0, &cached(50_000))
+            .unwrap();
+        let tx = spending(&[(1, 0), (1, 1), (1, 2), (2, 0), (3, 0)]);
+
+        let resolver = resolver(&db, rpc, PrevoutResolverMode::GetRawTransaction, 5_000);
+        let resolution = resolver.resolve(&tx, &MempoolState::new()).await;
+
+        assert_eq!(requested.load(Ordering::SeqCst), 2);
+        assert_eq!(resolution.resolved_count, 5);
+        assert_eq!(resolution.total_input_value, 4 * 100_000_000 + 50_000);
+        assert_eq!(resolution.oldest_input_height, Some(1));
+        assert!(!resolution.timed_out());
+        assert!(resolution.input_status.iter().all(|s| s.is_resolved()));
+        // Fetched outputs were cached
+        db.flush();
+        let parent = bitcoin::Txid::from_byte_array([1; 32]).to_string();
+        assert!(db.get_utxo(&parent, 2).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn budget_returns_partial_resolution() {
+        let db = temp_db("prevout_budget");
+        let rpc = node(Duration::from_secs(2), Arc::new(AtomicUsize::new(0))).await;
+        db.cache_utxo(&bitcoin::Txid::from_byte_array([9; 32]).to_string(), 0, &cached(70_000))
+            .unwrap();
+        let tx = spending(&[(9, 0), (1, 0), (2, 0)]);
+        let resolver = resolver(&db, rpc, PrevoutResolverMode::GetRawTransaction, 50);
+
+        let started = std::time::Instant::now();
+        let resolution = resolver.resolve(&tx, &MempoolState::new()).await;
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert!(resolution.timed_out());
+        assert_eq!(
+            resolution.input_status,
+            vec![InputStatus::Cached, InputStatus::TimedOut, InputStatus::TimedOut]
+        );
+        assert_eq!(resolution.resolved_count, 1);
+        assert_eq!(resolution.total_input_value, 70_000);
+    }
+
+    #[tokio::test]
+    async fn cache_only_skips_the_node() {
+        let db = temp_db("prevout_cache_only");
+        let requested = Arc::new(AtomicUsize::new(0));
+        let rpc = node(Duration::ZERO, requested.clone()).await;
+        db.cache_utxo(&bitcoin::Txid::from_byte_array([9; 32]).to_string(), 0, &cached(70_000))
+            .unwrap();
+        let tx = spending(&[(9, 0), (1, 0)]);
+
+        let resolution = resolver(&db, rpc, PrevoutResolverMode::GetRawTransaction, 5_000)
+            .resolve_cached(&tx, &MempoolState::new())
+            .await;
+        assert_eq!(requested.load(Ordering::SeqCst), 0);
+        assert_eq!(resolution.input_status, vec![InputStatus::Cached, InputStatus::TimedOut]);
+        assert_eq!(resolution.total_input_value, 70_000);
+    }
+
+    /// A pending parent whose oldest input is 100 days old, paying 2 BTC to output 0.
+    fn pending_parent(now: DateTime<Utc>) -> (MempoolState, bitcoin::Txid) {
+        let parsed = bitcoin::Transaction {
+            output: vec![TxOut {
+                value: bitcoin::Amount::from_sat(200_000_000),
+                script_pubkey: bitcoin::ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([7; 20])),
+            }],
+            ..spending(&[(5, 0)])
+        };
+        let txid = parsed.compute_txid();
+        let analyzed = crate::core::AnalyzedTx {
+            txid: txid.to_string(),
+            raw_size: 200,
+            vsize: 150,
+            total_input_value: 200_010_000,
+            total_output_value: 200_000_000,
+            fee: 10_000,
+            fee_rate: 66.7,
+            output_count: 1,
+            oldest_input_height: Some(850_000),
+            oldest_input_time: Some(now - chrono::Duration::days(100)),
+            coin_days_destroyed: Some(200.0),
+            seen_at: now,
+            prevouts_resolved: true,
+            resolved_inputs: 1,
+            ..make_test_tx()
+        };
+        let mut mempool = MempoolState::new();
+        mempool.add_scored_tx(analyzed, 20.0, AlertLevel::from_score(20.0), &parsed);
+        (mempool, txid)
+    }
+
+    #[tokio::test]
+    async fn chained_payment_resolves_from_mempool() {
+        let now = Utc::now();
+        let (mut mempool, parent_txid) = pending_parent(now);
+        let db = temp_db("prevout_chained");
+        // Nothing listens here: any RPC lookup would fail
+        let rpc = BitcoinRpc::new("127.0.0.1", 1, "user", "pass");
+        let child = bitcoin::Transaction {
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint { txid: parent_txid, vout: 0 },
+                ..Default::default()
+            }],
+            ..spending(&[])
+        };
+
+        let resolver = resolver(&db, rpc, PrevoutResolverMode::GetRawTransaction, 5_000);
+
+        let resolution = resolver.resolve(&child, &mempool).await;
+        assert_eq!(resolution.input_status, vec![InputStatus::Mempool]);
+        assert_eq!(resolution.resolved_count, 1);
+        assert_eq!(resolution.unconfirmed_parent_count, 1);
+        assert_eq!(resolution.total_input_value, 200_000_000);
+        // Age inherited from the parent's oldest input, but no coin days destroyed again
+        assert_eq!(resolution.oldest_input_height, Some(850_000));
+        let parent_oldest = now - chrono::Duration::days(100);
+        assert_eq!(resolution.oldest_input_time.map(|t| t.timestamp()), Some(parent_oldest.timestamp()));
+        assert_eq!(resolution.coin_days_destroyed, None);
+        assert_eq!(resolution.input_addresses.len(), 1);
+
+        // Once the parent confirms it is no longer resolved locally
+        mempool.confirm_txids(&[parent_txid.to_string()]);
+        let resolution = resolver.resolve(&child, &mempool).await;
+        assert_eq!(resolution.resolved_count, 0);
+        assert_eq!(resolution.input_status, vec![InputStatus::Failed]);
+    }
+
+    #[tokio::test]
+    async fn unconfirmed_rpc_parent_not_cached() {
+        let db = temp_db("prevout_unconfirmed");
+        let rpc = test_server::spawn(Duration::ZERO, |request| {
+            let out = json!({"value": 0.5, "scriptPubKey": {"type": "witness_v0_keyhash"}});
+            answer_batch(&request, &json!({"vout": [out], "confirmations": 0}))
+        })
+        .await;
+        let tx = spending(&[(4, 0)]);
+
+        let resolver = resolver(&db, rpc, PrevoutResolverMode::GetRawTransaction, 5_000);
+        let resolution = resolver.resolve(&tx, &MempoolState::new()).await;
+        assert_eq!(resolution.resolved_count, 1);
+        assert_eq!(resolution.unconfirmed_parent_count, 1);
+        assert_eq!(resolution.oldest_input_time, None);
+        db.flush();
+        let parent = bitcoin::Txid::from_byte_array([4; 32]).to_string();
+        assert!(db.get_utxo(&parent, 0).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn gettxout_mode_without_txindex() {
+        let db = temp_db("prevout_gettxout");
+        // A pruned node without txindex: getrawtransaction fails, gettxout works for
+        // unspent outputs. Tip is 900000; output 0 of tx 1 has 101 confirmations.
+        let rpc = test_server::spawn(Duration::ZERO, |request| {
+            let calls = request.as_array().cloned().unwrap_or_default();
+            let responses = calls.iter().map(|call| {
+                let result = match call["method"].as_str().unwrap() {
+                    "getblockcount" => json!(900_000),
+                    "gettxout" if call["params"][1] == 0 => json!({
+                        "confirmations": 101,
+                        "value": 0.25,
+                        "scriptPubKey": {"type": "witness_v1_taproot", "hex": "5120", "address": "bc1p"},
+                    }),
+                    "gettxout" => Value::Null,
+                    "getblockhash" => json!(format!("hash{}", call["params"][0])),
+                    "getblockheader" if call["params"][0] == "hash899900" => json!({"time": 1_700_000_000}),
+                    _ => return json!({"id": call["id"], "result": null, "error": {"code": -5}}),
+                };
+                json!({"id": call["id"], "result": result, "error": null})
+            });
+            Value::Array(responses.collect())
+        })
+        .await;
+        let tx = spending(&[(1, 0), (1, 1)]);
+
+        let resolver = resolver(&db, rpc, PrevoutResolverMode::GetTxOut, 5_000);
+        let resolution = resolver.resolve(&tx, &MempoolState::new()).await;
+        assert_eq!(resolution.input_status, vec![InputStatus::Fetched, InputStatus::Spent]);
+        assert_eq!(resolution.total_input_value, 25_000_000);
+        assert_eq!(resolution.oldest_input_height, Some(899_900));
+        assert_eq!(resolution.oldest_input_time.map(|t| t.timestamp()), Some(1_700_000_000));
+        assert_eq!(resolution.input_addresses, vec!["bc1p".to_string()]);
+        db.flush();
+        let parent = bitcoin::Txid::from_byte_array([1; 32]).to_string();
+        assert_eq!(
+            db.get_utxo(&parent, 0).unwrap(),
+            Some(CachedUtxo {
+                value: 25_000_000,
+                script_type: "witness_v1_taproot".to_string(),
+                script_pubkey: vec![0x51, 0x20],
+                address: Some("bc1p".to_string()),
+                block_height: 899_900,
+                block_time: 1_700_000_000,
+            })
+        );
+
+        let mut stats = PrevoutStats::default();
+        stats.record(&resolution.input_status);
+        assert_eq!((stats.resolved(), stats.unresolved(), stats.spent), (1, 1, 1));
+        // Both inputs went past the cache: one fetched, one spent
+        assert_eq!(stats.cache_hit_rate(), Some(0.0));
+        stats.record(&[InputStatus::Cached, InputStatus::Mempool]);
+        assert_eq!(stats.cache_hit_rate(), Some(1.0 / 3.0));
+    }
+
+    #[tokio::test]
+    async fn cache_hit_matches_rpc_hit() {
+        let db = temp_db("prevout_cache_hit");
+        let script = bitcoin::ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([8; 20]));
+        let address = address_of(&script).unwrap();
+        let output = json!({
+            "value": 1.0,
+            "scriptPubKey": {"type": "witness_v0_keyhash", "hex": script.to_hex_string(), "address": address},
+        });
+        let rpc = test_server::spawn(Duration::ZERO, move |request| {
+            answer_batch(&request, &confirmed_tx(vec![output.clone()]))
+        })
+        .await;
+        let tx = spending(&[(6, 0)]);
+
+        let fetched = resolver(&db, rpc, PrevoutResolverMode::GetRawTransaction, 5_000)
+            .resolve(&tx, &MempoolState::new())
+            .await;
+        db.flush();
+        // Second pass without a node: served from the cache
+        let offline = BitcoinRpc::new("127.0.0.1", 1, "user", "pass");
+        let cached = resolver(&db, offline, PrevoutResolverMode::GetRawTransaction, 5_000)
+            .resolve(&tx, &MempoolState::new())
+            .await;
+
+        assert_eq!(fetched.input_status, vec![InputStatus::Fetched]);
+        assert_eq!(cached.input_status, vec![InputStatus::Cached]);
+        assert_eq!(cached.input_addresses, vec![address]);
+        assert_eq!(cached.input_addresses, fetched.input_addresses);
+        assert_eq!(cached.total_input_value, fetched.total_input_value);
+        assert_eq!(fetched.oldest_input_height, Some(800_000));
+        assert_eq!(cached.oldest_input_height, fetched.oldest_input_height);
+
+        // A row without address (e.g. written by an importer) derives it from the script
+        let parent = bitcoin::Txid::from_byte_array([6; 32]).to_string();
+        let mut row = db.get_utxo(&parent, 0).unwrap().unwrap();
+        row.address = None;
+        assert_eq!(ResolvedPrevout::from_cached(row).address, cached.input_addresses.first().cloned());
+    }
+
+    #[tokio::test]
+    async fn missing_parent_without_txindex() {
+        let db = temp_db("prevout_not_found");
+        let rpc = test_server::spawn(Duration::ZERO, |request| {
+            let calls = request.as_array().cloned().unwrap_or_default();
+            Value::Array(
+                calls
+                    .iter()
+                    .map(|call| json!({"id": call["id"], "result": null, "error": {"code": -5, "message": "No such mempool or blockchain transaction"}}))
+                    .collect(),
+            )
+        })
+        .await;
+        let resolver = resolver(&db, rpc, PrevoutResolverMode::GetRawTransaction, 5_000);
+        let resolution = resolver.resolve(&spending(&[(1, 0)]), &MempoolState::new()).await;
+        assert_eq!(resolution.input_status, vec![InputStatus::NotFound]);
+    }
+
+    #[test]
+    fn summarize_oldest_and_cdd() {
+        let now = Utc.timestamp_opt(1_700_000_000 + 10 * 86_400, 0).unwrap();
+        let resolved = vec![
+            ResolvedPrevout {
+                value: 100_000_000,
+                block_height: 800_000,
+                block_time: 1_700_000_000,
+                address: None,
+                script_type: "witness_v0_keyhash".into(),
+                script_pubkey: ScriptBuf::new(),
+                unconfirmed_parent: false,
+            },
+            ResolvedPrevout {
+                value: 50_000_000,
+                block_height: 0,
+                block_time: 0,
+                address: Some("bc1q".into()),
+                script_type: "witness_v0_keyhash".into(),
+                script_pubkey: ScriptBuf::new(),
+                unconfirmed_parent: true,
+            },
+        ];
+        let resolution = summarize(&resolved, now);
+        assert_eq!(resolution.total_input_value, 150_000_000);
+        assert_eq!(resolution.oldest_input_height, Some(800_000));
+        assert_eq!(resolution.coin_days_destroyed, Some(10.0));
+        assert_eq!(resolution.input_addresses, vec!["bc1q".to_string()]);
+        assert_eq!(resolution.unconfirmed_parent_count, 1);
+        assert_eq!(summarize(&[], now), PrevoutResolution::default());
+    }
+}
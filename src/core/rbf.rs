@@ -0,0 +1,184 @@
+use bitcoin::TxOut;
+use serde::{Deserialize, Serialize};
+
+use super::mempool::MempoolEntry;
+use super::{AlertLevel, ScoredTx};
+
+/// How an RBF replacement differs from the tx it replaced.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplacementDiff {
+    pub old_txid: String,
+    pub new_txid: String,
+    pub old_score: f64,
+    pub new_score: f64,
+    pub old_alert_level: AlertLevel,
+    pub new_alert_level: AlertLevel,
+    /// Fee of the replacement minus fee of the original (sats).
+    pub fee_delta: i64,
+    pub old_fee_rate: f64,
+    pub new_fee_rate: f64,
+    /// The original paid a known exchange, the replacement doesn't.
+    pub exchange_output_removed: bool,
+    /// The replacement pays a known exchange, the original didn't.
+    pub exchange_output_added: bool,
+    /// Original outputs whose script doesn't appear in the replacement.
+    pub removed_outputs: usize,
+    /// Replacement outputs whose script didn't appear in the original.
+    pub added_outputs: usize,
+    /// Value of the removed outputs (sats), i.e. funds redirected elsewhere.
+    pub redirected_value: u64,
+}
+
+impl ReplacementDiff {
+    /// Compare a replaced mempool entry with the scored replacement and its outputs.
+    pub fn compute(old: &MempoolEntry, new: &ScoredTx, new_outputs: &[TxOut]) -> Self {
+        let old_score = old.score.unwrap_or(0.0);
+        let has_script = |outputs: &[TxOut], out: &TxOut| {
+            outputs.iter().any(|o| o.script_pubkey == out.script_pubkey)
+        };
+        let removed: Vec<&TxOut> = old
+            .outputs
+            .iter()
+            .filter(|o| !has_script(new_outputs, o))
+            .collect();
+        let added_outputs = new_outputs
+            .iter()
+            .filter(|o| !has_script(&old.outputs, o))
+            .count();
+
+        Self {
+            old_txid: old.tx.txid.clone(),
+            new_txid: new.tx.txid.clone(),
+            old_score,
+            new_score: new.composite_score,
+            old_alert_level: old.alert_level.unwrap_or(AlertLevel::Low),
+            new_alert_level: new.alert_level,
+            fee_delta: new.tx.fee as i64 - old.tx.fee as i64,
+            old_fee_rate: old.tx.fee_rate,
+            new_fee_rate: new.tx.fee_rate,
+            exchange_output_removed: old.tx.to_exchange && !new.tx.to_exchange,
+            exchange_output_added: !old.tx.to_exchange && new.tx.to_exchange,
+            removed_outputs: removed.len(),
+            added_outputs,
+            redirected_value: removed.iter().map(|o| o.value.to_sat()).sum(),
+        }
+    }
+
+    /// Whether the original was an alert (High or Critical).
+    pub fn is_alert(&self) -> bool {
+        matches!(self.old_alert_level, AlertLevel::Critical | AlertLevel::High)
+    }
+
+    /// One-line description, e.g. "replaced: score 82 → 35, exchange output removed".
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("replaced: score {:.0} → {:.0}", self.old_score, self.new_score)];
+        if self.exchange_output_removed {
+            parts.push("exchange output removed".to_string());
+        }
+        if self.exchange_output_added {
+            parts.push("exchange output added".to_string());
+        }
+        if self.redirected_value > 0 {
+            let btc = self.redirected_value as f64 / 100_000_000.0;
+            parts.push(format!("{btc:.4} BTC redirected"));
+        }
+        if self.fee_delta > 0 {
+            parts.push(format!("fee {:.1} → {:.1} sat/vB", self.old_fee_rate, self.new_fee_rate));
+        }
+        parts.join(", ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::AnalyzedTx;
+    use crate::signals::rules::tests::make_test_tx;
+    use crate::core::mempool::TxState;
+    use bitcoin::{Amount, ScriptBuf};
+    use chrono::Utc;
+
+    fn make_tx(txid: &str, fee: u64, to_exchange: bool) -> AnalyzedTx {
+        AnalyzedTx {
+            txid: txid.to_string(),
+            total_input_value: 1_000_000,
+            total_output_value: 1_000_000 - fee,
+            fee,
+            fee_rate: fee as f64 / 200.0,
+            is_rbf_signaling: true,
+            prevouts_resolved: true,
+            resolved_inputs: 1,
+            to_exchange,
+            to_exchange_confidence: if to_exchange { 0.9 } else { 0.0 },
+            ..make_test_tx()
+        }
+    }
+
+    fn output(script_byte: u8, sats: u64) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(sats),
+            script_pubkey: ScriptBuf::from_bytes(vec![0x51, script_byte]),
+        }
+    }
+
+    #[test]
+    fn redirect_away_from_exchange() {
+        let old = MempoolEntry {
+            tx: make_tx("old", 1_000, true),
+            state: TxState::Replaced,
+            state_changed_at: Utc::now(),
+            replaced_by: Some("new".to_string()),
+            score: Some(82.0),
+            alert_level: Some(AlertLevel::Critical),
+            spends: Vec::new(),
+            outputs: vec![output(1, 900_000), output(2, 99_000)],
+            parents: Vec::new(),
+            children: Vec::new(),
+        };
+        let new = ScoredTx {
+            tx: make_tx("new", 3_000, false),
+            composite_score: 35.0,
+            rule_scores: vec![],
+            alert_level: AlertLevel::from_score(35.0),
+        };
+        let diff = ReplacementDiff::compute(&old, &new, &[output(3, 900_000), output(2, 97_000)]);
+
+        assert!(diff.is_alert());
+        assert!(diff.exchange_output_removed);
+        assert!(!diff.exchange_output_added);
+        assert_eq!(diff.removed_outputs, 1);
+        assert_eq!(diff.added_outputs, 1);
+        assert_eq!(diff.redirected_value, 900_000);
+        assert_eq!(diff.fee_delta, 2_000);
+        assert_eq!(
+            diff.summary(),
+            "replaced: score 82 → 35, exchange output removed, 0.0090 BTC redirected, fee 5.0 → 15.0 sat/vB"
+        );
+    }
+
+    #[test]
+    fn alert_follows_stored_level() {
+        // 82 is only Medium under stricter configured thresholds
+        let old = MempoolEntry {
+            tx: make_tx("old", 1_000, true),
+            state: TxState::Replaced,
+            state_changed_at: Utc::now(),
+            replaced_by: Some("new".to_string()),
+            score: Some(82.0),
+            alert_level: Some(AlertLevel::from_score_with_thresholds(82.0, 95.0, 85.0, 50.0)),
+            spends: Vec::new(),
+            outputs: Vec::new(),
+            parents: Vec::new(),
+            children: Vec::new(),
+        };
+        let new = ScoredTx {
+            tx: make_tx("new", 3_000, true),
+            composite_score: 35.0,
+            rule_scores: vec![],
+            alert_level: AlertLevel::Low,
+        };
+        let diff = ReplacementDiff::compute(&old, &new, &[]);
+        assert_eq!(diff.old_alert_level, AlertLevel::Medium);
+        assert!(!diff.is_alert());
+    }
+}
@@ -1,5 +1,6 @@
 use bitcoin::consensus::deserialize;
-use bitcoin::Transaction;
+use bitcoin::script::Instruction;
+use bitcoin::{Address, CompressedPublicKey, Network, PublicKey, Script, Transaction, TxIn};
 
 /// Parse a raw transaction from bytes.
 pub fn parse_raw_tx(raw: &[u8]) -> Result<Transaction, bitcoin::consensus::encode::Error> {
@@ -16,3 +17,253 @@ pub fn vsize(tx: &Transaction) -> usize {
     let weight = tx.weight().to_wu() as usize;
     (weight + 3) / 4
 }
+
+/// Output script type, named like Bitcoin Core's `scriptPubKey.type`.
+pub fn script_type(script: &Script) -> &'static str {
+    if script.is_p2pkh() {
+        "pubkeyhash"
+    } else if script.is_p2sh() {
+        "scripthash"
+    } else if script.is_p2wpkh() {
+        "witness_v0_keyhash"
+    } else if script.is_p2wsh() {
+        "witness_v0_scripthash"
+    } else if script.is_p2tr() {
+        "witness_v1_taproot"
+    } else if script.is_op_return() {
+        "nulldata"
+    } else if script.is_p2pk() {
+        "pubkey"
+    } else if script.is_multisig() {
+        "multisig"
+    } else if script.is_witness_program() {
+        "witness_unknown"
+    } else {
+        "nonstandard"
+    }
+}
+
+/// What a raw input reveals about the output it spends.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InputScript {
+    /// Bitcoin Core's `scriptPubKey.type` of the spent output.
+    pub script_type: &'static str,
+    /// The spent address, if the input reveals it (taproot key-path spends don't).
+    pub address: Option<String>,
+}
+
+/// Recognize the spent output from the input's scriptSig and witness alone, without the
+/// prevout: P2PKH, P2WPKH and nested P2SH-P2WPKH/P2WSH give the address, a taproot
+/// key-path spend only the type. Anything else returns `None`.
+pub fn analyze_input(input: &TxIn) -> Option<InputScript> {
+    let witness: Vec<&[u8]> = input.witness.iter().collect();
+    let pushes = script_sig_pushes(&input.script_sig)?;
+
+    match (pushes.as_slice(), witness.as_slice()) {
+        // P2WPKH: <sig> <compressed pubkey>
+        ([], [sig, pubkey]) if is_ecdsa_sig(sig) => {
+            let pubkey = CompressedPublicKey::from_slice(pubkey).ok()?;
+            Some(InputScript {
+                script_type: "witness_v0_keyhash",
+                address: Some(Address::p2wpkh(&pubkey, Network::Bitcoin).to_string()),
+            })
+        }
+        // Taproot key path: a single Schnorr signature, optionally followed by an annex
+        ([], [sig]) | ([], [sig, [0x50, ..]]) if matches!(sig.len(), 64 | 65) => Some(InputScript {
+            script_type: "witness_v1_taproot",
+            address: None,
+        }),
+        // Nested segwit: the scriptSig pushes the witness program as redeem script
+        ([redeem], [_, ..]) if is_nested_witness_program(redeem) => {
+            let address = Address::p2sh(Script::from_bytes(redeem), Network::Bitcoin).ok()?;
+            Some(InputScript {
+                script_type: "scripthash",
+                address: Some(address.to_string()),
+            })
+        }
+        // P2PKH: <sig> <pubkey>, compressed or not
+        ([sig, pubkey], []) if is_ecdsa_sig(sig) => {
+            let pubkey = PublicKey::from_slice(pubkey).ok()?;
+            Some(InputScript {
+                script_type: "pubkeyhash",
+                address: Some(Address::p2pkh(pubkey, Network::Bitcoin).to_string()),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Input addresses of a tx: those derived from the inputs themselves, then any others
+/// learned from resolved prevouts, without duplicates. Derivation works even when RPC
+/// resolution failed or ran out of time; prevouts fill in the rest (e.g. P2WSH, P2TR).
+pub fn input_addresses(tx: &Transaction, resolved: &[String]) -> Vec<String> {
+    let mut addresses: Vec<String> = Vec::new();
+    let derived = tx.input.iter().filter_map(|input| analyze_input(input)?.address);
+    for address in derived.chain(resolved.iter().cloned()) {
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    addresses
+}
+
+/// Distinct script types of the outputs a tx spends: those recognized from the inputs
+/// themselves, then those of resolved prevouts (`resolved`), like `input_addresses`.
+pub fn input_script_types(tx: &Transaction, resolved: &[String]) -> Vec<String> {
+    let mut types: Vec<String> = Vec::new();
+    let derived = tx.input.iter().filter_map(|input| Some(analyze_input(input)?.script_type.to_string()));
+    for script_type in derived.chain(resolved.iter().cloned()) {
+        if !types.contains(&script_type) {
+            types.push(script_type);
+        }
+    }
+    types
+}
+
+/// Distinct script types of a tx's outputs, in output order.
+pub fn output_script_types(tx: &Transaction) -> Vec<String> {
+    let mut types: Vec<String> = Vec::new();
+    for output in &tx.output {
+        let script_type = script_type(&output.script_pubkey);
+        if !types.iter().any(|t| t == script_type) {
+            types.push(script_type.to_string());
+        }
+    }
+    types
+}
+
+/// The data pushes of a push-only scriptSig, or `None` if it runs any opcode.
+fn script_sig_pushes(script_sig: &Script) -> Option<Vec<&[u8]>> {
+    script_sig
+        .instructions()
+        .map(|instruction| match instruction.ok()? {
+            Instruction::PushBytes(bytes) => Some(bytes.as_bytes()),
+            Instruction::Op(_) => None,
+        })
+        .collect()
+}
+
+/// DER-encoded ECDSA signature plus sighash byte.
+fn is_ecdsa_sig(bytes: &[u8]) -> bool {
+    (9..=73).contains(&bytes.len()) && bytes[0] == 0x30
+}
+
+/// A v0 witness program (`OP_0 <20 or 32 bytes>`), as pushed by nested segwit inputs.
+fn is_nested_witness_program(redeem: &[u8]) -> bool {
+    matches!(redeem, [0x00, 0x14, rest @ ..] if rest.len() == 20)
+        || matches!(redeem, [0x00, 0x20, rest @ ..] if rest.len() == 32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::{ScriptBuf, Witness};
+
+    /// Compressed generator point, whose addresses are well known.
+    const PUBKEY: &str = "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798";
+
+    fn pubkey() -> Vec<u8> {
+        bitcoin::hex::FromHex::from_hex(PUBKEY).unwrap()
+    }
+
+    fn sig() -> Vec<u8> {
+        let mut sig = vec![0x30; 71];
+        sig[70] = 0x01; // SIGHASH_ALL
+        sig
+    }
+
+    fn input(script_sig: &[Vec<u8>], witness: &[Vec<u8>]) -> TxIn {
+        let mut builder = Builder::new();
+        for push in script_sig {
+            builder = builder.push_slice(PushBytesBuf::try_from(push.clone()).unwrap());
+        }
+        TxIn {
+            script_sig: builder.into_script(),
+            witness: Witness::from_slice(witness),
+            ..Default::default()
+        }
+    }
+
+    fn address_of(input: &TxIn) -> Option<String> {
+        analyze_input(input)?.address
+    }
+
+    #[test]
+    fn derives_single_key_addresses() {
+        let p2wpkh = input(&[], &[sig(), pubkey()]);
+        assert_eq!(analyze_input(&p2wpkh).unwrap().script_type, "witness_v0_keyhash");
+        assert_eq!(address_of(&p2wpkh).as_deref(), Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"));
+
+        let p2pkh = input(&[sig(), pubkey()], &[]);
+        assert_eq!(analyze_input(&p2pkh).unwrap().script_type, "pubkeyhash");
+        assert_eq!(address_of(&p2pkh).as_deref(), Some("1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"));
+
+        let mut redeem = vec![0x00, 0x14];
+        redeem.extend_from_slice(&bitcoin::PublicKey::from_slice(&pubkey()).unwrap().wpubkey_hash().unwrap()[..]);
+        let nested = input(&[redeem], &[sig(), pubkey()]);
+        assert_eq!(analyze_input(&nested).unwrap().script_type, "scripthash");
+        let expected = Address::p2shwpkh(&CompressedPublicKey::from_slice(&pubkey()).unwrap(), Network::Bitcoin);
+        assert_eq!(address_of(&nested), Some(expected.to_string()));
+    }
+
+    #[test]
+    fn taproot_key_path_gives_type_only() {
+        let key_path = input(&[], &[vec![0xab; 64]]);
+        assert_eq!(
+            analyze_input(&key_path),
+            Some(InputScript { script_type: "witness_v1_taproot", address: None })
+        );
+        let with_annex = input(&[], &[vec![0xab; 65], vec![0x50, 0x01]]);
+        assert_eq!(analyze_input(&with_annex).unwrap().script_type, "witness_v1_taproot");
+    }
+
+    #[test]
+    fn unrecognized_inputs() {
+        // Coinbase, P2WSH multisig and a scriptSig with opcodes
+        assert_eq!(analyze_input(&TxIn::default()), None);
+        let multisig = input(&[], &[vec![], sig(), sig(), vec![0x52; 71]]);
+        assert_eq!(analyze_input(&multisig), None);
+        let with_op = TxIn {
+            script_sig: Builder::new().push_opcode(bitcoin::opcodes::OP_TRUE).into_script(),
+            ..Default::default()
+        };
+        assert_eq!(analyze_input(&with_op), None);
+        // Not a valid curve point
+        assert_eq!(analyze_input(&input(&[], &[sig(), vec![0x05; 33]])), None);
+    }
+
+    #[test]
+    fn input_addresses_merge_derived_and_resolved() {
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![input(&[], &[sig(), pubkey()]), input(&[], &[vec![0xab; 64]])],
+            output: vec![bitcoin::TxOut { value: bitcoin::Amount::ZERO, script_pubkey: ScriptBuf::new() }],
+        };
+        let derived = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string();
+        // Without prevouts only the P2WPKH input is known
+        assert_eq!(input_addresses(&tx, &[]), vec![derived.clone()]);
+        // Resolved prevouts add the taproot address; duplicates are dropped
+        let resolved = vec![derived.clone(), "bc1ptaproot".to_string()];
+        assert_eq!(input_addresses(&tx, &resolved), vec![derived, "bc1ptaproot".to_string()]);
+    }
+
+    #[test]
+    fn script_types_merge_and_dedup() {
+        let p2pkh = ScriptBuf::new_p2pkh(&bitcoin::PublicKey::from_slice(&pubkey()).unwrap().pubkey_hash());
+        let output = |script: &ScriptBuf| bitcoin::TxOut { value: bitcoin::Amount::ZERO, script_pubkey: script.clone() };
+        let tx = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![input(&[], &[sig(), pubkey()]), input(&[], &[sig(), pubkey()]), TxIn::default()],
+            output: vec![output(&p2pkh), output(&ScriptBuf::new_op_return([1])), output(&p2pkh)],
+        };
+        // The unrecognized input is known from its prevout only
+        assert_eq!(
+            input_script_types(&tx, &["pubkey".to_string(), "witness_v0_keyhash".to_string()]),
+            vec!["witness_v0_keyhash".to_string(), "pubkey".to_string()]
+        );
+        assert_eq!(output_script_types(&tx), vec!["pubkeyhash".to_string(), "nulldata".to_string()]);
+    }
+}
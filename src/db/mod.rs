@@ -1,12 +1,53 @@
 pub mod schema;
 
 use chrono::{DateTime, Utc};
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Condvar, Mutex};
 
+use crate::core::block::BlockStats;
+use crate::core::cpfp::CpfpBump;
+use crate::core::rbf::ReplacementDiff;
 use crate::tags::AddressTag;
+use schema::MigrationError;
+
+/// Metadata of a resolved prevout, as kept in `utxo_cache`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedUtxo {
+    pub value: u64,
+    /// Bitcoin Core's `scriptPubKey.type`.
+    pub script_type: String,
+    pub script_pubkey: Vec<u8>,
+    /// Address derived from the script, if it has one.
+    pub address: Option<String>,
+    pub block_height: u32,
+    pub block_time: i64,
+}
+
+/// Size of the UTXO cache and the outcome of the last maintenance run.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UtxoCacheStats {
+    pub rows: u64,
+    /// Database file size (all tables; the cache dominates).
+    pub disk_bytes: u64,
+    /// Rows dropped because a block spent them.
+    pub spent_removed: usize,
+    /// Rows evicted to stay within the size budget.
+    pub evicted: usize,
+}
+
+/// Progress of a UTXO snapshot import, saved with every imported batch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotImport {
+    pub base_blockhash: String,
+    pub coins_total: u64,
+    pub coins_done: u64,
+    /// Snapshot file position to resume reading from.
+    pub offset: u64,
+    pub finished: bool,
+}
 
 /// A persisted signal record from the database.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -22,85 +63,278 @@ pub struct SignalRecord {
     pub coin_days_destroyed: Option<f64>,
     pub block_height_seen: u32,
     pub created_at: String,
+    /// Seen on a chain tip that was later disconnected by a reorg.
+    pub reorged: bool,
+    /// What happened to the tx, once it left the mempool.
+    pub outcome: Option<SignalOutcome>,
+    /// Child that paid for this tx (CPFP), with the highest package fee rate seen.
+    pub bumped_by: Option<String>,
+    pub package_fee_rate: Option<f64>,
+}
+
+/// How a signaled tx left the mempool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignalOutcome {
+    /// `confirmed`, `replaced`, `evicted` or `conflicted`.
+    pub outcome: String,
+    /// Height of the block that confirmed the tx, or a conflicting spend of it.
+    pub confirmed_height: Option<u32>,
+    /// Timestamp of that block.
+    pub confirmed_time: Option<i64>,
+    /// Seconds from first sight to the outcome.
+    pub mempool_secs: i64,
+    pub replaced_by: Option<String>,
 }
 
 pub struct Database {
     conn: Connection,
 }
 
-/// Thread-safe wrapper around Database.
+/// Read-only connections kept open for queries.
+const READ_CONNECTIONS: usize = 4;
+
+/// How long a connection waits on a lock held by another one before failing.
+const BUSY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+type WriteJob = Box<dyn FnOnce(&Database) + Send>;
+
+/// Handle to the database, cheap to clone and safe to share across threads and tasks.
+///
+/// All writes run in order on one writer thread that owns the read-write connection;
+/// queries use a small pool of read-only WAL connections, so reads from the UI never
+/// wait behind ingestion writes and vice versa. Blocking methods are for the UI thread,
+/// tests and `spawn_blocking`; async code uses the `_async` variants or queues writes with
+/// `write_detached`.
 #[derive(Clone)]
 pub struct SharedDatabase {
-    inner: Arc<Mutex<Database>>,
+    writer: std::sync::mpsc::Sender<WriteJob>,
+    readers: Arc<ReaderPool>,
+}
+
+struct ReaderPool {
+    idle: Mutex<Vec<Database>>,
+    returned: Condvar,
+}
+
+/// A checked-out read connection, returned to the pool on drop.
+struct PooledReader<'a> {
+    pool: &'a ReaderPool,
+    db: Option<Database>,
+}
+
+impl Drop for PooledReader<'_> {
+    fn drop(&mut self) {
+        if let Some(db) = self.db.take() {
+            self.pool.idle.lock().unwrap().push(db);
+            self.pool.returned.notify_one();
+        }
+    }
+}
+
+impl ReaderPool {
+    fn get(&self) -> PooledReader<'_> {
+        let mut idle = self.idle.lock().unwrap();
+        loop {
+            if let Some(db) = idle.pop() {
+                return PooledReader { pool: self, db: Some(db) };
+            }
+            idle = self.returned.wait(idle).unwrap();
+        }
+    }
 }
 
 impl SharedDatabase {
-    pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+    pub fn open(path: &Path) -> Result<Self, MigrationError> {
         let db = Database::open(path)?;
+        // Readers open after the writer so they see the migrated schema and the WAL files
+        let readers = (0..READ_CONNECTIONS)
+            .map(|_| Database::open_read_only(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let (writer, jobs) = std::sync::mpsc::channel::<WriteJob>();
+        std::thread::Builder::new()
+            .name("db-writer".into())
+            .spawn(move || {
+                // Ends once every handle is dropped
+                for job in jobs {
+                    if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(&db))).is_err() {
+                        tracing::error!("Database write panicked");
+                    }
+                }
+            })
+            .expect("failed to spawn database writer thread");
+
         Ok(Self {
-            inner: Arc::new(Mutex::new(db)),
+            writer,
+            readers: Arc::new(ReaderPool { idle: Mutex::new(readers), returned: Condvar::new() }),
         })
     }
 
-    /// Look up cached UTXO metadata. Returns (value_sats, script_type, block_height, block_time).
-    pub fn get_utxo(
+    /// Run `f` on a pooled read-only connection, blocking the calling thread.
+    pub fn read<T>(&self, f: impl FnOnce(&Database) -> T) -> T {
+        let reader = self.readers.get();
+        f(reader.db.as_ref().unwrap())
+    }
+
+    /// `read` from async code, on the blocking thread pool.
+    pub async fn read_async<T: Send + 'static>(&self, f: impl FnOnce(&Database) -> T + Send + 'static) -> T {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || db.read(f)).await.expect("database read panicked")
+    }
+
+    /// Run `f` on the writer thread and wait for it, blocking the calling thread.
+    pub fn write<T: Send + 'static>(&self, f: impl FnOnce(&Database) -> T + Send + 'static) -> T {
+        let (tx, rx) = std::sync::mpsc::sync_channel(1);
+        self.send(Box::new(move |db| {
+            let _ = tx.send(f(db));
+        }));
+        rx.recv().expect("database write failed")
+    }
+
+    /// `write` from async code: awaits the writer without holding up the runtime.
+    pub async fn write_async<T: Send + 'static>(&self, f: impl FnOnce(&Database) -> T + Send + 'static) -> T {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        self.send(Box::new(move |db| {
+            let _ = tx.send(f(db));
+        }));
+        rx.await.expect("database write failed")
+    }
+
+    /// Queue `f` on the writer thread without waiting; a failure is logged with `what`.
+    pub fn write_detached(
         &self,
-        txid: &str,
-        vout: u32,
-    ) -> Result<Option<(u64, String, u32, i64)>, rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.get_utxo(txid, vout)
+        what: &'static str,
+        f: impl FnOnce(&Database) -> Result<(), rusqlite::Error> + Send + 'static,
+    ) {
+        self.send(Box::new(move |db| {
+            if let Err(e) = f(db) {
+                tracing::warn!("Database write failed ({what}): {e}");
+            }
+        }));
+    }
+
+    fn send(&self, job: WriteJob) {
+        self.writer.send(job).expect("database writer thread stopped");
+    }
+
+    /// Wait until every write queued so far has been applied.
+    #[cfg(test)]
+    pub fn flush(&self) {
+        self.write(|_| ());
+    }
+
+    /// Look up cached UTXO metadata.
+    pub fn get_utxo(&self, txid: &str, vout: u32) -> Result<Option<CachedUtxo>, rusqlite::Error> {
+        let utxo = self.read(|db| db.get_utxo(txid, vout))?;
+        if utxo.is_some() {
+            self.touch_utxos(vec![(txid.to_string(), vout)]);
+        }
+        Ok(utxo)
+    }
+
+    /// Look up several outpoints in one read, in order.
+    pub async fn get_utxos_async(
+        &self,
+        outpoints: Vec<(String, u32)>,
+    ) -> Result<Vec<Option<CachedUtxo>>, rusqlite::Error> {
+        let (outpoints, utxos) = self
+            .read_async(move |db| {
+                let utxos: Result<Vec<_>, _> =
+                    outpoints.iter().map(|(txid, vout)| db.get_utxo(txid, *vout)).collect();
+                (outpoints, utxos)
+            })
+            .await;
+        let utxos = utxos?;
+        let hits = outpoints.into_iter().zip(&utxos).filter(|(_, u)| u.is_some()).map(|(o, _)| o).collect();
+        self.touch_utxos(hits);
+        Ok(utxos)
+    }
+
+    /// Mark cache hits as recently used (LRU), without waiting.
+    fn touch_utxos(&self, outpoints: Vec<(String, u32)>) {
+        if !outpoints.is_empty() {
+            self.write_detached("touch cached UTXOs", move |db| db.touch_utxos(&outpoints));
+        }
     }
 
     /// Cache a resolved UTXO.
-    pub fn cache_utxo(
+    pub fn cache_utxo(&self, txid: &str, vout: u32, utxo: &CachedUtxo) -> Result<(), rusqlite::Error> {
+        let (txid, utxo) = (txid.to_string(), utxo.clone());
+        self.write(move |db| db.cache_utxo(&txid, vout, &utxo))
+    }
+
+    /// Drop cached UTXOs that have been spent. Returns the number of rows deleted.
+    pub fn delete_utxos(&self, outpoints: &[(String, u32)]) -> Result<usize, rusqlite::Error> {
+        let outpoints = outpoints.to_vec();
+        self.write(move |db| db.delete_utxos(&outpoints))
+    }
+
+    /// Evict least recently used UTXOs down to `max_rows`. Returns the number evicted.
+    pub fn evict_utxos(&self, max_rows: u64) -> Result<usize, rusqlite::Error> {
+        self.write(move |db| db.evict_utxos(max_rows))
+    }
+
+    pub fn utxo_cache_stats(&self) -> Result<UtxoCacheStats, rusqlite::Error> {
+        self.read(|db| db.utxo_cache_stats())
+    }
+
+    /// Import a batch of snapshot coins and record the progress, atomically.
+    pub fn import_utxo_batch(
         &self,
-        txid: &str,
-        vout: u32,
-        value: u64,
-        script_type: &str,
-        block_height: u32,
-        block_time: i64,
+        utxos: Vec<(String, u32, CachedUtxo)>,
+        progress: &SnapshotImport,
     ) -> Result<(), rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.cache_utxo(txid, vout, value, script_type, block_height, block_time)
+        let progress = progress.clone();
+        self.write(move |db| db.import_utxo_batch(&utxos, &progress))
+    }
+
+    pub fn get_snapshot_import(&self, base_blockhash: &str) -> Result<Option<SnapshotImport>, rusqlite::Error> {
+        self.read(|db| db.get_snapshot_import(base_blockhash))
+    }
+
+    /// Known times of the blocks at `heights`.
+    pub fn get_block_times(&self, heights: &[u32]) -> Result<HashMap<u32, i64>, rusqlite::Error> {
+        self.read(|db| db.get_block_times(heights))
+    }
+
+    /// All known block times.
+    pub fn all_block_times(&self) -> Result<HashMap<u32, i64>, rusqlite::Error> {
+        self.read(|db| db.all_block_times())
+    }
+
+    pub fn store_block_times(&self, times: &[(u32, i64)]) -> Result<(), rusqlite::Error> {
+        let times = times.to_vec();
+        self.write(move |db| db.store_block_times(&times))
     }
 
     /// Look up an address tag.
     pub fn lookup_address(&self, address: &str) -> Option<AddressTag> {
-        let db = self.inner.lock().unwrap();
-        db.lookup_address(address)
+        self.read(|db| db.lookup_address(address))
     }
 
     /// Insert an address tag.
     pub fn insert_tag(&self, tag: &AddressTag) -> Result<(), rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.insert_tag(tag)
+        let tag = tag.clone();
+        self.write(move |db| db.insert_tag(&tag))
     }
 
     /// Insert a tag only if the address doesn't already exist with higher confidence.
     /// Returns true if the tag was actually inserted.
     pub fn insert_tag_if_higher(&self, tag: &AddressTag) -> Result<bool, rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        if let Some(existing) = db.lookup_address(&tag.address) {
-            if existing.confidence >= tag.confidence {
-                return Ok(false);
-            }
-        }
-        db.insert_tag(tag)?;
-        Ok(true)
+        let tag = tag.clone();
+        self.write(move |db| db.insert_tag_if_higher(&tag))
     }
 
     /// Bulk-load tags from a CSV file.
     pub fn load_tags_from_csv(&self, path: &Path) -> Result<usize, Box<dyn std::error::Error>> {
-        let db = self.inner.lock().unwrap();
-        db.load_tags_from_csv(path)
+        let path = path.to_path_buf();
+        self.write(move |db| db.load_tags_from_csv(&path).map_err(|e| e.to_string()))
+            .map_err(Into::into)
     }
 
     /// Load all address tags from DB.
     pub fn all_tags(&self) -> Result<Vec<AddressTag>, rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.all_tags()
+        self.read(|db| db.all_tags())
     }
 
     /// Store a signal for history (extended version).
@@ -116,41 +350,77 @@ impl SharedDatabase {
         coin_days_destroyed: Option<f64>,
         block_height_seen: u32,
     ) -> Result<(), rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.store_signal(txid, score, alert_level, rule_scores_json, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen)
+        let (txid, alert_level, rule_scores_json) =
+            (txid.to_string(), alert_level.to_string(), rule_scores_json.to_string());
+        self.write(move |db| {
+            db.store_signal(&txid, score, &alert_level, &rule_scores_json, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen)
+        })
     }
 
     /// Batch-store multiple signals in a single transaction.
-    pub fn store_signals_batch(
+    pub async fn store_signals_batch(
         &self,
-        signals: &[SignalBatchEntry],
+        signals: Vec<SignalBatchEntry>,
     ) -> Result<(), rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.store_signals_batch(signals)
+        self.write_async(move |db| db.store_signals_batch(&signals)).await
     }
 
     /// Get recent signals ordered by time.
     pub fn get_recent_signals(&self, limit: usize) -> Result<Vec<SignalRecord>, rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.get_recent_signals(limit)
+        self.read(|db| db.get_recent_signals(limit))
     }
 
     /// Get signals with score above threshold.
     pub fn get_signals_above_score(&self, min_score: f64, limit: usize) -> Result<Vec<SignalRecord>, rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.get_signals_above_score(min_score, limit)
+        self.read(|db| db.get_signals_above_score(min_score, limit))
     }
 
     /// Get total signal count.
     pub fn get_signal_count(&self) -> Result<usize, rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.get_signal_count()
+        self.read(|db| db.get_signal_count())
     }
 
     /// Get signals within a time range.
     pub fn get_signals_by_timerange(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<SignalRecord>, rusqlite::Error> {
-        let db = self.inner.lock().unwrap();
-        db.get_signals_by_timerange(from, to)
+        self.read(|db| db.get_signals_by_timerange(from, to))
+    }
+
+    /// Those of `txids` already stored as signals, in one read.
+    pub async fn stored_signals_async(&self, txids: Vec<String>) -> Result<HashSet<String>, rusqlite::Error> {
+        self.read_async(move |db| {
+            let mut stored = HashSet::new();
+            for txid in txids {
+                if db.has_signal(&txid)? {
+                    stored.insert(txid);
+                }
+            }
+            Ok(stored)
+        })
+        .await
+    }
+
+    /// Record statistics for a connected block.
+    pub fn store_block_stats(&self, stats: &BlockStats) -> Result<(), rusqlite::Error> {
+        let stats = stats.clone();
+        self.write(move |db| db.store_block_stats(&stats))
+    }
+
+    /// Get the most recent block statistics, highest first.
+    pub fn get_recent_block_stats(&self, limit: usize) -> Result<Vec<BlockStats>, rusqlite::Error> {
+        self.read(|db| db.get_recent_block_stats(limit))
+    }
+
+    /// Undo the effects of a disconnected block at `height`. Returns the number of
+    /// signals newly flagged as reorged.
+    pub async fn apply_block_disconnect(&self, block_hash: &str, height: u32) -> Result<usize, rusqlite::Error> {
+        let block_hash = block_hash.to_string();
+        self.write_async(move |db| db.apply_block_disconnect(&block_hash, height)).await
+    }
+
+    /// Get replacements involving `txid` (as original or replacement), oldest first.
+    #[cfg(test)]
+    pub fn get_replacements_for(&self, txid: &str) -> Result<Vec<ReplacementDiff>, rusqlite::Error> {
+        self.read(|db| db.get_replacements_for(txid))
     }
 }
 
@@ -165,51 +435,252 @@ pub struct SignalBatchEntry {
     pub fee_rate: f64,
     pub coin_days_destroyed: Option<f64>,
     pub block_height_seen: u32,
+    /// The full `AnalyzedTx` as JSON, for re-scoring.
+    pub analyzed_json: String,
+    pub raw_tx: Option<Vec<u8>>,
 }
 
+/// A stored signal's analysis, as input for re-scoring.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SignalPayload {
+    pub id: i64,
+    pub txid: String,
+    pub score: f64,
+    pub alert_level: String,
+    pub analyzed_json: String,
+}
+
+/// A stored signal scored again with the current engine.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rescore {
+    pub signal_id: i64,
+    pub txid: String,
+    pub original_score: f64,
+    pub original_alert_level: String,
+    pub score: f64,
+    pub alert_level: String,
+    pub rule_scores_json: String,
+}
+
+/// Columns selected for `SignalRecord`, in `row_to_signal` order.
+const SIGNAL_COLUMNS: &str = "id, txid, score, alert_level, rule_scores, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen, created_at, reorged, outcome, confirmed_height, confirmed_time, mempool_secs, replaced_by, child_txid, package_fee_rate";
+
+/// Signals joined with their outcome, if any.
+const SIGNAL_SOURCE: &str = "signals LEFT JOIN signal_outcomes USING (txid)
+     LEFT JOIN cpfp_bumps ON cpfp_bumps.parent_txid = signals.txid";
+
 impl Database {
-    pub fn open(path: &Path) -> Result<Self, rusqlite::Error> {
+    pub fn open(path: &Path) -> Result<Self, MigrationError> {
         let conn = Connection::open(path)?;
         conn.execute_batch("PRAGMA journal_mode=WAL; PRAGMA synchronous=NORMAL;")?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
         schema::migrate(&conn)?;
         Ok(Self { conn })
     }
 
+    /// A query-only connection to an already migrated database in WAL mode.
+    pub fn open_read_only(path: &Path) -> Result<Self, rusqlite::Error> {
+        let conn = Connection::open_with_flags(
+            path,
+            OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+        )?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        Ok(Self { conn })
+    }
+
     /// Cache a UTXO's metadata for fast prevout resolution.
-    pub fn cache_utxo(
-        &self,
-        txid: &str,
-        vout: u32,
-        value: u64,
-        script_type: &str,
-        block_height: u32,
-        block_time: i64,
-    ) -> Result<(), rusqlite::Error> {
+    pub fn cache_utxo(&self, txid: &str, vout: u32, utxo: &CachedUtxo) -> Result<(), rusqlite::Error> {
         self.conn.execute(
-            "INSERT OR REPLACE INTO utxo_cache (txid, vout, value, script_type, block_height, block_time)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            rusqlite::params![txid, vout, value, script_type, block_height, block_time],
+            "INSERT INTO utxo_cache
+                (txid, vout, value, script_type, block_height, block_time, script_pubkey, address, last_accessed)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, unixepoch())
+             ON CONFLICT (txid, vout) DO UPDATE SET
+                value = excluded.value, script_type = excluded.script_type,
+                block_height = excluded.block_height, block_time = excluded.block_time,
+                script_pubkey = excluded.script_pubkey, address = excluded.address,
+                last_accessed = excluded.last_accessed",
+            rusqlite::params![
+                txid,
+                vout,
+                utxo.value,
+                utxo.script_type,
+                utxo.block_height,
+                utxo.block_time,
+                utxo.script_pubkey,
+                utxo.address,
+            ],
         )?;
         Ok(())
     }
 
-    /// Look up cached UTXO metadata.
-    pub fn get_utxo(
+    pub fn delete_utxos(&self, outpoints: &[(String, u32)]) -> Result<usize, rusqlite::Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        let mut deleted = 0;
+        {
+            let mut stmt = tx.prepare_cached("DELETE FROM utxo_cache WHERE txid = ?1 AND vout = ?2")?;
+            for (txid, vout) in outpoints {
+                deleted += stmt.execute(rusqlite::params![txid, vout])?;
+            }
+        }
+        tx.commit()?;
+        Ok(deleted)
+    }
+
+    /// Evict the least recently used rows beyond `max_rows`. Rows that were never read
+    /// (e.g. imported from a snapshot) go first.
+    pub fn evict_utxos(&self, max_rows: u64) -> Result<usize, rusqlite::Error> {
+        let rows = self.utxo_cache_rows()?;
+        if rows <= max_rows {
+            return Ok(0);
+        }
+        self.conn.execute(
+            "DELETE FROM utxo_cache WHERE rowid IN
+                (SELECT rowid FROM utxo_cache ORDER BY last_accessed ASC LIMIT ?1)",
+            rusqlite::params![rows - max_rows],
+        )
+    }
+
+    /// Rows in the UTXO cache, from the trigger-maintained counter.
+    fn utxo_cache_rows(&self) -> Result<u64, rusqlite::Error> {
+        self.conn.query_row("SELECT rows FROM utxo_cache_count", [], |row| row.get(0))
+    }
+
+    /// Row count and on-disk size of the UTXO cache.
+    pub fn utxo_cache_stats(&self) -> Result<UtxoCacheStats, rusqlite::Error> {
+        let rows = self.utxo_cache_rows()?;
+        let page_count: u64 = self.conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let page_size: u64 = self.conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        Ok(UtxoCacheStats { rows, disk_bytes: page_count * page_size, ..Default::default() })
+    }
+
+    /// Insert snapshot coins and save the import progress in one transaction, so a
+    /// resumed import never skips coins.
+    pub fn import_utxo_batch(
         &self,
-        txid: &str,
-        vout: u32,
-    ) -> Result<Option<(u64, String, u32, i64)>, rusqlite::Error> {
+        utxos: &[(String, u32, CachedUtxo)],
+        progress: &SnapshotImport,
+    ) -> Result<(), rusqlite::Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO utxo_cache
+                    (txid, vout, value, script_type, block_height, block_time, script_pubkey, address)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                 ON CONFLICT (txid, vout) DO UPDATE SET
+                    value = excluded.value, script_type = excluded.script_type,
+                    block_height = excluded.block_height, block_time = excluded.block_time,
+                    script_pubkey = excluded.script_pubkey, address = excluded.address",
+            )?;
+            for (txid, vout, utxo) in utxos {
+                stmt.execute(rusqlite::params![
+                    txid,
+                    vout,
+                    utxo.value,
+                    utxo.script_type,
+                    utxo.block_height,
+                    utxo.block_time,
+                    utxo.script_pubkey,
+                    utxo.address,
+                ])?;
+            }
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO snapshot_imports
+                (base_blockhash, coins_total, coins_done, offset, finished, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, datetime('now'))",
+            rusqlite::params![
+                progress.base_blockhash,
+                progress.coins_total,
+                progress.coins_done,
+                progress.offset,
+                progress.finished as i32,
+            ],
+        )?;
+        tx.commit()
+    }
+
+    pub fn get_snapshot_import(&self, base_blockhash: &str) -> Result<Option<SnapshotImport>, rusqlite::Error> {
         let mut stmt = self.conn.prepare(
-            "SELECT value, script_type, block_height, block_time FROM utxo_cache WHERE txid = ?1 AND vout = ?2",
+            "SELECT base_blockhash, coins_total, coins_done, offset, finished
+             FROM snapshot_imports WHERE base_blockhash = ?1",
         )?;
-        let mut rows = stmt.query(rusqlite::params![txid, vout])?;
+        let mut rows = stmt.query(rusqlite::params![base_blockhash])?;
         if let Some(row) = rows.next()? {
-            Ok(Some((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            Ok(Some(SnapshotImport {
+                base_blockhash: row.get(0)?,
+                coins_total: row.get(1)?,
+                coins_done: row.get(2)?,
+                offset: row.get(3)?,
+                finished: row.get::<_, i64>(4)? != 0,
+            }))
         } else {
             Ok(None)
         }
     }
 
+    pub fn get_block_times(&self, heights: &[u32]) -> Result<HashMap<u32, i64>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare_cached("SELECT time FROM block_times WHERE height = ?1")?;
+        let mut times = HashMap::new();
+        for &height in heights {
+            let mut rows = stmt.query(rusqlite::params![height])?;
+            if let Some(row) = rows.next()? {
+                times.insert(height, row.get(0)?);
+            }
+        }
+        Ok(times)
+    }
+
+    pub fn all_block_times(&self) -> Result<HashMap<u32, i64>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare("SELECT height, time FROM block_times")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    pub fn store_block_times(&self, times: &[(u32, i64)]) -> Result<(), rusqlite::Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached("INSERT OR REPLACE INTO block_times (height, time) VALUES (?1, ?2)")?;
+            for (height, time) in times {
+                stmt.execute(rusqlite::params![height, time])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Look up cached UTXO metadata. Rows cached before scripts were stored count as
+    /// misses, so they get re-resolved (and upgraded) instead of losing the address.
+    pub fn get_utxo(&self, txid: &str, vout: u32) -> Result<Option<CachedUtxo>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT value, script_type, block_height, block_time, script_pubkey, address
+             FROM utxo_cache WHERE txid = ?1 AND vout = ?2 AND script_pubkey IS NOT NULL",
+        )?;
+        let mut rows = stmt.query(rusqlite::params![txid, vout])?;
+        let Some(row) = rows.next()? else {
+            return Ok(None);
+        };
+        Ok(Some(CachedUtxo {
+            value: row.get(0)?,
+            script_type: row.get(1)?,
+            block_height: row.get(2)?,
+            block_time: row.get(3)?,
+            script_pubkey: row.get(4)?,
+            address: row.get(5)?,
+        }))
+    }
+
+    /// Record a read of these outpoints for LRU eviction.
+    pub fn touch_utxos(&self, outpoints: &[(String, u32)]) -> Result<(), rusqlite::Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt =
+                tx.prepare_cached("UPDATE utxo_cache SET last_accessed = unixepoch() WHERE txid = ?1 AND vout = ?2")?;
+            for (txid, vout) in outpoints {
+                stmt.execute(rusqlite::params![txid, vout])?;
+            }
+        }
+        tx.commit()
+    }
+
     /// Look up an address tag.
     pub fn lookup_address(&self, address: &str) -> Option<AddressTag> {
         let mut stmt = self.conn.prepare(
@@ -229,6 +700,17 @@ impl Database {
         }
     }
 
+    /// Insert a tag unless the address is already tagged with at least its confidence.
+    pub fn insert_tag_if_higher(&self, tag: &AddressTag) -> Result<bool, rusqlite::Error> {
+        if let Some(existing) = self.lookup_address(&tag.address) {
+            if existing.confidence >= tag.confidence {
+                return Ok(false);
+            }
+        }
+        self.insert_tag(tag)?;
+        Ok(true)
+    }
+
     /// Insert an address tag.
     pub fn insert_tag(&self, tag: &AddressTag) -> Result<(), rusqlite::Error> {
         self.conn.execute(
@@ -239,6 +721,24 @@ impl Database {
         Ok(())
     }
 
+    /// Last outgoing activity (unix seconds) of every tracked entity.
+    pub fn entity_activity(&self) -> Result<HashMap<String, i64>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare("SELECT entity, last_outgoing FROM entity_activity")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    }
+
+    /// Record an outgoing tx of `entity`, unless a later one is already recorded.
+    pub fn record_entity_activity(&self, entity: &str, time: i64, txid: &str) -> Result<(), rusqlite::Error> {
+        self.conn.execute(
+            "INSERT INTO entity_activity (entity, last_outgoing, last_txid) VALUES (?1, ?2, ?3)
+             ON CONFLICT(entity) DO UPDATE SET last_outgoing = excluded.last_outgoing, last_txid = excluded.last_txid
+             WHERE excluded.last_outgoing > last_outgoing",
+            rusqlite::params![entity, time, txid],
+        )?;
+        Ok(())
+    }
+
     /// Load all address tags from DB.
     pub fn all_tags(&self) -> Result<Vec<AddressTag>, rusqlite::Error> {
         let mut stmt = self.conn.prepare(
@@ -312,14 +812,14 @@ impl Database {
         let tx = self.conn.unchecked_transaction()?;
         {
             let mut stmt = tx.prepare_cached(
-                "INSERT INTO signals (txid, score, alert_level, rule_scores, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen, created_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, datetime('now'))"
+                "INSERT INTO signals (txid, score, alert_level, rule_scores, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen, created_at, analyzed_tx, raw_tx)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, datetime('now'), ?10, ?11)"
             )?;
             for s in signals {
                 stmt.execute(rusqlite::params![
                     s.txid, s.score, s.alert_level, s.rule_scores_json,
                     s.to_exchange as i32, s.total_input_value, s.fee_rate,
-                    s.coin_days_destroyed, s.block_height_seen
+                    s.coin_days_destroyed, s.block_height_seen, s.analyzed_json, s.raw_tx
                 ])?;
             }
         }
@@ -341,25 +841,58 @@ impl Database {
             coin_days_destroyed: row.get(8)?,
             block_height_seen: row.get::<_, i64>(9)? as u32,
             created_at: row.get(10)?,
+            reorged: row.get::<_, i64>(11)? != 0,
+            outcome: match row.get::<_, Option<String>>(12)? {
+                Some(outcome) => Some(SignalOutcome {
+                    outcome,
+                    confirmed_height: row.get(13)?,
+                    confirmed_time: row.get(14)?,
+                    mempool_secs: row.get(15)?,
+                    replaced_by: row.get(16)?,
+                }),
+                None => None,
+            },
+            bumped_by: row.get(17)?,
+            package_fee_rate: row.get(18)?,
         })
     }
 
+    /// Record (or update) how a signaled tx left the mempool. Keyed by txid, so it can
+    /// land before the signal row itself is flushed.
+    pub fn record_signal_outcome(&self, txid: &str, outcome: &SignalOutcome) -> Result<(), rusqlite::Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO signal_outcomes
+                (txid, outcome, confirmed_height, confirmed_time, mempool_secs, replaced_by, resolved_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            rusqlite::params![
+                txid,
+                outcome.outcome,
+                outcome.confirmed_height,
+                outcome.confirmed_time,
+                outcome.mempool_secs,
+                outcome.replaced_by,
+                Utc::now().to_rfc3339(),
+            ],
+        )?;
+        Ok(())
+    }
+
     /// Get recent signals ordered by time.
     pub fn get_recent_signals(&self, limit: usize) -> Result<Vec<SignalRecord>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, txid, score, alert_level, rule_scores, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen, created_at
-             FROM signals ORDER BY created_at DESC LIMIT ?1"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {SIGNAL_COLUMNS}
+             FROM {SIGNAL_SOURCE} ORDER BY created_at DESC LIMIT ?1"
+        ))?;
         let rows = stmt.query_map(rusqlite::params![limit as i64], Self::row_to_signal)?;
         rows.collect()
     }
 
     /// Get signals with score above threshold.
     pub fn get_signals_above_score(&self, min_score: f64, limit: usize) -> Result<Vec<SignalRecord>, rusqlite::Error> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, txid, score, alert_level, rule_scores, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen, created_at
-             FROM signals WHERE score >= ?1 ORDER BY score DESC LIMIT ?2"
-        )?;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {SIGNAL_COLUMNS}
+             FROM {SIGNAL_SOURCE} WHERE score >= ?1 ORDER BY score DESC LIMIT ?2"
+        ))?;
         let rows = stmt.query_map(rusqlite::params![min_score, limit as i64], Self::row_to_signal)?;
         rows.collect()
     }
@@ -373,15 +906,187 @@ impl Database {
 
     /// Get signals within a time range.
     pub fn get_signals_by_timerange(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<SignalRecord>, rusqlite::Error> {
+        let from_str = from.format("%Y-%m-%d %H:%M:%S").to_string();
+        let to_str = to.format("%Y-%m-%d %H:%M:%S").to_string();
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {SIGNAL_COLUMNS}
+             FROM {SIGNAL_SOURCE} WHERE created_at >= ?1 AND created_at <= ?2 ORDER BY created_at DESC"
+        ))?;
+        let rows = stmt.query_map(rusqlite::params![from_str, to_str], Self::row_to_signal)?;
+        rows.collect()
+    }
+
+    /// Stored analyses of signals created within a time range, oldest first. Signals from
+    /// before payloads were stored are left out.
+    pub fn get_signal_payloads(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<SignalPayload>, rusqlite::Error> {
         let from_str = from.format("%Y-%m-%d %H:%M:%S").to_string();
         let to_str = to.format("%Y-%m-%d %H:%M:%S").to_string();
         let mut stmt = self.conn.prepare(
-            "SELECT id, txid, score, alert_level, rule_scores, to_exchange, total_input_value, fee_rate, coin_days_destroyed, block_height_seen, created_at
-             FROM signals WHERE created_at >= ?1 AND created_at <= ?2 ORDER BY created_at DESC"
+            "SELECT id, txid, score, alert_level, analyzed_tx FROM signals
+             WHERE created_at >= ?1 AND created_at <= ?2 AND analyzed_tx IS NOT NULL
+             ORDER BY created_at, id",
         )?;
-        let rows = stmt.query_map(rusqlite::params![from_str, to_str], Self::row_to_signal)?;
+        let rows = stmt.query_map(rusqlite::params![from_str, to_str], |row| {
+            Ok(SignalPayload {
+                id: row.get(0)?,
+                txid: row.get(1)?,
+                score: row.get(2)?,
+                alert_level: row.get(3)?,
+                analyzed_json: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Store the results of one re-scoring run.
+    pub fn store_rescores(&self, run_id: &str, rescores: &[Rescore]) -> Result<(), rusqlite::Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO rescores (run_id, signal_id, txid, original_score, original_alert_level, score, alert_level, rule_scores, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, datetime('now'))",
+            )?;
+            for r in rescores {
+                stmt.execute(rusqlite::params![
+                    run_id, r.signal_id, r.txid, r.original_score, r.original_alert_level,
+                    r.score, r.alert_level, r.rule_scores_json
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Results of a re-scoring run, in signal order.
+    pub fn get_rescores(&self, run_id: &str) -> Result<Vec<Rescore>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT signal_id, txid, original_score, original_alert_level, score, alert_level, rule_scores
+             FROM rescores WHERE run_id = ?1 ORDER BY id",
+        )?;
+        let rows = stmt.query_map(rusqlite::params![run_id], |row| {
+            Ok(Rescore {
+                signal_id: row.get(0)?,
+                txid: row.get(1)?,
+                original_score: row.get(2)?,
+                original_alert_level: row.get(3)?,
+                score: row.get(4)?,
+                alert_level: row.get(5)?,
+                rule_scores_json: row.get(6)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Whether a signal has already been stored for this txid.
+    pub fn has_signal(&self, txid: &str) -> Result<bool, rusqlite::Error> {
+        self.conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM signals WHERE txid = ?1)",
+            rusqlite::params![txid],
+            |row| row.get::<_, i64>(0).map(|v| v != 0),
+        )
+    }
+
+    /// Record statistics for a connected block.
+    pub fn store_block_stats(&self, stats: &BlockStats) -> Result<(), rusqlite::Error> {
+        self.conn.execute(
+            "INSERT OR REPLACE INTO blocks (height, hash, tx_count, total_fees, tracked_count, signal_count, connected_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))",
+            rusqlite::params![
+                stats.height, stats.hash, stats.tx_count as i64, stats.total_fees as i64,
+                stats.tracked_count as i64, stats.signal_count as i64
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Get the most recent block statistics, highest first.
+    pub fn get_recent_block_stats(&self, limit: usize) -> Result<Vec<BlockStats>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT height, hash, tx_count, total_fees, tracked_count, signal_count
+             FROM blocks ORDER BY height DESC LIMIT ?1"
+        )?;
+        let rows = stmt.query_map(rusqlite::params![limit as i64], |row| {
+            Ok(BlockStats {
+                height: row.get::<_, i64>(0)? as u32,
+                hash: row.get(1)?,
+                tx_count: row.get::<_, i64>(2)? as usize,
+                total_fees: row.get::<_, i64>(3)? as u64,
+                tracked_count: row.get::<_, i64>(4)? as usize,
+                signal_count: row.get::<_, i64>(5)? as usize,
+            })
+        })?;
         rows.collect()
     }
+
+    /// Record an RBF replacement link.
+    pub fn store_replacement(&self, diff: &ReplacementDiff) -> Result<(), rusqlite::Error> {
+        let diff_json = serde_json::to_string(diff).unwrap_or_default();
+        self.conn.execute(
+            "INSERT INTO replacements (old_txid, new_txid, old_score, new_score, summary, diff, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, datetime('now'))",
+            rusqlite::params![diff.old_txid, diff.new_txid, diff.old_score, diff.new_score, diff.summary(), diff_json],
+        )?;
+        Ok(())
+    }
+
+    /// Record that a child bumped `parent_txid` via CPFP, unless a higher package rate is
+    /// already recorded. Keyed by txid like the outcomes.
+    pub fn store_cpfp_bump(&self, bump: &CpfpBump) -> Result<(), rusqlite::Error> {
+        self.conn.execute(
+            "INSERT INTO cpfp_bumps (parent_txid, child_txid, package_fee_rate, bumped_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(parent_txid) DO UPDATE SET child_txid = excluded.child_txid,
+                package_fee_rate = excluded.package_fee_rate, bumped_at = excluded.bumped_at
+             WHERE excluded.package_fee_rate > package_fee_rate",
+            rusqlite::params![bump.parent_txid, bump.child_txid, bump.package_fee_rate, Utc::now().to_rfc3339()],
+        )?;
+        Ok(())
+    }
+
+    /// Get replacements involving `txid` (as original or replacement), oldest first.
+    #[cfg(test)]
+    pub fn get_replacements_for(&self, txid: &str) -> Result<Vec<ReplacementDiff>, rusqlite::Error> {
+        let mut stmt = self.conn.prepare(
+            "SELECT diff FROM replacements WHERE old_txid = ?1 OR new_txid = ?1 ORDER BY id"
+        )?;
+        let rows = stmt.query_map(rusqlite::params![txid], |row| row.get::<_, String>(0))?;
+        let mut diffs = Vec::new();
+        for json in rows {
+            if let Ok(diff) = serde_json::from_str(&json?) {
+                diffs.push(diff);
+            }
+        }
+        Ok(diffs)
+    }
+
+    /// Undo the effects of a disconnected block at `height`:
+    /// signals seen at or above it are flagged as reorged, UTXO cache rows and block times
+    /// from it (and anything above) are invalidated, and its block stats row is dropped.
+    /// Returns the number of signals newly flagged.
+    pub fn apply_block_disconnect(&self, block_hash: &str, height: u32) -> Result<usize, rusqlite::Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        let flagged = tx.execute(
+            "UPDATE signals SET reorged = 1 WHERE block_height_seen >= ?1 AND reorged = 0",
+            rusqlite::params![height],
+        )?;
+        tx.execute(
+            "DELETE FROM utxo_cache WHERE block_height >= ?1",
+            rusqlite::params![height],
+        )?;
+        tx.execute("DELETE FROM block_times WHERE height >= ?1", rusqlite::params![height])?;
+        // Those txs are unconfirmed again. Txs its spends conflicted stay out of the
+        // mempool, but no longer because of a block on the chain.
+        tx.execute(
+            "DELETE FROM signal_outcomes WHERE confirmed_height >= ?1 AND outcome = 'confirmed'",
+            rusqlite::params![height],
+        )?;
+        tx.execute(
+            "UPDATE signal_outcomes SET confirmed_height = NULL, confirmed_time = NULL
+             WHERE confirmed_height >= ?1 AND outcome = 'conflicted'",
+            rusqlite::params![height],
+        )?;
+        tx.execute("DELETE FROM blocks WHERE hash = ?1", rusqlite::params![block_hash])?;
+        tx.commit()?;
+        Ok(flagged)
+    }
 }
 
 #[cfg(test)]
@@ -403,17 +1108,48 @@ mod tests {
         SharedDatabase::open(&path).unwrap()
     }
 
+    fn utxo(value: u64, block_height: u32) -> CachedUtxo {
+        CachedUtxo {
+            value,
+            script_type: "witness_v0_keyhash".to_string(),
+            script_pubkey: vec![0x00, 0x14, 0xab],
+            address: Some("bc1qtest".to_string()),
+            block_height,
+            block_time: 1700000000,
+        }
+    }
+
     #[test]
     fn utxo_cache_roundtrip() {
         let db = open_memory_db();
-        db.cache_utxo("abc123", 0, 50_000, "p2wpkh", 800_000, 1700000000).unwrap();
+        db.cache_utxo("abc123", 0, &utxo(50_000, 800_000)).unwrap();
         let result = db.get_utxo("abc123", 0).unwrap();
-        assert!(result.is_some());
-        let (value, script_type, height, time) = result.unwrap();
-        assert_eq!(value, 50_000);
-        assert_eq!(script_type, "p2wpkh");
-        assert_eq!(height, 800_000);
-        assert_eq!(time, 1700000000);
+        assert_eq!(result, Some(utxo(50_000, 800_000)));
+    }
+
+    #[test]
+    fn legacy_utxo_rows_migrated_and_missed() {
+        let path = std::env::temp_dir().join(format!("txradar_legacy_utxo_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute_batch(
+                "CREATE TABLE utxo_cache (
+                    txid TEXT NOT NULL, vout INTEGER NOT NULL, value INTEGER NOT NULL,
+                    script_type TEXT NOT NULL, block_height INTEGER NOT NULL,
+                    block_time INTEGER NOT NULL, PRIMARY KEY (txid, vout)
+                );
+                INSERT INTO utxo_cache VALUES ('old', 0, 100, 'witness_v0_keyhash', 1, 1);",
+            )
+            .unwrap();
+        }
+
+        let db = SharedDatabase::open(&path).unwrap();
+        // The old row has no script: a miss, so it is re-fetched and upgraded
+        assert!(db.get_utxo("old", 0).unwrap().is_none());
+        db.cache_utxo("old", 0, &utxo(100, 1)).unwrap();
+        assert_eq!(db.get_utxo("old", 0).unwrap(), Some(utxo(100, 1)));
+        let _ = std::fs::remove_file(&path);
     }
 
     #[test]
@@ -425,10 +1161,92 @@ mod tests {
     #[test]
     fn utxo_cache_overwrite() {
         let db = open_memory_db();
-        db.cache_utxo("tx1", 0, 100, "p2pkh", 1, 1).unwrap();
-        db.cache_utxo("tx1", 0, 200, "p2wpkh", 2, 2).unwrap();
-        let (value, _, _, _) = db.get_utxo("tx1", 0).unwrap().unwrap();
-        assert_eq!(value, 200);
+        db.cache_utxo("tx1", 0, &utxo(100, 1)).unwrap();
+        db.cache_utxo("tx1", 0, &utxo(200, 2)).unwrap();
+        assert_eq!(db.get_utxo("tx1", 0).unwrap().unwrap().value, 200);
+    }
+
+    #[test]
+    fn spent_utxos_deleted() {
+        let db = open_memory_db();
+        db.cache_utxo("tx1", 0, &utxo(100, 1)).unwrap();
+        db.cache_utxo("tx1", 1, &utxo(200, 1)).unwrap();
+        let spent = vec![("tx1".to_string(), 0), ("unknown".to_string(), 3)];
+        assert_eq!(db.delete_utxos(&spent).unwrap(), 1);
+        assert!(db.get_utxo("tx1", 0).unwrap().is_none());
+        assert!(db.get_utxo("tx1", 1).unwrap().is_some());
+    }
+
+    #[test]
+    fn lru_eviction_keeps_recently_read() {
+        let db = open_memory_db();
+        for vout in 0..4 {
+            db.cache_utxo("tx1", vout, &utxo(100, 1)).unwrap();
+        }
+        // Age all rows, then read vout 2 so it becomes the most recent
+        db.write(|db| db.conn.execute("UPDATE utxo_cache SET last_accessed = vout", [])).unwrap();
+        assert!(db.get_utxo("tx1", 2).unwrap().is_some());
+
+        assert_eq!(db.evict_utxos(10).unwrap(), 0);
+        assert_eq!(db.evict_utxos(2).unwrap(), 2);
+        assert!(db.get_utxo("tx1", 2).unwrap().is_some());
+        assert!(db.get_utxo("tx1", 3).unwrap().is_some());
+        assert!(db.get_utxo("tx1", 0).unwrap().is_none());
+
+        let stats = db.utxo_cache_stats().unwrap();
+        assert_eq!(stats.rows, 2);
+        assert!(stats.disk_bytes > 0);
+    }
+
+    #[test]
+    fn utxo_row_count_maintained() {
+        let db = open_memory_db();
+        let rows = || db.utxo_cache_stats().unwrap().rows;
+        db.cache_utxo("tx1", 0, &utxo(100, 1)).unwrap();
+        db.cache_utxo("tx1", 1, &utxo(100, 1)).unwrap();
+        // Overwrites don't count twice
+        db.cache_utxo("tx1", 0, &utxo(200, 2)).unwrap();
+        let progress = SnapshotImport {
+            base_blockhash: "base".to_string(),
+            coins_total: 2,
+            coins_done: 2,
+            offset: 0,
+            finished: true,
+        };
+        let batch = vec![("tx1".to_string(), 1, utxo(100, 1)), ("tx2".to_string(), 0, utxo(100, 1))];
+        db.import_utxo_batch(batch, &progress).unwrap();
+        assert_eq!(rows(), 3);
+
+        db.delete_utxos(&[("tx2".to_string(), 0)]).unwrap();
+        assert_eq!(rows(), 2);
+        assert_eq!(db.evict_utxos(1).unwrap(), 1);
+        assert_eq!(rows(), 1);
+    }
+
+    #[test]
+    fn reads_not_blocked_by_writer() {
+        let db = open_memory_db();
+        db.cache_utxo("tx1", 0, &utxo(100, 1)).unwrap();
+
+        // Hold a write transaction open on the writer thread
+        let (release_tx, release_rx) = std::sync::mpsc::channel::<()>();
+        let (started_tx, started_rx) = std::sync::mpsc::channel::<()>();
+        db.write_detached("held transaction", move |db| {
+            let tx = db.conn.unchecked_transaction()?;
+            db.cache_utxo("tx2", 0, &utxo(200, 2))?;
+            started_tx.send(()).unwrap();
+            release_rx.recv().unwrap();
+            tx.commit()
+        });
+        started_rx.recv().unwrap();
+
+        // Readers see the last committed state without waiting
+        assert!(db.get_utxo("tx1", 0).unwrap().is_some());
+        assert!(db.get_utxo("tx2", 0).unwrap().is_none());
+
+        release_tx.send(()).unwrap();
+        db.flush();
+        assert!(db.get_utxo("tx2", 0).unwrap().is_some());
     }
 
     #[test]
@@ -461,6 +1279,118 @@ mod tests {
         assert_eq!(db.get_signal_count().unwrap(), 0);
     }
 
+    #[tokio::test]
+    async fn stored_signals_by_txid() {
+        let db = open_memory_db();
+        let txids = vec!["tx1".to_string(), "tx2".to_string()];
+        assert!(db.stored_signals_async(txids.clone()).await.unwrap().is_empty());
+        db.store_signal("tx1", 85.0, "Critical", "{}", true, 1_000_000, 50.0, Some(500.0), 800_000).unwrap();
+        assert_eq!(db.stored_signals_async(txids).await.unwrap(), HashSet::from(["tx1".to_string()]));
+    }
+
+    #[test]
+    fn block_stats_roundtrip() {
+        let db = open_memory_db();
+        let stats = BlockStats {
+            height: 850_000,
+            hash: "0000abcd".into(),
+            tx_count: 3000,
+            total_fees: 25_000_000,
+            tracked_count: 2800,
+            signal_count: 12,
+        };
+        db.store_block_stats(&stats).unwrap();
+        db.store_block_stats(&BlockStats { height: 850_001, hash: "0000abce".into(), ..Default::default() }).unwrap();
+
+        let recent = db.get_recent_block_stats(10).unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].height, 850_001);
+        assert_eq!(recent[1], stats);
+    }
+
+    #[tokio::test]
+    async fn block_disconnect_flags_signals_and_invalidates_cache() {
+        let db = open_memory_db();
+        db.store_signal("old", 50.0, "Medium", "{}", false, 1, 1.0, None, 849_999).unwrap();
+        db.store_signal("stale", 85.0, "Critical", "{}", true, 1, 1.0, None, 850_000).unwrap();
+        db.store_signal("staler", 85.0, "Critical", "{}", true, 1, 1.0, None, 850_001).unwrap();
+        db.cache_utxo("kept", 0, &utxo(100, 849_999)).unwrap();
+        db.cache_utxo("dropped", 0, &utxo(100, 850_000)).unwrap();
+        db.store_block_stats(&BlockStats { height: 850_000, hash: "stale_block".into(), ..Default::default() }).unwrap();
+
+        assert_eq!(db.apply_block_disconnect("stale_block", 850_000).await.unwrap(), 2);
+        // Already flagged signals aren't counted twice
+        assert_eq!(db.apply_block_disconnect("stale_block", 850_000).await.unwrap(), 0);
+
+        let signals = db.get_recent_signals(10).unwrap();
+        let flagged: Vec<&str> = signals.iter().filter(|s| s.reorged).map(|s| s.txid.as_str()).collect();
+        assert_eq!(flagged.len(), 2);
+        assert!(!flagged.contains(&"old"));
+
+        assert!(db.get_utxo("kept", 0).unwrap().is_some());
+        assert!(db.get_utxo("dropped", 0).unwrap().is_none());
+        assert!(db.get_recent_block_stats(10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn replacement_roundtrip() {
+        use crate::core::AlertLevel;
+
+        let db = open_memory_db();
+        let diff = ReplacementDiff {
+            old_txid: "old".into(),
+            new_txid: "new".into(),
+            old_score: 82.0,
+            new_score: 35.0,
+            old_alert_level: AlertLevel::Critical,
+            new_alert_level: AlertLevel::Low,
+            fee_delta: 2_000,
+            old_fee_rate: 5.0,
+            new_fee_rate: 15.0,
+            exchange_output_removed: true,
+            exchange_output_added: false,
+            removed_outputs: 1,
+            added_outputs: 1,
+            redirected_value: 900_000,
+        };
+        let stored = diff.clone();
+        db.write(move |db| db.store_replacement(&stored)).unwrap();
+
+        assert_eq!(db.get_replacements_for("old").unwrap(), vec![diff.clone()]);
+        assert_eq!(db.get_replacements_for("new").unwrap(), vec![diff]);
+        assert!(db.get_replacements_for("other").unwrap().is_empty());
+    }
+
+    #[test]
+    fn cpfp_bump_joined_on_signal() {
+        use crate::core::AlertLevel;
+
+        let db = open_memory_db();
+        let bump = |child: &str, package_fee_rate: f64| CpfpBump {
+            parent_txid: "parent".into(),
+            child_txid: child.into(),
+            parent_score: 72.0,
+            parent_alert_level: AlertLevel::High,
+            child_score: 20.0,
+            parent_fee_rate: 1.0,
+            package_fee_rate,
+        };
+        // The bump may land before the signal is flushed
+        db.write(move |db| {
+            db.store_cpfp_bump(&bump("child1", 12.5))?;
+            db.store_cpfp_bump(&bump("child2", 8.0))
+        })
+        .unwrap();
+        db.store_signal("parent", 72.0, "High", "{}", false, 1, 1.0, None, 1).unwrap();
+        db.store_signal("other", 60.0, "High", "{}", false, 1, 1.0, None, 1).unwrap();
+
+        let signals = db.get_signals_above_score(0.0, 10).unwrap();
+        let parent = signals.iter().find(|s| s.txid == "parent").unwrap();
+        assert_eq!(parent.bumped_by.as_deref(), Some("child1"));
+        assert_eq!(parent.package_fee_rate, Some(12.5));
+        assert_eq!(signals.iter().find(|s| s.txid == "other").unwrap().bumped_by, None);
+    }
+
     #[test]
     fn address_tag_roundtrip() {
         let db = open_memory_db();
@@ -508,14 +1438,70 @@ mod tests {
         assert_eq!(results.len(), 1);
     }
 
-    #[test]
-    fn batch_store_signals() {
+    #[tokio::test]
+    async fn signal_outcomes_joined_and_rolled_back() {
+        let db = open_memory_db();
+        let confirmed = SignalOutcome {
+            outcome: "confirmed".into(),
+            confirmed_height: Some(850_000),
+            confirmed_time: Some(1_700_000_000),
+            mempool_secs: 720,
+            replaced_by: None,
+        };
+        let conflicted = SignalOutcome {
+            outcome: "conflicted".into(),
+            confirmed_height: Some(850_000),
+            confirmed_time: Some(1_700_000_000),
+            mempool_secs: 600,
+            replaced_by: None,
+        };
+        let replaced = SignalOutcome {
+            outcome: "replaced".into(),
+            confirmed_height: None,
+            confirmed_time: None,
+            mempool_secs: 30,
+            replaced_by: Some("tx3".into()),
+        };
+        // Outcomes may be written before the signal itself is flushed
+        let (c, r, x) = (confirmed.clone(), replaced.clone(), conflicted.clone());
+        db.write(move |db| {
+            db.record_signal_outcome("tx1", &c)?;
+            db.record_signal_outcome("tx2", &r)?;
+            db.record_signal_outcome("tx4", &x)
+        })
+        .unwrap();
+        db.store_signal("tx1", 80.0, "Critical", "{}", false, 1, 1.0, None, 849_999).unwrap();
+        db.store_signal("tx2", 60.0, "High", "{}", false, 1, 1.0, None, 849_999).unwrap();
+        db.store_signal("tx4", 50.0, "Medium", "{}", false, 1, 1.0, None, 849_999).unwrap();
+        db.store_signal("tx5", 50.0, "Medium", "{}", false, 1, 1.0, None, 849_999).unwrap();
+
+        let outcome_of = |txid: &str| {
+            db.get_signals_above_score(0.0, 10).unwrap().into_iter().find(|s| s.txid == txid).unwrap().outcome
+        };
+        assert_eq!(outcome_of("tx1"), Some(confirmed));
+        assert_eq!(outcome_of("tx2"), Some(replaced.clone()));
+        assert_eq!(outcome_of("tx4"), Some(conflicted.clone()));
+        assert_eq!(outcome_of("tx5"), None);
+
+        // The confirming block is disconnected: tx1 is pending again, tx4 stays conflicted
+        // without the block
+        db.apply_block_disconnect("block", 850_000).await.unwrap();
+        assert_eq!(outcome_of("tx1"), None);
+        assert_eq!(
+            outcome_of("tx4"),
+            Some(SignalOutcome { confirmed_height: None, confirmed_time: None, ..conflicted })
+        );
+        assert_eq!(outcome_of("tx2"), Some(replaced));
+    }
+
+    #[tokio::test]
+    async fn batch_store_signals() {
         let db = open_memory_db();
         let entries = vec![
-            SignalBatchEntry { txid: "tx1".into(), score: 80.0, alert_level: "Critical".into(), rule_scores_json: "{}".into(), to_exchange: true, total_input_value: 1000, fee_rate: 10.0, coin_days_destroyed: None, block_height_seen: 1 },
-            SignalBatchEntry { txid: "tx2".into(), score: 50.0, alert_level: "Medium".into(), rule_scores_json: "{}".into(), to_exchange: false, total_input_value: 500, fee_rate: 5.0, coin_days_destroyed: Some(100.0), block_height_seen: 2 },
+            SignalBatchEntry { txid: "tx1".into(), score: 80.0, alert_level: "Critical".into(), rule_scores_json: "{}".into(), to_exchange: true, total_input_value: 1000, fee_rate: 10.0, coin_days_destroyed: None, block_height_seen: 1, analyzed_json: "{}".into(), raw_tx: None },
+            SignalBatchEntry { txid: "tx2".into(), score: 50.0, alert_level: "Medium".into(), rule_scores_json: "{}".into(), to_exchange: false, total_input_value: 500, fee_rate: 5.0, coin_days_destroyed: Some(100.0), block_height_seen: 2, analyzed_json: "{}".into(), raw_tx: Some(vec![1, 2]) },
         ];
-        db.store_signals_batch(&entries).unwrap();
+        db.store_signals_batch(entries).await.unwrap();
         assert_eq!(db.get_signal_count().unwrap(), 2);
     }
 }
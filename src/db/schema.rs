@@ -1,6 +1,144 @@
-use rusqlite::Connection;
+use std::fmt;
+use std::path::Path;
 
-pub fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
+use rusqlite::{Connection, OpenFlags};
+use tracing::info;
+
+/// One schema step. `PRAGMA user_version` holds the version of the last applied step.
+pub struct Migration {
+    pub version: u32,
+    pub description: &'static str,
+    apply: fn(&Connection) -> Result<(), rusqlite::Error>,
+}
+
+/// All schema steps in order. Append new ones; never edit an applied step.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "baseline schema (adopts unversioned databases)",
+        apply: baseline,
+    },
+    Migration {
+        version: 2,
+        description: "signal outcomes table",
+        apply: signal_outcomes,
+    },
+    Migration {
+        version: 3,
+        description: "analysis payload on signals, rescores table",
+        apply: signal_payloads,
+    },
+    Migration {
+        version: 4,
+        description: "entity activity table",
+        apply: entity_activity,
+    },
+    Migration {
+        version: 5,
+        description: "cpfp bumps table",
+        apply: cpfp_bumps,
+    },
+    Migration {
+        version: 6,
+        description: "utxo cache row count",
+        apply: utxo_cache_count,
+    },
+];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    Sqlite(rusqlite::Error),
+    /// A step failed and was rolled back; the database stays at the previous version.
+    Step {
+        version: u32,
+        description: &'static str,
+        source: rusqlite::Error,
+    },
+    /// The database was written by a newer build.
+    TooNew { version: u32, latest: u32 },
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MigrationError::Sqlite(e) => write!(f, "database error: {e}"),
+            MigrationError::Step { version, description, source } => write!(
+                f,
+                "migration {version} ({description}) failed and was rolled back: {source}"
+            ),
+            MigrationError::TooNew { version, latest } => write!(
+                f,
+                "database schema version {version} is newer than this build supports ({latest})"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+impl From<rusqlite::Error> for MigrationError {
+    fn from(e: rusqlite::Error) -> Self {
+        MigrationError::Sqlite(e)
+    }
+}
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+pub fn user_version(conn: &Connection) -> Result<u32, rusqlite::Error> {
+    conn.pragma_query_value(None, "user_version", |row| row.get(0))
+}
+
+/// Bring the database up to the latest version, one transaction per step.
+pub fn migrate(conn: &Connection) -> Result<(), MigrationError> {
+    run(conn, MIGRATIONS)
+}
+
+/// Steps not yet applied to the database at `path`, and its current version. Opens the
+/// file read-only; a missing file is version 0.
+pub fn pending(path: &Path) -> Result<(u32, Vec<&'static Migration>), MigrationError> {
+    if !path.exists() {
+        return Ok((0, MIGRATIONS.iter().collect()));
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let version = checked_version(&conn, MIGRATIONS)?;
+    Ok((version, MIGRATIONS.iter().filter(|m| m.version > version).collect()))
+}
+
+fn checked_version(conn: &Connection, steps: &[Migration]) -> Result<u32, MigrationError> {
+    let version = user_version(conn)?;
+    let latest = steps.last().map_or(0, |m| m.version);
+    if version > latest {
+        return Err(MigrationError::TooNew { version, latest });
+    }
+    Ok(version)
+}
+
+fn run(conn: &Connection, steps: &[Migration]) -> Result<(), MigrationError> {
+    let version = checked_version(conn, steps)?;
+    for step in steps.iter().filter(|m| m.version > version) {
+        apply(conn, step).map_err(|source| MigrationError::Step {
+            version: step.version,
+            description: step.description,
+            source,
+        })?;
+        info!("Database migrated to version {}: {}", step.version, step.description);
+    }
+    Ok(())
+}
+
+fn apply(conn: &Connection, step: &Migration) -> Result<(), rusqlite::Error> {
+    let tx = conn.unchecked_transaction()?;
+    (step.apply)(&tx)?;
+    tx.pragma_update(None, "user_version", step.version)?;
+    tx.commit()
+}
+
+/// The schema as of the switch to versioned migrations. Databases from before have
+/// `user_version` 0 and some of these tables and columns already; everything here is
+/// idempotent so they are adopted as they are.
+fn baseline(conn: &Connection) -> Result<(), rusqlite::Error> {
     conn.execute_batch(
         "
         CREATE TABLE IF NOT EXISTS utxo_cache (
@@ -10,6 +148,9 @@ pub fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
             script_type TEXT NOT NULL,
             block_height INTEGER NOT NULL,
             block_time  INTEGER NOT NULL,
+            script_pubkey BLOB,
+            address     TEXT,
+            last_accessed INTEGER NOT NULL DEFAULT 0, -- unix time of the last read (LRU)
             PRIMARY KEY (txid, vout)
         );
 
@@ -24,13 +165,53 @@ pub fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
             fee_rate            REAL NOT NULL DEFAULT 0.0,
             coin_days_destroyed REAL,
             block_height_seen   INTEGER NOT NULL DEFAULT 0,
-            created_at          TEXT NOT NULL
+            created_at          TEXT NOT NULL,
+            reorged             INTEGER NOT NULL DEFAULT 0
         );
 
         CREATE INDEX IF NOT EXISTS idx_signals_score ON signals(score DESC);
         CREATE INDEX IF NOT EXISTS idx_signals_created ON signals(created_at DESC);
+        CREATE INDEX IF NOT EXISTS idx_signals_txid ON signals(txid);
         CREATE INDEX IF NOT EXISTS idx_utxo_cache_height ON utxo_cache(block_height);
 
+        CREATE TABLE IF NOT EXISTS blocks (
+            height         INTEGER NOT NULL,
+            hash           TEXT PRIMARY KEY,
+            tx_count       INTEGER NOT NULL,
+            total_fees     INTEGER NOT NULL,
+            tracked_count  INTEGER NOT NULL,
+            signal_count   INTEGER NOT NULL,
+            connected_at   TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_blocks_height ON blocks(height DESC);
+
+        CREATE TABLE IF NOT EXISTS replacements (
+            id          INTEGER PRIMARY KEY AUTOINCREMENT,
+            old_txid    TEXT NOT NULL,
+            new_txid    TEXT NOT NULL,
+            old_score   REAL NOT NULL,
+            new_score   REAL NOT NULL,
+            summary     TEXT NOT NULL,
+            diff        TEXT NOT NULL, -- JSON
+            created_at  TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_replacements_old ON replacements(old_txid);
+        CREATE INDEX IF NOT EXISTS idx_replacements_new ON replacements(new_txid);
+
+        CREATE TABLE IF NOT EXISTS block_times (
+            height      INTEGER PRIMARY KEY,
+            time        INTEGER NOT NULL
+        );
+
+        CREATE TABLE IF NOT EXISTS snapshot_imports (
+            base_blockhash TEXT PRIMARY KEY,
+            coins_total    INTEGER NOT NULL,
+            coins_done     INTEGER NOT NULL,
+            offset         INTEGER NOT NULL, -- file position after the last imported coin
+            finished       INTEGER NOT NULL DEFAULT 0,
+            updated_at     TEXT NOT NULL
+        );
+
         CREATE TABLE IF NOT EXISTS address_tags (
             address     TEXT PRIMARY KEY,
             entity      TEXT NOT NULL,
@@ -42,29 +223,176 @@ pub fn migrate(conn: &Connection) -> Result<(), rusqlite::Error> {
         ",
     )?;
 
-    // Add columns if they don't exist (migration for existing DBs)
+    // Columns added to existing tables before versioning
     let cols = [
-        "to_exchange INTEGER NOT NULL DEFAULT 0",
-        "total_input_value INTEGER NOT NULL DEFAULT 0",
-        "fee_rate REAL NOT NULL DEFAULT 0.0",
-        "coin_days_destroyed REAL",
-        "block_height_seen INTEGER NOT NULL DEFAULT 0",
+        ("signals", "to_exchange INTEGER NOT NULL DEFAULT 0"),
+        ("signals", "total_input_value INTEGER NOT NULL DEFAULT 0"),
+        ("signals", "fee_rate REAL NOT NULL DEFAULT 0.0"),
+        ("signals", "coin_days_destroyed REAL"),
+        ("signals", "block_height_seen INTEGER NOT NULL DEFAULT 0"),
+        ("signals", "reorged INTEGER NOT NULL DEFAULT 0"),
+        // utxo_cache rows from before script_pubkey keep NULL and are treated as misses
+        ("utxo_cache", "script_pubkey BLOB"),
+        ("utxo_cache", "address TEXT"),
+        ("utxo_cache", "last_accessed INTEGER NOT NULL DEFAULT 0"),
     ];
-    for col_def in &cols {
-        let _col_name = col_def.split_whitespace().next().unwrap();
-        let sql = format!("ALTER TABLE signals ADD COLUMN {col_def}");
-        // Ignore error if column already exists
-        match conn.execute_batch(&sql) {
-            Ok(_) => {}
-            Err(e) => {
-                let msg = e.to_string();
-                if !msg.contains("duplicate column") {
-                    // Ignore — column already exists
-                }
-                let _ = msg;
-            }
+    for (table, col_def) in &cols {
+        match conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {col_def}")) {
+            Err(e) if e.to_string().contains("duplicate column") => {}
+            result => result?,
         }
     }
 
+    // Needs the migrated column
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_utxo_cache_accessed ON utxo_cache(last_accessed);")?;
+
     Ok(())
 }
+
+/// How signaled txs left the mempool: confirmed, replaced, evicted or conflicted.
+fn signal_outcomes(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE signal_outcomes (
+            txid             TEXT PRIMARY KEY,
+            outcome          TEXT NOT NULL,
+            confirmed_height INTEGER,
+            confirmed_time   INTEGER, -- block timestamp
+            mempool_secs     INTEGER NOT NULL, -- first sight to outcome
+            replaced_by      TEXT,
+            resolved_at      TEXT NOT NULL
+        );
+        CREATE INDEX idx_signal_outcomes_height ON signal_outcomes(confirmed_height);
+        ",
+    )
+}
+
+/// The full `AnalyzedTx` (JSON) and optionally the raw tx of each signal, and the results
+/// of re-scoring them with another engine configuration.
+fn signal_payloads(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        ALTER TABLE signals ADD COLUMN analyzed_tx TEXT; -- JSON
+        ALTER TABLE signals ADD COLUMN raw_tx BLOB;
+
+        CREATE TABLE rescores (
+            id             INTEGER PRIMARY KEY AUTOINCREMENT,
+            run_id         TEXT NOT NULL,
+            signal_id      INTEGER NOT NULL REFERENCES signals(id),
+            txid           TEXT NOT NULL,
+            original_score REAL NOT NULL,
+            original_alert_level TEXT NOT NULL,
+            score          REAL NOT NULL,
+            alert_level    TEXT NOT NULL,
+            rule_scores    TEXT NOT NULL, -- JSON
+            created_at     TEXT NOT NULL
+        );
+        CREATE INDEX idx_rescores_run ON rescores(run_id);
+        ",
+    )
+}
+
+/// Last outgoing tx of each tagged entity, for the dormant cluster rule.
+fn entity_activity(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE entity_activity (
+            entity        TEXT PRIMARY KEY,
+            last_outgoing INTEGER NOT NULL, -- unix seconds
+            last_txid     TEXT NOT NULL
+        );
+        ",
+    )
+}
+
+/// Signaled txs bumped by a child paying for them (CPFP), keyed by the parent.
+fn cpfp_bumps(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE cpfp_bumps (
+            parent_txid      TEXT PRIMARY KEY,
+            child_txid       TEXT NOT NULL,
+            package_fee_rate REAL NOT NULL, -- sat/vB of the child with its ancestors
+            bumped_at        TEXT NOT NULL
+        );
+        ",
+    )
+}
+
+/// Row count of `utxo_cache`, kept by triggers so size checks don't scan the table.
+/// Writes upsert rather than `INSERT OR REPLACE`, whose implicit deletes skip triggers.
+fn utxo_cache_count(conn: &Connection) -> Result<(), rusqlite::Error> {
+    conn.execute_batch(
+        "
+        CREATE TABLE utxo_cache_count (rows INTEGER NOT NULL);
+        INSERT INTO utxo_cache_count SELECT count(*) FROM utxo_cache;
+        CREATE TRIGGER utxo_cache_count_insert AFTER INSERT ON utxo_cache
+            BEGIN UPDATE utxo_cache_count SET rows = rows + 1; END;
+        CREATE TRIGGER utxo_cache_count_delete AFTER DELETE ON utxo_cache
+            BEGIN UPDATE utxo_cache_count SET rows = rows - 1; END;
+        ",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_a(conn: &Connection) -> Result<(), rusqlite::Error> {
+        conn.execute_batch("CREATE TABLE a (x INTEGER);")
+    }
+
+    fn broken(conn: &Connection) -> Result<(), rusqlite::Error> {
+        conn.execute_batch("CREATE TABLE b (x INTEGER); INSERT INTO missing VALUES (1);")
+    }
+
+    #[test]
+    fn fresh_database_reaches_latest() {
+        let conn = Connection::open_in_memory().unwrap();
+        migrate(&conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+        // Idempotent
+        migrate(&conn).unwrap();
+        assert_eq!(user_version(&conn).unwrap(), latest_version());
+    }
+
+    #[test]
+    fn failed_step_rolls_back() {
+        let conn = Connection::open_in_memory().unwrap();
+        let steps = [
+            Migration { version: 1, description: "a", apply: create_a },
+            Migration { version: 2, description: "b", apply: broken },
+        ];
+        let err = run(&conn, &steps).unwrap_err();
+        assert!(matches!(err, MigrationError::Step { version: 2, .. }));
+        assert!(err.to_string().starts_with("migration 2 (b) failed"));
+        assert_eq!(user_version(&conn).unwrap(), 1);
+        let tables: i64 = conn
+            .query_row("SELECT count(*) FROM sqlite_master WHERE name IN ('a', 'b')", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tables, 1);
+    }
+
+    #[test]
+    fn newer_database_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "user_version", latest_version() + 1).unwrap();
+        assert!(matches!(migrate(&conn), Err(MigrationError::TooNew { .. })));
+    }
+
+    #[test]
+    fn pending_reads_without_migrating() {
+        let path = std::env::temp_dir().join(format!("txradar_pending_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(pending(&path).unwrap().1.len(), MIGRATIONS.len());
+        assert!(!path.exists());
+
+        Connection::open(&path).unwrap();
+        let (version, steps) = pending(&path).unwrap();
+        assert_eq!((version, steps.len()), (0, MIGRATIONS.len()));
+        migrate(&Connection::open(&path).unwrap()).unwrap();
+        let (version, steps) = pending(&path).unwrap();
+        assert_eq!((version, steps.len()), (latest_version(), 0));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,9 +1,12 @@
+mod cli;
 mod config;
 mod core;
 mod db;
 mod notifications;
 mod rpc;
 mod signals;
+mod snapshot;
+mod source;
 pub mod tags;
 mod ui;
 
@@ -15,9 +18,24 @@ use crate::config::Config;
 use crate::core::pipeline::PipelineOutput;
 use crate::db::SharedDatabase;
 use crate::rpc::BitcoinRpc;
-use crate::rpc::zmq_sub::{ZmqConfig, start_zmq_subscriber};
+use crate::rpc::zmq_sub::{ZmqConfig, ZmqSource};
+use crate::source::EventSource;
+use crate::source::record::CaptureRecorder;
+use crate::source::replay::ReplaySource;
 
 fn main() {
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(cli::Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{}", cli::USAGE);
+            std::process::exit(2);
+        }
+    };
+
     // Initialize logging
     tracing_subscriber::fmt()
         .with_env_filter(
@@ -25,20 +43,21 @@ fn main() {
         )
         .init();
 
+    let args = match command {
+        cli::Command::Run(args) => args,
+        cli::Command::ImportUtxos(path) => std::process::exit(import_utxos(&path)),
+        cli::Command::DbMigrate { dry_run } => std::process::exit(db_migrate(dry_run)),
+        cli::Command::Rescore { from, to } => std::process::exit(rescore(from, to)),
+        cli::Command::Help => return,
+    };
+
     tracing::info!("⚡ TxRadar10 starting...");
 
     // Load configuration
     let config = Config::load("config.toml");
     tracing::info!("Config: {:?}", config);
 
-    // Open UTXO cache database
-    let db_path = Path::new(&config.database.path);
-    if let Some(parent) = db_path.parent() {
-        std::fs::create_dir_all(parent).expect("Failed to create database directory");
-    }
-    let db = SharedDatabase::open(db_path)
-        .expect("Failed to open UTXO cache database");
-    tracing::info!("UTXO cache database opened at {}", config.database.path);
+    let db = open_database(&config);
 
     // Load exchange address tags from CSV if available
     if let Some(ref csv_path_str) = config.database.exchange_csv {
@@ -54,21 +73,10 @@ fn main() {
     // Build in-memory tag lookup
     let tag_lookup = std::sync::Arc::new(std::sync::Mutex::new(crate::tags::TagLookup::load_from_db(&db)));
 
-    // Create RPC client
-    let rpc = if config.bitcoin.rpc_user.is_some() && config.bitcoin.rpc_password.is_some() {
-        BitcoinRpc::new(
-            &config.bitcoin.rpc_host,
-            config.bitcoin.rpc_port,
-            config.bitcoin.rpc_user.as_deref().unwrap(),
-            config.bitcoin.rpc_password.as_deref().unwrap(),
-        )
-    } else {
-        BitcoinRpc::from_config_with_defaults(&config.bitcoin.rpc_host, config.bitcoin.rpc_port)
-    };
-    tracing::info!("Bitcoin RPC client configured");
+    let rpc = rpc_client(&config);
 
-    // ZMQ → Pipeline channel
-    let (zmq_tx, zmq_rx) = mpsc::unbounded_channel();
+    // Event source → Pipeline channel
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
 
     // Pipeline → UI channel
     let (ui_tx, ui_rx) = mpsc::unbounded_channel::<PipelineOutput>();
@@ -78,26 +86,41 @@ fn main() {
     UI_DB.set(std::sync::Mutex::new(Some(db.clone()))).ok();
     UI_CONFIG.set(config.clone()).ok();
 
-    // Build ZMQ config from Config
-    let zmq_config = ZmqConfig {
-        rawtx_endpoint: config.bitcoin.zmq_rawtx.clone(),
-        hashblock_endpoint: config.bitcoin.zmq_hashblock.clone(),
-        sequence_endpoint: config.bitcoin.zmq_sequence.clone(),
+    // Event source: capture replay or live ZMQ (built from Config).
+    // The node's current state has nothing to do with a capture.
+    let offline = args.replay.is_some();
+    let source: Box<dyn EventSource> = match args.replay {
+        Some(path) => Box::new(ReplaySource::new(path, args.speed)),
+        None => {
+            let zmq = ZmqSource::new(ZmqConfig {
+                rawtx_endpoint: config.bitcoin.zmq_rawtx.clone(),
+                hashblock_endpoint: config.bitcoin.zmq_hashblock.clone(),
+                sequence_endpoint: config.bitcoin.zmq_sequence.clone(),
+            });
+            match args.record {
+                Some(path) => match CaptureRecorder::new(&path, &config.capture) {
+                    Ok(recorder) => Box::new(zmq.with_recorder(recorder)),
+                    Err(e) => {
+                        eprintln!("error: can't record to {}: {e}", path.display());
+                        std::process::exit(1);
+                    }
+                },
+                None => Box::new(zmq),
+            }
+        }
     };
-
-    // Start ZMQ subscriber thread
-    let _zmq_handle = start_zmq_subscriber(zmq_config, zmq_tx);
-    tracing::info!("ZMQ subscriber started");
+    tracing::info!("Event source started: {}", source.describe());
+    let mut source_handle = Some(source.start(event_tx));
 
     // Start pipeline in a tokio runtime on a separate thread
     let pipeline_config = config.clone();
     std::thread::spawn(move || {
         let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
-        rt.block_on(core::pipeline::run_pipeline(zmq_rx, ui_tx, db, rpc, tag_lookup, pipeline_config));
+        rt.block_on(core::pipeline::run_pipeline(event_rx, ui_tx, db, rpc, tag_lookup, pipeline_config, offline));
     });
     tracing::info!("Pipeline thread started");
 
-    // Launch Dioxus desktop app (blocks)
+    // Launch Dioxus desktop app (blocks; the process exits when the event loop ends)
     dioxus::LaunchBuilder::desktop()
         .with_cfg(
             dioxus::desktop::Config::new()
@@ -106,10 +129,140 @@ fn main() {
                         .with_title("⚡ TxRadar10")
                         .with_inner_size(dioxus::desktop::tao::dpi::LogicalSize::new(1200.0, 800.0))
                 )
+                .with_custom_event_handler(move |event, _| {
+                    // Stop the event source before exit so a recording gets flushed
+                    if let dioxus::desktop::tao::event::Event::LoopDestroyed = event
+                        && let Some(handle) = source_handle.take()
+                        && handle.stop().is_err()
+                    {
+                        tracing::error!("Event source thread panicked");
+                    }
+                })
         )
         .launch(ui::App);
 }
 
+/// Open the UTXO cache database.
+fn open_database(config: &Config) -> SharedDatabase {
+    let db_path = Path::new(&config.database.path);
+    if let Some(parent) = db_path.parent() {
+        std::fs::create_dir_all(parent).expect("Failed to create database directory");
+    }
+    let db = SharedDatabase::open(db_path).unwrap_or_else(|e| {
+        eprintln!("error: failed to open database {}: {e}", db_path.display());
+        std::process::exit(1);
+    });
+    tracing::info!("UTXO cache database opened at {}", config.database.path);
+    db
+}
+
+/// Create the RPC client from config credentials, or cookie/bitcoin.conf auth.
+fn rpc_client(config: &Config) -> BitcoinRpc {
+    let rpc = if config.bitcoin.rpc_user.is_some() && config.bitcoin.rpc_password.is_some() {
+        BitcoinRpc::new(
+            &config.bitcoin.rpc_host,
+            config.bitcoin.rpc_port,
+            config.bitcoin.rpc_user.as_deref().unwrap(),
+            config.bitcoin.rpc_password.as_deref().unwrap(),
+        )
+    } else {
+        BitcoinRpc::from_config_with_defaults(&config.bitcoin.rpc_host, config.bitcoin.rpc_port)
+    };
+    tracing::info!("Bitcoin RPC client configured");
+    rpc
+}
+
+/// `import-utxos`: seed the UTXO cache from a snapshot. Returns the exit code.
+fn import_utxos(path: &Path) -> i32 {
+    let config = Config::load("config.toml");
+    let db = open_database(&config);
+    let rpc = rpc_client(&config);
+    let rt = tokio::runtime::Runtime::new().expect("Failed to create tokio runtime");
+    let max_rows = config.database.utxo_cache_max_rows;
+    match rt.block_on(snapshot::import_snapshot(path, &db, &rpc)) {
+        Ok(summary) if summary.already_done => {
+            println!("Snapshot at block {} was already imported", summary.base_blockhash);
+            0
+        }
+        Ok(summary) => {
+            println!(
+                "Imported {} coins from the snapshot at height {} ({} in total)",
+                summary.coins_imported, summary.base_height, summary.coins_total
+            );
+            if max_rows > 0 && summary.coins_total > max_rows {
+                eprintln!(
+                    "warning: database.utxo_cache_max_rows is {max_rows}; most imported coins will be evicted on the next start"
+                );
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("error: snapshot import failed: {e}");
+            1
+        }
+    }
+}
+
+/// `db migrate`: list the pending schema steps, then apply them unless `dry_run`.
+fn db_migrate(dry_run: bool) -> i32 {
+    let config = Config::load("config.toml");
+    let path = Path::new(&config.database.path);
+    let (version, steps) = match db::schema::pending(path) {
+        Ok(pending) => pending,
+        Err(e) => {
+            eprintln!("error: {}: {e}", path.display());
+            return 1;
+        }
+    };
+    println!(
+        "{} is at schema version {version} (latest {})",
+        path.display(),
+        db::schema::latest_version()
+    );
+    if steps.is_empty() {
+        println!("Nothing to migrate");
+        return 0;
+    }
+    for step in &steps {
+        println!("  pending {}: {}", step.version, step.description);
+    }
+    if dry_run {
+        return 0;
+    }
+    open_database(&config);
+    println!("Applied {} migration(s)", steps.len());
+    0
+}
+
+/// `rescore`: score stored signals again with the configured engine. Returns the exit code.
+fn rescore(from: Option<chrono::DateTime<chrono::Utc>>, to: Option<chrono::DateTime<chrono::Utc>>) -> i32 {
+    let config = Config::load("config.toml");
+    let db = open_database(&config);
+    let to = to.unwrap_or_else(chrono::Utc::now);
+    let from = from.unwrap_or(to - chrono::Duration::hours(24));
+    let engine = signals::SignalEngine::with_config(&config.signals);
+    match signals::rescore::rescore(&db, &engine, from, to) {
+        Ok(summary) => {
+            println!(
+                "Rescored {} signals from {from} to {to} as run {}",
+                summary.scored, summary.run_id
+            );
+            println!(
+                "  mean score change {:+.1}, alert level changed for {}",
+                summary.mean_delta, summary.level_changed
+            );
+            if summary.skipped > 0 {
+                println!("  skipped {} signals whose stored analysis no longer parses", summary.skipped);
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("error: rescore failed: {e}");
+            1
+        }
+    }
+}
+
 /// One-shot global to pass the UI receiver into the Dioxus app.
 static UI_RX: std::sync::OnceLock<std::sync::Mutex<Option<mpsc::UnboundedReceiver<PipelineOutput>>>> =
     std::sync::OnceLock::new();
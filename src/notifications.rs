@@ -3,6 +3,7 @@ use std::time::{Duration, Instant};
 
 use crate::config::NotificationConfig;
 use crate::core::ScoredTx;
+use crate::core::rbf::ReplacementDiff;
 
 /// Desktop notification sender with cooldown to prevent spam.
 pub struct Notifier {
@@ -39,6 +40,26 @@ impl Notifier {
         true
     }
 
+    /// Try to send a desktop notification for a replaced alert transaction.
+    /// Uses the original's score for the threshold and shares the cooldown.
+    pub fn notify_replacement(&self, diff: &ReplacementDiff) -> bool {
+        if !self.enabled {
+            return false;
+        }
+        if diff.old_score < self.min_score {
+            return false;
+        }
+        if !self.check_cooldown() {
+            return false;
+        }
+
+        let title = format!("⚡ TxRadar10 — {:?} tx replaced", diff.old_alert_level);
+        let txid_short = &diff.old_txid[..8.min(diff.old_txid.len())];
+        let body = format!("{txid_short} {}", diff.summary());
+        Self::show(title, body);
+        true
+    }
+
     /// Check and update cooldown. Returns true if enough time has passed.
     fn check_cooldown(&self) -> bool {
         let mut last = self.last_sent.lock().unwrap();
@@ -62,7 +83,11 @@ impl Notifier {
             body.push_str(" → Exchange detected");
         }
 
-        // Fire-and-forget in a background thread to never block the pipeline
+        Self::show(title, body);
+    }
+
+    /// Fire-and-forget in a background thread to never block the pipeline.
+    fn show(title: String, body: String) {
         std::thread::spawn(move || {
             if let Err(e) = notify_rust::Notification::new()
                 .summary(&title)
@@ -80,32 +105,23 @@ mod tests {
     use super::*;
     use crate::config::NotificationConfig;
     use crate::core::{AlertLevel, AnalyzedTx, ScoredTx};
+    use crate::signals::rules::tests::make_test_tx;
     use chrono::Utc;
 
     fn make_scored(score: f64, to_exchange: bool) -> ScoredTx {
         ScoredTx {
             tx: AnalyzedTx {
                 txid: "aabbccdd11223344".to_string(),
-                raw_size: 250,
-                vsize: 200,
                 total_input_value: 500_000_000,
                 total_output_value: 499_000_000,
                 fee: 1_000_000,
                 fee_rate: 50.0,
                 input_count: 2,
-                output_count: 2,
-                oldest_input_height: None,
-                oldest_input_time: None,
-                coin_days_destroyed: None,
-                is_rbf_signaling: false,
-                seen_at: Utc::now(),
                 prevouts_resolved: true,
+                resolved_inputs: 2,
                 to_exchange,
                 to_exchange_confidence: if to_exchange { 0.9 } else { 0.0 },
-                from_exchange: false,
-                from_exchange_confidence: 0.0,
-                is_coinjoin: false,
-                coinjoin_confidence: 0.0,
+                ..make_test_tx()
             },
             composite_score: score,
             rule_scores: vec![],
@@ -140,6 +156,32 @@ mod tests {
         assert!(notifier.check_cooldown());
     }
 
+    #[test]
+    fn replacement_below_min_score_skips() {
+        let config = NotificationConfig {
+            enabled: true,
+            min_score: 60.0,
+            cooldown_seconds: 0,
+        };
+        let notifier = Notifier::new(&config);
+        let old = make_scored(45.0, true);
+        let new = make_scored(20.0, false);
+        let entry = crate::core::mempool::MempoolEntry {
+            tx: old.tx,
+            state: crate::core::mempool::TxState::Replaced,
+            state_changed_at: Utc::now(),
+            replaced_by: None,
+            score: Some(old.composite_score),
+            alert_level: Some(old.alert_level),
+            spends: vec![],
+            outputs: vec![],
+            parents: vec![],
+            children: vec![],
+        };
+        let diff = ReplacementDiff::compute(&entry, &new, &[]);
+        assert!(!notifier.notify_replacement(&diff));
+    }
+
     #[test]
     fn disabled_notifier_skips() {
         let config = NotificationConfig {
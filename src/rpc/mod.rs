@@ -2,6 +2,7 @@ pub mod zmq_sub;
 
 use reqwest::Client;
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// Simple Bitcoin Core JSON-RPC client.
@@ -81,44 +82,121 @@ impl BitcoinRpc {
             "params": params,
         });
 
+        let json = self.post(&body).await?;
+        parse_response(&json)
+    }
+
+    /// Send several calls as one JSON-RPC batch request.
+    ///
+    /// The outer error means the request as a whole failed; otherwise there is one
+    /// result per call, in call order.
+    pub async fn call_batch(
+        &self,
+        calls: &[(&str, Vec<Value>)],
+    ) -> Result<Vec<Result<Value, RpcError>>, RpcError> {
+        if calls.is_empty() {
+            return Ok(Vec::new());
+        }
+        let body: Vec<Value> = calls
+            .iter()
+            .enumerate()
+            .map(|(id, (method, params))| {
+                json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "method": method,
+                    "params": params,
+                })
+            })
+            .collect();
+
+        let json = self.post(&Value::Array(body)).await?;
+        let Some(responses) = json.as_array() else {
+            // Rejected as a whole, e.g. batch requests unsupported
+            return Err(RpcError::Rpc(json));
+        };
+
+        let mut results: Vec<Result<Value, RpcError>> = (0..calls.len())
+            .map(|_| Err(RpcError::Rpc(json!("missing from batch response"))))
+            .collect();
+        // Responses may come in any order; match them up by id
+        for response in responses {
+            if let Some(slot) = response
+                .get("id")
+                .and_then(|id| id.as_u64())
+                .and_then(|id| results.get_mut(id as usize))
+            {
+                *slot = parse_response(response);
+            }
+        }
+        Ok(results)
+    }
+
+    async fn post(&self, body: &Value) -> Result<Value, RpcError> {
         let resp = self
             .client
             .post(&self.url)
             .header("Authorization", format!("Basic {}", self.auth))
             .header("Content-Type", "application/json")
-            .json(&body)
+            .json(body)
             .send()
             .await
             .map_err(RpcError::Http)?;
 
-        let json: Value = resp.json().await.map_err(RpcError::Http)?;
+        resp.json().await.map_err(RpcError::Http)
+    }
 
-        if let Some(err) = json.get("error").and_then(|e| {
-            if e.is_null() {
-                None
-            } else {
-                Some(e.clone())
-            }
-        }) {
-            return Err(RpcError::Rpc(err));
-        }
+    /// Batch of verbose `getrawtransaction` calls, one per txid.
+    pub async fn getrawtransactions(&self, txids: &[String]) -> Result<Vec<Result<Value, RpcError>>, RpcError> {
+        let calls: Vec<(&str, Vec<Value>)> = txids
+            .iter()
+            .map(|txid| ("getrawtransaction", vec![json!(txid), json!(true)]))
+            .collect();
+        self.call_batch(&calls).await
+    }
 
-        Ok(json["result"].clone())
+    /// Batch of `getmempoolentry` calls, one per txid.
+    pub async fn getmempoolentries(&self, txids: &[String]) -> Result<Vec<Result<Value, RpcError>>, RpcError> {
+        let calls: Vec<(&str, Vec<Value>)> = txids.iter().map(|txid| ("getmempoolentry", vec![json!(txid)])).collect();
+        self.call_batch(&calls).await
     }
 
-    /// Get raw transaction with optional verbosity.
-    pub async fn getrawtransaction(
-        &self,
-        txid: &str,
-        verbose: bool,
-    ) -> Result<Value, RpcError> {
+    /// Times of the blocks at `heights`, via batched `getblockhash` + `getblockheader`.
+    /// Heights the node can't answer for are left out.
+    pub async fn block_times(&self, heights: &[u32]) -> Result<HashMap<u32, i64>, RpcError> {
+        let calls: Vec<(&str, Vec<Value>)> = heights.iter().map(|h| ("getblockhash", vec![json!(h)])).collect();
+        let hashes: Vec<(u32, String)> = heights
+            .iter()
+            .zip(self.call_batch(&calls).await?)
+            .filter_map(|(&height, r)| Some((height, r.ok()?.as_str()?.to_string())))
+            .collect();
+        let calls: Vec<(&str, Vec<Value>)> = hashes.iter().map(|(_, hash)| ("getblockheader", vec![json!(hash)])).collect();
+        let headers = self.call_batch(&calls).await?;
+        Ok(hashes
+            .iter()
+            .zip(headers)
+            .filter_map(|((height, _), header)| Some((*height, header.ok()?.get("time")?.as_i64()?)))
+            .collect())
+    }
+
+    /// Get the txids currently in the node's mempool.
+    ///
+    /// With `mempool_sequence` the result is an object
+    /// `{ "txids": [...], "mempool_sequence": n }` instead of a plain array.
+    pub async fn getrawmempool(&self, mempool_sequence: bool) -> Result<Value, RpcError> {
         self.call(
-            "getrawtransaction",
-            vec![json!(txid), json!(verbose)],
+            "getrawmempool",
+            vec![json!(false), json!(mempool_sequence)],
         )
         .await
     }
 
+    /// Get a block by hash. Verbosity 0 = raw hex, 1 = txids, 2 = full tx objects.
+    pub async fn getblock(&self, block_hash: &str, verbosity: u8) -> Result<Value, RpcError> {
+        self.call("getblock", vec![json!(block_hash), json!(verbosity)])
+            .await
+    }
+
     /// Get mempool info (size, bytes, usage, fees).
     #[allow(dead_code)]
     pub async fn getmempoolinfo(&self) -> Result<Value, RpcError> {
@@ -126,12 +204,19 @@ impl BitcoinRpc {
     }
 
     /// Get blockchain info (chain, blocks, headers, etc.).
-    #[allow(dead_code)]
     pub async fn getblockchaininfo(&self) -> Result<Value, RpcError> {
         self.call("getblockchaininfo", vec![]).await
     }
 }
 
+/// Extract the result of a single JSON-RPC response object.
+fn parse_response(json: &Value) -> Result<Value, RpcError> {
+    if let Some(err) = json.get("error").filter(|e| !e.is_null()) {
+        return Err(RpcError::Rpc(err.clone()));
+    }
+    Ok(json["result"].clone())
+}
+
 fn dirs_cookie_path() -> PathBuf {
     #[cfg(target_os = "macos")]
     {
@@ -184,3 +269,108 @@ impl std::fmt::Display for RpcError {
 }
 
 impl std::error::Error for RpcError {}
+
+/// A minimal HTTP JSON-RPC endpoint for tests.
+#[cfg(test)]
+pub mod test_server {
+    use super::BitcoinRpc;
+    use serde_json::Value;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve `handler(request body)` as the response to every request, after `delay`.
+    pub async fn spawn<F>(delay: Duration, handler: F) -> BitcoinRpc
+    where
+        F: Fn(Value) -> Value + Send + Sync + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handler = Arc::new(handler);
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let handler = handler.clone();
+                tokio::spawn(async move {
+                    let mut buf = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    let body = loop {
+                        let n = stream.read(&mut chunk).await.unwrap_or(0);
+                        if n == 0 {
+                            return;
+                        }
+                        buf.extend_from_slice(&chunk[..n]);
+                        let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") else {
+                            continue;
+                        };
+                        let headers = String::from_utf8_lossy(&buf[..end]).to_ascii_lowercase();
+                        let len: usize = headers
+                            .lines()
+                            .find_map(|l| l.strip_prefix("content-length:"))
+                            .and_then(|v| v.trim().parse().ok())
+                            .unwrap_or(0);
+                        if buf.len() >= end + 4 + len {
+                            break buf[end + 4..end + 4 + len].to_vec();
+                        }
+                    };
+                    let request: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+                    tokio::time::sleep(delay).await;
+                    let response = handler(request).to_string();
+                    let head = format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        response.len()
+                    );
+                    let _ = stream.write_all(head.as_bytes()).await;
+                    let _ = stream.write_all(response.as_bytes()).await;
+                });
+            }
+        });
+        BitcoinRpc::new("127.0.0.1", port, "user", "pass")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn batch_results_in_call_order() {
+        // Answers in reverse order, failing the second call
+        let rpc = test_server::spawn(Duration::ZERO, |request| {
+            let responses: Vec<Value> = request
+                .as_array()
+                .unwrap()
+                .iter()
+                .rev()
+                .map(|call| {
+                    let id = call["id"].clone();
+                    if id == 1 {
+                        json!({"id": id, "result": null, "error": {"code": -5, "message": "No such tx"}})
+                    } else {
+                        json!({"id": id, "result": call["params"][0], "error": null})
+                    }
+                })
+                .collect();
+            Value::Array(responses)
+        })
+        .await;
+
+        let results = rpc
+            .getrawtransactions(&["aa".to_string(), "bb".to_string(), "cc".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap(), &json!("aa"));
+        assert!(matches!(&results[1], Err(RpcError::Rpc(e)) if e["code"] == -5));
+        assert_eq!(results[2].as_ref().unwrap(), &json!("cc"));
+
+        assert!(rpc.call_batch(&[]).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn batch_rejected_as_a_whole() {
+        let rpc = test_server::spawn(Duration::ZERO, |_| json!({"id": null, "error": {"code": -32700}})).await;
+        assert!(rpc.getrawtransactions(&["aa".to_string()]).await.is_err());
+    }
+}
@@ -1,8 +1,15 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
 use tokio::sync::mpsc;
 use tracing::{error, info, warn};
 
-use crate::core::MempoolEvent;
-use crate::core::RemovalReason;
+use crate::core::tx::parse_raw_tx;
+use crate::core::{MempoolEvent, RemovalReason, ResyncReason};
+use crate::source::{EventSource, SourceHandle};
+use crate::source::capture::FLAG_SEQUENCE;
+use crate::source::record::CaptureRecorder;
 
 /// ZMQ subscriber configuration.
 pub struct ZmqConfig {
@@ -22,17 +29,33 @@ impl Default for ZmqConfig {
     }
 }
 
+/// Minimum time between two resync requests; gaps within the window are coalesced.
+const RESYNC_MIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a `rawtx` body waits for its sequence 'A' announcement (and vice versa)
+/// before it is classified as a block-only transaction.
+const CLASSIFY_WINDOW: Duration = Duration::from_secs(1);
+
+/// In-process endpoint for the socket monitor used to detect reconnects.
+const MONITOR_ENDPOINT: &str = "inproc://txradar-zmq-monitor";
+
 /// Parse a ZMQ sequence message.
-/// Body: 32-byte hash + 1-byte label ('A'/'R'/'C'/'D').
-/// Sequence number comes as a separate ZMQ frame (4 bytes LE u32).
-fn parse_sequence_body(body: &[u8]) -> Option<([u8; 32], u8)> {
-    if body.len() != 33 {
+/// Body: 32-byte hash + 1-byte label ('A'/'R'/'C'/'D'), followed by an 8-byte LE
+/// mempool sequence number for 'A' and 'R'.
+/// The ZMQ message sequence number comes as a separate frame (4 bytes LE u32).
+fn parse_sequence_body(body: &[u8]) -> Option<([u8; 32], u8, Option<u64>)> {
+    if body.len() != 33 && body.len() != 41 {
         return None;
     }
     let mut hash = [0u8; 32];
     hash.copy_from_slice(&body[..32]);
     let label = body[32];
-    Some((hash, label))
+    let mempool_sequence = if body.len() == 41 {
+        Some(u64::from_le_bytes(body[33..41].try_into().ok()?))
+    } else {
+        None
+    };
+    Some((hash, label, mempool_sequence))
 }
 
 /// Parse the sequence number from the ZMQ sequence frame (4 bytes LE u32).
@@ -46,208 +69,533 @@ fn parse_sequence_number(frame: &[u8]) -> Option<u64> {
     }
 }
 
-/// Start ZMQ subscriber in a blocking thread (zmq crate is synchronous).
-/// Sends MempoolEvents into the provided channel.
+/// Tracks the per-topic ZMQ message sequence number for missed-event detection.
+#[derive(Debug, Default)]
+struct SequenceTracker {
+    last: Option<u64>,
+}
+
+impl SequenceTracker {
+    /// Record a sequence number. Returns `(expected, got)` if messages were missed.
+    fn observe(&mut self, seq: u64) -> Option<(u64, u64)> {
+        let gap = match self.last {
+            Some(prev) if seq != prev.wrapping_add(1) => Some((prev.wrapping_add(1), seq)),
+            _ => None,
+        };
+        self.last = Some(seq);
+        gap
+    }
+}
+
+/// Compute the txid (display byte order) of a raw transaction.
+fn raw_txid(raw: &[u8]) -> Option<[u8; 32]> {
+    use bitcoin::hashes::Hash;
+    let parsed = parse_raw_tx(raw).ok()?;
+    let mut txid = parsed.compute_txid().to_byte_array();
+    txid.reverse();
+    Some(txid)
+}
+
+/// Tells mempool arrivals apart from block replays on the `rawtx` topic.
 ///
-/// Strategy: `rawtx` for TxAdded (has full tx data inline),
-/// `sequence` for TxRemoved + Block events only.
-pub fn start_zmq_subscriber(
-    config: ZmqConfig,
-    tx: mpsc::UnboundedSender<MempoolEvent>,
-) -> std::thread::JoinHandle<()> {
-    std::thread::spawn(move || {
-        let ctx = zmq::Context::new();
-
-        // Subscribe to rawtx
-        let rawtx_sock = ctx.socket(zmq::SUB).expect("failed to create rawtx socket");
-        rawtx_sock
-            .connect(&config.rawtx_endpoint)
-            .unwrap_or_else(|e| panic!("failed to connect rawtx at {}: {e}", config.rawtx_endpoint));
-        rawtx_sock.set_subscribe(b"rawtx").expect("subscribe rawtx");
-        info!(endpoint = %config.rawtx_endpoint, "ZMQ rawtx subscriber connected");
-
-        // Subscribe to hashblock
-        let hashblock_sock = ctx.socket(zmq::SUB).expect("failed to create hashblock socket");
-        hashblock_sock
-            .connect(&config.hashblock_endpoint)
-            .unwrap_or_else(|e| panic!("failed to connect hashblock at {}: {e}", config.hashblock_endpoint));
-        hashblock_sock.set_subscribe(b"hashblock").expect("subscribe hashblock");
-        info!(endpoint = %config.hashblock_endpoint, "ZMQ hashblock subscriber connected");
-
-        // Optionally subscribe to sequence
-        let sequence_sock = config.sequence_endpoint.as_ref().and_then(|endpoint| {
-            let sock = match ctx.socket(zmq::SUB) {
-                Ok(s) => s,
-                Err(e) => {
-                    warn!("Failed to create sequence socket: {e}, continuing without sequence topic");
-                    return None;
+/// Bitcoin Core publishes `rawtx` both when a tx enters the mempool and for every tx of
+/// a newly connected block, but sequence 'A' only for mempool acceptance. A `rawtx` body
+/// is a mempool arrival once its 'A' shows up; bodies still unmatched after
+/// `CLASSIFY_WINDOW` were only seen in a block. The two topics arrive on separate
+/// sockets, so either side may come first.
+#[derive(Debug, Default)]
+struct ArrivalClassifier {
+    /// `rawtx` bodies waiting for their 'A' announcement.
+    unannounced: HashMap<[u8; 32], (Vec<u8>, Instant)>,
+    /// 'A' announcements (with mempool sequence) waiting for their `rawtx` body.
+    announced: HashMap<[u8; 32], (Option<u64>, Instant)>,
+}
+
+impl ArrivalClassifier {
+    fn on_rawtx(&mut self, txid: [u8; 32], raw: Vec<u8>, now: Instant) -> Option<MempoolEvent> {
+        match self.announced.remove(&txid) {
+            Some((mempool_sequence, _)) => Some(MempoolEvent::TxAdded { txid, raw, mempool_sequence }),
+            None => {
+                self.unannounced.insert(txid, (raw, now));
+                None
+            }
+        }
+    }
+
+    fn on_accepted(&mut self, txid: [u8; 32], mempool_sequence: Option<u64>, now: Instant) -> Option<MempoolEvent> {
+        match self.unannounced.remove(&txid) {
+            Some((raw, _)) => Some(MempoolEvent::TxAdded { txid, raw, mempool_sequence }),
+            None => {
+                self.announced.insert(txid, (mempool_sequence, now));
+                None
+            }
+        }
+    }
+
+    /// Classify everything older than the window: unannounced bodies become
+    /// `TxSeenInBlock`, announcements whose body never arrived are dropped.
+    fn expire(&mut self, now: Instant) -> Vec<MempoolEvent> {
+        let expired: Vec<[u8; 32]> = self
+            .unannounced
+            .iter()
+            .filter(|(_, (_, at))| now.duration_since(*at) >= CLASSIFY_WINDOW)
+            .map(|(txid, _)| *txid)
+            .collect();
+        let events = expired
+            .into_iter()
+            .filter_map(|txid| {
+                let (raw, _) = self.unannounced.remove(&txid)?;
+                Some(MempoolEvent::TxSeenInBlock { txid, raw })
+            })
+            .collect();
+        self.announced
+            .retain(|_, (_, at)| now.duration_since(*at) < CLASSIFY_WINDOW);
+        events
+    }
+}
+
+/// ZMQ topics we subscribe to.
+pub const TOPIC_RAWTX: &[u8] = b"rawtx";
+pub const TOPIC_HASHBLOCK: &[u8] = b"hashblock";
+pub const TOPIC_SEQUENCE: &[u8] = b"sequence";
+
+/// Turns raw multipart ZMQ messages (`[topic, body, sequence]`) into `MempoolEvent`s.
+///
+/// Strategy: `rawtx` for the full tx data, paired with `sequence` 'A' events to emit
+/// TxAdded for mempool arrivals and TxSeenInBlock for block replays (see
+/// `ArrivalClassifier`); `sequence` for TxRemoved + Block events. Without the sequence
+/// topic every `rawtx` is reported as TxAdded.
+/// Sequence gaps and reconnects are reported as (rate-limited) `ResyncRequired`.
+///
+/// All timing decisions use the `now` passed in, so a capture replay can drive the
+/// decoder with recorded receive times.
+#[derive(Debug)]
+pub struct ZmqDecoder {
+    with_sequence: bool,
+    rawtx_seq: SequenceTracker,
+    sequence_seq: SequenceTracker,
+    classifier: ArrivalClassifier,
+    /// Coalesce bursts of gaps into a single resync request
+    pending_resync: Option<ResyncReason>,
+    last_resync: Option<Instant>,
+}
+
+impl ZmqDecoder {
+    /// `with_sequence`: whether the `sequence` topic is subscribed.
+    pub fn new(with_sequence: bool) -> Self {
+        Self {
+            with_sequence,
+            rawtx_seq: SequenceTracker::default(),
+            sequence_seq: SequenceTracker::default(),
+            classifier: ArrivalClassifier::default(),
+            pending_resync: None,
+            last_resync: None,
+        }
+    }
+
+    /// Decode one multipart message received at `now`, appending resulting events.
+    pub fn on_message(&mut self, frames: &[Vec<u8>], now: Instant, out: &mut Vec<MempoolEvent>) {
+        let (Some(topic), Some(body)) = (frames.first(), frames.get(1)) else {
+            warn!("Unexpected ZMQ message format, parts: {}", frames.len());
+            return;
+        };
+        let seq = frames.get(2).and_then(|f| parse_sequence_number(f));
+        match topic.as_slice() {
+            TOPIC_RAWTX => self.on_rawtx(body, seq, now, out),
+            TOPIC_HASHBLOCK => {
+                if let Ok(block_hash) = <[u8; 32]>::try_from(body.as_slice()) {
+                    out.push(MempoolEvent::BlockConnected { block_hash, height: 0 });
                 }
-            };
-            if let Err(e) = sock.connect(endpoint) {
-                warn!("Failed to connect sequence at {endpoint}: {e}, continuing without sequence topic");
-                return None;
             }
-            if let Err(e) = sock.set_subscribe(b"sequence") {
-                warn!("Failed to subscribe to sequence topic: {e}");
+            TOPIC_SEQUENCE => self.on_sequence(body, seq, now, out),
+            other => warn!("Unexpected ZMQ topic: {}", String::from_utf8_lossy(other)),
+        }
+    }
+
+    fn on_rawtx(&mut self, body: &[u8], seq: Option<u64>, now: Instant, out: &mut Vec<MempoolEvent>) {
+        if let Some(s) = seq
+            && let Some((expected, got)) = self.rawtx_seq.observe(s)
+        {
+            warn!("ZMQ rawtx gap detected: expected {expected}, got {got}");
+            self.pending_resync.get_or_insert(ResyncReason::SequenceGap {
+                topic: "rawtx",
+                expected,
+                got,
+            });
+        }
+
+        let Some(txid) = raw_txid(body) else {
+            warn!("Failed to parse rawtx body ({} bytes)", body.len());
+            return;
+        };
+        let event = if self.with_sequence {
+            self.classifier.on_rawtx(txid, body.to_vec(), now)
+        } else {
+            Some(MempoolEvent::TxAdded { txid, raw: body.to_vec(), mempool_sequence: None })
+        };
+        out.extend(event);
+    }
+
+    fn on_sequence(&mut self, body: &[u8], seq: Option<u64>, now: Instant, out: &mut Vec<MempoolEvent>) {
+        let Some((hash, label, mempool_sequence)) = parse_sequence_body(body) else {
+            warn!("Invalid sequence message body length: {}", body.len());
+            return;
+        };
+        // Missed-event detection
+        if let Some(s) = seq
+            && let Some((expected, got)) = self.sequence_seq.observe(s)
+        {
+            warn!(
+                "ZMQ sequence gap detected: expected {}, got {} (missed {} events)",
+                expected, got, got.saturating_sub(expected)
+            );
+            self.pending_resync.get_or_insert(ResyncReason::SequenceGap {
+                topic: "sequence",
+                expected,
+                got,
+            });
+        }
+
+        match label {
+            // Mempool acceptance: pairs with the rawtx body
+            b'A' => out.extend(self.classifier.on_accepted(hash, mempool_sequence, now)),
+            b'R' => out.push(MempoolEvent::TxRemoved {
+                txid: hash,
+                reason: RemovalReason::Unknown,
+                mempool_sequence,
+            }),
+            b'C' => out.push(MempoolEvent::BlockConnected { block_hash: hash, height: 0 }),
+            b'D' => out.push(MempoolEvent::BlockDisconnected { block_hash: hash, height: 0 }),
+            other => warn!("Unknown sequence label: 0x{other:02x}"),
+        }
+    }
+
+    /// The connection dropped and came back; anything published meanwhile is lost.
+    pub fn on_reconnect(&mut self) {
+        self.pending_resync.get_or_insert(ResyncReason::Reconnected);
+    }
+
+    /// Periodic housekeeping: classify expired `rawtx` bodies and emit a due resync.
+    pub fn tick(&mut self, now: Instant, out: &mut Vec<MempoolEvent>) {
+        // Unmatched rawtx bodies past the window were block replays
+        out.extend(self.classifier.expire(now));
+
+        if let Some(reason) = self.pending_resync {
+            let due = self
+                .last_resync
+                .is_none_or(|t| now.saturating_duration_since(t) >= RESYNC_MIN_INTERVAL);
+            if due {
+                self.pending_resync = None;
+                self.last_resync = Some(now);
+                out.push(MempoolEvent::ResyncRequired { reason });
+            }
+        }
+    }
+
+    /// End of stream: classify everything still waiting as if its window had passed.
+    pub fn finish(&mut self, now: Instant, out: &mut Vec<MempoolEvent>) {
+        self.tick(now + CLASSIFY_WINDOW, out);
+    }
+}
+
+/// Parse a socket monitor event frame (2 bytes event id + 4 bytes value).
+fn parse_monitor_event(frame: &[u8]) -> Option<zmq::SocketEvent> {
+    if frame.len() != 6 {
+        return None;
+    }
+    let raw = u16::from_ne_bytes([frame[0], frame[1]]);
+    Some(zmq::SocketEvent::from_raw(raw))
+}
+
+/// Live events from a Bitcoin Core node's ZMQ publishers.
+pub struct ZmqSource {
+    config: ZmqConfig,
+    recorder: Option<CaptureRecorder>,
+}
+
+impl ZmqSource {
+    pub fn new(config: ZmqConfig) -> Self {
+        Self { config, recorder: None }
+    }
+
+    /// Also write every raw message to capture files (`--record`).
+    pub fn with_recorder(mut self, recorder: CaptureRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+}
+
+impl EventSource for ZmqSource {
+    fn describe(&self) -> String {
+        match &self.recorder {
+            Some(recorder) => format!("ZMQ {}, recording to {}", self.config.rawtx_endpoint, recorder.describe()),
+            None => format!("ZMQ {}", self.config.rawtx_endpoint),
+        }
+    }
+
+    fn start(self: Box<Self>, tx: mpsc::UnboundedSender<MempoolEvent>) -> SourceHandle {
+        SourceHandle::spawn(move |stop| run_zmq_subscriber(self.config, self.recorder, tx, &stop))
+    }
+}
+
+/// Run the ZMQ subscriber on the current thread (zmq crate is synchronous) until `stop`
+/// is set or the channel closes.
+/// Decodes messages with `ZmqDecoder` and sends the MempoolEvents into the provided channel,
+/// recording the raw messages first if a recorder is given.
+pub fn run_zmq_subscriber(
+    config: ZmqConfig,
+    mut recorder: Option<CaptureRecorder>,
+    tx: mpsc::UnboundedSender<MempoolEvent>,
+    stop: &AtomicBool,
+) {
+    let ctx = zmq::Context::new();
+
+    // Subscribe to rawtx
+    let rawtx_sock = ctx.socket(zmq::SUB).expect("failed to create rawtx socket");
+    rawtx_sock
+        .connect(&config.rawtx_endpoint)
+        .unwrap_or_else(|e| panic!("failed to connect rawtx at {}: {e}", config.rawtx_endpoint));
+    rawtx_sock.set_subscribe(TOPIC_RAWTX).expect("subscribe rawtx");
+    info!(endpoint = %config.rawtx_endpoint, "ZMQ rawtx subscriber connected");
+
+    // Subscribe to hashblock
+    let hashblock_sock = ctx.socket(zmq::SUB).expect("failed to create hashblock socket");
+    hashblock_sock
+        .connect(&config.hashblock_endpoint)
+        .unwrap_or_else(|e| panic!("failed to connect hashblock at {}: {e}", config.hashblock_endpoint));
+    hashblock_sock.set_subscribe(TOPIC_HASHBLOCK).expect("subscribe hashblock");
+    info!(endpoint = %config.hashblock_endpoint, "ZMQ hashblock subscriber connected");
+
+    // Optionally subscribe to sequence
+    let sequence_sock = config.sequence_endpoint.as_ref().and_then(|endpoint| {
+        let sock = match ctx.socket(zmq::SUB) {
+            Ok(s) => s,
+            Err(e) => {
+                warn!("Failed to create sequence socket: {e}, continuing without sequence topic");
                 return None;
             }
-            info!(endpoint = %endpoint, "ZMQ sequence subscriber connected");
-            Some(sock)
-        });
-
-        // Track last sequence number for missed-event detection
-        let mut last_seq: Option<u64> = None;
-
-        loop {
-            // Build poll items dynamically based on whether sequence socket exists
-            let poll_result = if let Some(ref seq_sock) = sequence_sock {
-                let mut items = [
-                    rawtx_sock.as_poll_item(zmq::POLLIN),
-                    hashblock_sock.as_poll_item(zmq::POLLIN),
-                    seq_sock.as_poll_item(zmq::POLLIN),
-                ];
-                let res = zmq::poll(&mut items, 1000);
-                res.map(|_| (items[0].is_readable(), items[1].is_readable(), items[2].is_readable()))
-            } else {
-                let mut items = [
-                    rawtx_sock.as_poll_item(zmq::POLLIN),
-                    hashblock_sock.as_poll_item(zmq::POLLIN),
-                ];
-                let res = zmq::poll(&mut items, 1000);
-                res.map(|_| (items[0].is_readable(), items[1].is_readable(), false))
-            };
+        };
+        if let Err(e) = sock.connect(endpoint) {
+            warn!("Failed to connect sequence at {endpoint}: {e}, continuing without sequence topic");
+            return None;
+        }
+        if let Err(e) = sock.set_subscribe(TOPIC_SEQUENCE) {
+            warn!("Failed to subscribe to sequence topic: {e}");
+            return None;
+        }
+        info!(endpoint = %endpoint, "ZMQ sequence subscriber connected");
+        Some(sock)
+    });
 
-            let (rawtx_ready, hashblock_ready, sequence_ready) = match poll_result {
-                Ok(flags) => flags,
-                Err(e) => {
-                    error!("ZMQ poll error: {e}");
-                    std::thread::sleep(std::time::Duration::from_secs(1));
-                    continue;
-                }
-            };
+    // Monitor the rawtx socket for reconnects: anything published while the
+    // connection was down is lost, so the pipeline has to resync afterwards.
+    let monitor_sock = rawtx_sock
+        .monitor(
+            MONITOR_ENDPOINT,
+            (zmq::SocketEvent::CONNECTED as i32) | (zmq::SocketEvent::DISCONNECTED as i32),
+        )
+        .and_then(|_| {
+            let sock = ctx.socket(zmq::PAIR)?;
+            sock.connect(MONITOR_ENDPOINT)?;
+            Ok(sock)
+        })
+        .map_err(|e| warn!("Failed to set up ZMQ socket monitor: {e}, reconnects won't trigger resync"))
+        .ok();
 
-            // Check rawtx
-            if rawtx_ready {
-                match rawtx_sock.recv_multipart(zmq::DONTWAIT) {
-                    Ok(msg) if msg.len() >= 2 && msg[0] == b"rawtx" => {
-                        let body = &msg[1];
-                        use bitcoin::hashes::{sha256d, Hash};
-                        let txid_hash = sha256d::Hash::hash(body);
-                        let mut txid = [0u8; 32];
-                        txid.copy_from_slice(txid_hash.as_ref());
-
-                        if tx.send(MempoolEvent::TxAdded { txid, raw: body.to_vec() }).is_err() {
-                            info!("Channel closed, stopping ZMQ subscriber");
-                            return;
-                        }
-                    }
-                    Ok(msg) => {
-                        warn!("Unexpected rawtx message format, parts: {}", msg.len());
-                    }
-                    Err(e) => {
-                        if e != zmq::Error::EAGAIN {
-                            error!("ZMQ rawtx recv error: {e}");
-                        }
-                    }
+    let mut decoder = ZmqDecoder::new(sequence_sock.is_some());
+    if let Some(ref mut recorder) = recorder {
+        recorder.set_flags(if sequence_sock.is_some() { FLAG_SEQUENCE } else { 0 });
+    }
+    let mut was_disconnected = false;
+    let mut events = Vec::new();
+
+    loop {
+        // Returning drops the recorder, which flushes and closes the current capture
+        if stop.load(Ordering::Relaxed) {
+            info!("Stopping ZMQ subscriber");
+            return;
+        }
+
+        // Build poll items dynamically based on which sockets exist
+        let mut items = vec![
+            rawtx_sock.as_poll_item(zmq::POLLIN),
+            hashblock_sock.as_poll_item(zmq::POLLIN),
+        ];
+        if let Some(ref seq_sock) = sequence_sock {
+            items.push(seq_sock.as_poll_item(zmq::POLLIN));
+        }
+        let monitor_idx = items.len();
+        if let Some(ref mon_sock) = monitor_sock {
+            items.push(mon_sock.as_poll_item(zmq::POLLIN));
+        }
+
+        if let Err(e) = zmq::poll(&mut items, 250) {
+            error!("ZMQ poll error: {e}");
+            std::thread::sleep(Duration::from_secs(1));
+            continue;
+        }
+
+        let rawtx_ready = items[0].is_readable();
+        let hashblock_ready = items[1].is_readable();
+        let sequence_ready = sequence_sock.is_some() && items[2].is_readable();
+        let monitor_ready = monitor_sock.is_some() && items[monitor_idx].is_readable();
+        drop(items);
+
+        // Check socket monitor
+        if monitor_ready
+            && let Some(ref mon_sock) = monitor_sock
+            && let Ok(msg) = mon_sock.recv_multipart(zmq::DONTWAIT)
+        {
+            match msg.first().and_then(|f| parse_monitor_event(f)) {
+                Some(zmq::SocketEvent::DISCONNECTED) => {
+                    warn!("ZMQ rawtx socket disconnected");
+                    was_disconnected = true;
                 }
+                Some(zmq::SocketEvent::CONNECTED) if was_disconnected => {
+                    info!("ZMQ rawtx socket reconnected");
+                    was_disconnected = false;
+                    decoder.on_reconnect();
+                }
+                _ => {}
             }
+        }
 
-            // Check hashblock
-            if hashblock_ready {
-                match hashblock_sock.recv_multipart(zmq::DONTWAIT) {
-                    Ok(msg) if msg.len() >= 2 && msg[0] == b"hashblock" => {
-                        let body = &msg[1];
-                        if body.len() == 32 {
-                            let mut block_hash = [0u8; 32];
-                            block_hash.copy_from_slice(body);
-                            if tx.send(MempoolEvent::BlockConnected { block_hash, height: 0 }).is_err() {
-                                info!("Channel closed, stopping ZMQ subscriber");
-                                return;
-                            }
-                        }
-                    }
-                    Ok(_) => {}
-                    Err(e) => {
-                        if e != zmq::Error::EAGAIN {
-                            error!("ZMQ hashblock recv error: {e}");
-                        }
+        let ready = [
+            (rawtx_ready, Some(&rawtx_sock), "rawtx"),
+            (hashblock_ready, Some(&hashblock_sock), "hashblock"),
+            (sequence_ready, sequence_sock.as_ref(), "sequence"),
+        ];
+        for (is_ready, sock, name) in ready {
+            let (true, Some(sock)) = (is_ready, sock) else {
+                continue;
+            };
+            match sock.recv_multipart(zmq::DONTWAIT) {
+                Ok(msg) => {
+                    let now = Instant::now();
+                    if let Some(ref mut recorder) = recorder {
+                        recorder.record(&msg, now);
                     }
+                    decoder.on_message(&msg, now, &mut events);
                 }
+                Err(zmq::Error::EAGAIN) => {}
+                Err(e) => error!("ZMQ {name} recv error: {e}"),
             }
+        }
 
-            // Check sequence
-            if sequence_ready {
-                if let Some(ref seq_sock) = sequence_sock {
-                    match seq_sock.recv_multipart(zmq::DONTWAIT) {
-                        Ok(msg) if msg.len() >= 2 && msg[0] == b"sequence" => {
-                            let body = &msg[1];
-                            if let Some((hash, label)) = parse_sequence_body(body) {
-                                // Parse sequence number from frame 2 (if present)
-                                let seq = msg.get(2).and_then(|f| parse_sequence_number(f));
-                                if let Some(s) = seq {
-                                    // Missed-event detection
-                                    if let Some(prev) = last_seq {
-                                        if s != prev + 1 {
-                                            warn!(
-                                                "ZMQ sequence gap detected: expected {}, got {} (missed {} events)",
-                                                prev + 1, s, s.saturating_sub(prev + 1)
-                                            );
-                                        }
-                                    }
-                                    last_seq = Some(s);
-                                }
-
-                                match label {
-                                    b'A' => {
-                                        // TxAdded from sequence — we ignore this since rawtx
-                                        // provides the full tx data. No action needed.
-                                    }
-                                    b'R' => {
-                                        if tx.send(MempoolEvent::TxRemoved {
-                                            txid: hash,
-                                            reason: RemovalReason::Unknown,
-                                        }).is_err() {
-                                            info!("Channel closed, stopping ZMQ subscriber");
-                                            return;
-                                        }
-                                    }
-                                    b'C' => {
-                                        if tx.send(MempoolEvent::BlockConnected {
-                                            block_hash: hash,
-                                            height: 0,
-                                        }).is_err() {
-                                            info!("Channel closed, stopping ZMQ subscriber");
-                                            return;
-                                        }
-                                    }
-                                    b'D' => {
-                                        if tx.send(MempoolEvent::BlockDisconnected {
-                                            block_hash: hash,
-                                            height: 0,
-                                        }).is_err() {
-                                            info!("Channel closed, stopping ZMQ subscriber");
-                                            return;
-                                        }
-                                    }
-                                    other => {
-                                        warn!("Unknown sequence label: 0x{other:02x}");
-                                    }
-                                }
-                            } else {
-                                warn!("Invalid sequence message body length: {}", body.len());
-                            }
-                        }
-                        Ok(msg) => {
-                            warn!("Unexpected sequence message format, parts: {}", msg.len());
-                        }
-                        Err(e) => {
-                            if e != zmq::Error::EAGAIN {
-                                error!("ZMQ sequence recv error: {e}");
-                            }
-                        }
-                    }
-                }
+        let now = Instant::now();
+        decoder.tick(now, &mut events);
+        if let Some(ref mut recorder) = recorder {
+            recorder.tick(now);
+        }
+        for event in events.drain(..) {
+            if tx.send(event).is_err() {
+                info!("Channel closed, stopping ZMQ subscriber");
+                return;
             }
         }
-    })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_block_sequence_body() {
+        let mut body = vec![0x11u8; 32];
+        body.push(b'C');
+        let (hash, label, mempool_seq) = parse_sequence_body(&body).unwrap();
+        assert_eq!(hash, [0x11u8; 32]);
+        assert_eq!(label, b'C');
+        assert_eq!(mempool_seq, None);
+    }
+
+    #[test]
+    fn parse_mempool_sequence_body() {
+        let mut body = vec![0x22u8; 32];
+        body.push(b'R');
+        body.extend_from_slice(&42u64.to_le_bytes());
+        let (_, label, mempool_seq) = parse_sequence_body(&body).unwrap();
+        assert_eq!(label, b'R');
+        assert_eq!(mempool_seq, Some(42));
+    }
+
+    #[test]
+    fn parse_sequence_body_rejects_bad_length() {
+        assert!(parse_sequence_body(&[0u8; 34]).is_none());
+        assert!(parse_sequence_body(&[]).is_none());
+    }
+
+    #[test]
+    fn sequence_tracker_detects_gap() {
+        let mut tracker = SequenceTracker::default();
+        assert_eq!(tracker.observe(5), None);
+        assert_eq!(tracker.observe(6), None);
+        assert_eq!(tracker.observe(9), Some((7, 9)));
+        assert_eq!(tracker.observe(10), None);
+    }
+
+    #[test]
+    fn sequence_tracker_detects_reset() {
+        let mut tracker = SequenceTracker::default();
+        tracker.observe(100);
+        // Node restart resets the counter
+        assert_eq!(tracker.observe(0), Some((101, 0)));
+    }
+
+    fn added_txid(event: &MempoolEvent) -> Option<([u8; 32], Option<u64>)> {
+        match event {
+            MempoolEvent::TxAdded { txid, mempool_sequence, .. } => Some((*txid, *mempool_sequence)),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn classifier_pairs_rawtx_then_announce() {
+        let mut c = ArrivalClassifier::default();
+        let now = Instant::now();
+        assert!(c.on_rawtx([1u8; 32], vec![0xaa], now).is_none());
+        let event = c.on_accepted([1u8; 32], Some(7), now).unwrap();
+        assert_eq!(added_txid(&event), Some(([1u8; 32], Some(7))));
+        assert!(c.expire(now + CLASSIFY_WINDOW).is_empty());
+    }
+
+    #[test]
+    fn classifier_pairs_announce_then_rawtx() {
+        let mut c = ArrivalClassifier::default();
+        let now = Instant::now();
+        assert!(c.on_accepted([2u8; 32], Some(9), now).is_none());
+        let event = c.on_rawtx([2u8; 32], vec![0xbb], now).unwrap();
+        assert_eq!(added_txid(&event), Some(([2u8; 32], Some(9))));
+    }
+
+    #[test]
+    fn classifier_unannounced_rawtx_is_block_only() {
+        let mut c = ArrivalClassifier::default();
+        let now = Instant::now();
+        assert!(c.on_rawtx([3u8; 32], vec![0xcc], now).is_none());
+        // Still inside the window
+        assert!(c.expire(now).is_empty());
+        let events = c.expire(now + CLASSIFY_WINDOW);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(events[0], MempoolEvent::TxSeenInBlock { txid, .. } if txid == [3u8; 32]));
+    }
+
+    #[test]
+    fn classifier_drops_stale_announcements() {
+        let mut c = ArrivalClassifier::default();
+        let now = Instant::now();
+        c.on_accepted([4u8; 32], Some(1), now);
+        assert!(c.expire(now + CLASSIFY_WINDOW).is_empty());
+        // A late body no longer pairs with the expired announcement
+        assert!(c.on_rawtx([4u8; 32], vec![0xdd], now + CLASSIFY_WINDOW).is_none());
+    }
+
+    #[test]
+    fn monitor_event_frame() {
+        let raw = (zmq::SocketEvent::DISCONNECTED as u16).to_ne_bytes();
+        let frame = [raw[0], raw[1], 0, 0, 0, 0];
+        assert_eq!(parse_monitor_event(&frame), Some(zmq::SocketEvent::DISCONNECTED));
+        assert_eq!(parse_monitor_event(&[0u8; 3]), None);
+    }
 }
@@ -0,0 +1,209 @@
+use bitcoin::{Address, Network, Transaction};
+
+use crate::core::tx::script_type;
+
+/// Outputs at or above this change probability don't count toward the economic value.
+pub const CHANGE_THRESHOLD: f64 = 0.7;
+
+/// Probability assigned to an output paying back to one of the tx's input addresses.
+const ADDRESS_REUSE_PROBABILITY: f64 = 0.95;
+
+/// Likelihood ratios the heuristics apply to the odds of an output being change.
+const SCRIPT_TYPE_MATCH: f64 = 3.0;
+const NON_ROUND_AMOUNT: f64 = 3.0;
+const SMALLER_THAN_INPUTS: f64 = 2.0;
+
+/// Amounts that are a multiple of this (0.0001 BTC) look like chosen payment amounts.
+const ROUND_AMOUNT_SATS: u64 = 10_000;
+
+/// What the spender side of a tx reveals, as input to change detection.
+#[derive(Debug, Clone, Default)]
+pub struct InputContext<'a> {
+    /// Script types of the spent outputs (Bitcoin Core's `scriptPubKey.type`).
+    pub script_types: &'a [String],
+    /// Addresses spent by the tx.
+    pub addresses: &'a [String],
+    /// Smallest input value, if every prevout was resolved.
+    pub smallest_value: Option<u64>,
+}
+
+/// Change probabilities of a tx's outputs.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChangeAnalysis {
+    /// Change probability (0.0-1.0) of each output, in output order.
+    pub probabilities: Vec<f64>,
+    /// Sum of the outputs below `CHANGE_THRESHOLD`, in satoshis.
+    pub economic_value: u64,
+}
+
+impl ChangeAnalysis {
+    /// Indexes of the outputs taken as change.
+    pub fn change_outputs(&self) -> impl Iterator<Item = usize> + '_ {
+        self.probabilities
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| **p >= CHANGE_THRESHOLD)
+            .map(|(i, _)| i)
+    }
+}
+
+/// Estimate which outputs of `tx` return change to the sender.
+///
+/// Starts from an even prior over the spendable outputs (a single output is a payment or a
+/// sweep, never change) and combines the usual wallet heuristics:
+/// - address reuse: paying back to an input address is almost certainly change
+/// - script type: change usually has the inputs' script type when the payment differs
+/// - round amounts: the non-round output beside round ones is likely change
+/// - optimal change: an output smaller than every input is likely change, or the wallet
+///   wouldn't have needed the smallest input
+///
+/// No IO; CoinJoins should be handled by the caller, the heuristics don't apply to them.
+pub fn detect_change(tx: &Transaction, inputs: &InputContext) -> ChangeAnalysis {
+    let spendable: Vec<usize> = (0..tx.output.len())
+        .filter(|&i| !tx.output[i].script_pubkey.is_op_return())
+        .collect();
+    let types: Vec<&str> = tx.output.iter().map(|o| script_type(&o.script_pubkey)).collect();
+    let mixed_types = spendable.iter().any(|&i| types[i] != types[spendable[0]]);
+    let single_input_type = match inputs.script_types.split_first() {
+        Some((first, rest)) if rest.iter().all(|t| t == first) => Some(first.as_str()),
+        _ => None,
+    };
+    let round = |i: usize| tx.output[i].value.to_sat().is_multiple_of(ROUND_AMOUNT_SATS);
+    let any_round = spendable.iter().any(|&i| round(i));
+
+    let mut probabilities = vec![0.0; tx.output.len()];
+    for &i in &spendable {
+        let output = &tx.output[i];
+        let reuses_input_address = Address::from_script(&output.script_pubkey, Network::Bitcoin)
+            .is_ok_and(|a| inputs.addresses.contains(&a.to_string()));
+        if reuses_input_address {
+            probabilities[i] = ADDRESS_REUSE_PROBABILITY;
+            continue;
+        }
+        if spendable.len() < 2 {
+            continue;
+        }
+
+        let mut odds = 1.0 / (spendable.len() - 1) as f64;
+        if mixed_types && let Some(input_type) = single_input_type {
+            odds *= if types[i] == input_type { SCRIPT_TYPE_MATCH } else { 1.0 / SCRIPT_TYPE_MATCH };
+        }
+        if any_round {
+            odds *= if round(i) { 1.0 / NON_ROUND_AMOUNT } else { NON_ROUND_AMOUNT };
+        }
+        if tx.input.len() > 1 && inputs.smallest_value.is_some_and(|min| output.value.to_sat() < min) {
+            odds *= SMALLER_THAN_INPUTS;
+        }
+        probabilities[i] = odds / (1.0 + odds);
+    }
+
+    let economic_value = tx
+        .output
+        .iter()
+        .zip(&probabilities)
+        .filter(|(_, p)| **p < CHANGE_THRESHOLD)
+        .map(|(o, _)| o.value.to_sat())
+        .sum();
+    ChangeAnalysis { probabilities, economic_value }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{Amount, OutPoint, ScriptBuf, TxIn, TxOut};
+
+    fn p2wpkh(n: u8) -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([n; 20]))
+    }
+
+    fn p2pkh(n: u8) -> ScriptBuf {
+        ScriptBuf::new_p2pkh(&bitcoin::PubkeyHash::from_byte_array([n; 20]))
+    }
+
+    fn make_tx(inputs: usize, outputs: &[(u64, ScriptBuf)]) -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: (0..inputs)
+                .map(|i| TxIn {
+                    previous_output: OutPoint::new(bitcoin::Txid::from_byte_array([i as u8; 32]), 0),
+                    ..Default::default()
+                })
+                .collect(),
+            output: outputs
+                .iter()
+                .map(|(sats, script)| TxOut { value: Amount::from_sat(*sats), script_pubkey: script.clone() })
+                .collect(),
+        }
+    }
+
+    fn types(t: &str) -> Vec<String> {
+        vec![t.to_string()]
+    }
+
+    #[test]
+    fn round_payment_leaves_change_out() {
+        // 500 BTC wallet paying 0.01 BTC
+        let tx = make_tx(1, &[(1_000_000, p2wpkh(1)), (49_998_990_000 + 1_234, p2wpkh(2))]);
+        let analysis = detect_change(&tx, &InputContext::default());
+        assert_eq!(analysis.change_outputs().collect::<Vec<_>>(), vec![1]);
+        assert_eq!(analysis.economic_value, 1_000_000);
+    }
+
+    #[test]
+    fn script_type_match() {
+        let tx = make_tx(1, &[(123_456, p2pkh(1)), (654_321, p2wpkh(2))]);
+        let input_types = types("witness_v0_keyhash");
+        let analysis = detect_change(&tx, &InputContext { script_types: &input_types, ..Default::default() });
+        assert!(analysis.probabilities[1] >= CHANGE_THRESHOLD);
+        assert!(analysis.probabilities[0] < 0.5);
+        assert_eq!(analysis.economic_value, 123_456);
+    }
+
+    #[test]
+    fn address_reuse_is_change() {
+        let script = p2wpkh(7);
+        let address = Address::from_script(&script, Network::Bitcoin).unwrap().to_string();
+        let tx = make_tx(1, &[(500_000_000, p2wpkh(1)), (123_456, script)]);
+        let addresses = vec![address];
+        let analysis = detect_change(&tx, &InputContext { addresses: &addresses, ..Default::default() });
+        assert_eq!(analysis.probabilities[1], ADDRESS_REUSE_PROBABILITY);
+        assert_eq!(analysis.economic_value, 500_000_000);
+    }
+
+    #[test]
+    fn optimal_change_needs_other_evidence() {
+        let tx = make_tx(2, &[(3_333_333, p2wpkh(1)), (777_777, p2wpkh(2))]);
+        let alone = detect_change(&tx, &InputContext { smallest_value: Some(1_000_000), ..Default::default() });
+        assert!(alone.probabilities[1] > alone.probabilities[0]);
+        assert_eq!(alone.economic_value, 4_111_110);
+
+        let input_types = types("witness_v0_keyhash");
+        let tx = make_tx(2, &[(3_333_333, p2pkh(1)), (777_777, p2wpkh(2))]);
+        let combined = detect_change(
+            &tx,
+            &InputContext { script_types: &input_types, smallest_value: Some(1_000_000), ..Default::default() },
+        );
+        assert_eq!(combined.change_outputs().collect::<Vec<_>>(), vec![1]);
+    }
+
+    #[test]
+    fn no_evidence_counts_everything() {
+        let single = make_tx(3, &[(12_345_678, p2wpkh(1))]);
+        assert_eq!(detect_change(&single, &InputContext::default()).economic_value, 12_345_678);
+
+        let even = make_tx(1, &[(111_111, p2wpkh(1)), (222_222, p2wpkh(2))]);
+        let analysis = detect_change(&even, &InputContext::default());
+        assert_eq!(analysis.probabilities, vec![0.5, 0.5]);
+        assert_eq!(analysis.economic_value, 333_333);
+    }
+
+    #[test]
+    fn op_return_is_never_change() {
+        let tx = make_tx(1, &[(0, ScriptBuf::new_op_return([1, 2, 3])), (222_222, p2wpkh(2))]);
+        let analysis = detect_change(&tx, &InputContext::default());
+        assert_eq!(analysis.probabilities, vec![0.0, 0.0]);
+        assert_eq!(analysis.economic_value, 222_222);
+    }
+}
@@ -1,10 +1,12 @@
+pub mod change;
 pub mod coinjoin;
+pub mod rescore;
 pub mod rules;
 pub mod score;
 
 use std::collections::HashMap;
 
-use crate::config::AlertThresholds;
+use crate::config::{AlertThresholds, SignalConfig};
 use crate::core::{AlertLevel, AnalyzedTx, RuleScore, ScoredTx};
 use rules::Rule;
 
@@ -18,18 +20,15 @@ pub struct SignalEngine {
 impl SignalEngine {
     #[allow(dead_code)]
     pub fn new() -> Self {
-        Self {
-            rules: rules::default_rules(),
-            weight_overrides: HashMap::new(),
-            thresholds: AlertThresholds::default(),
-        }
+        Self::with_config(&SignalConfig::default())
     }
 
-    pub fn with_config(weights: HashMap<String, f64>, thresholds: AlertThresholds) -> Self {
+    /// Rules, weight overrides and alert thresholds from the `[signals]` config.
+    pub fn with_config(config: &SignalConfig) -> Self {
         Self {
-            rules: rules::default_rules(),
-            weight_overrides: weights,
-            thresholds,
+            rules: rules::default_rules(config),
+            weight_overrides: config.weights.clone(),
+            thresholds: config.alert_thresholds.clone(),
         }
     }
 
@@ -49,6 +48,7 @@ impl SignalEngine {
                     raw_value,
                     weight,
                     weighted_score: raw_value * weight,
+                    detail: rule.detail(tx),
                 }
             })
             .collect();
@@ -0,0 +1,183 @@
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use crate::core::AnalyzedTx;
+use crate::db::{Rescore, SharedDatabase};
+
+use super::SignalEngine;
+
+/// Outcome of scoring stored signals again.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RescoreSummary {
+    /// Key of the run's rows in `rescores`.
+    pub run_id: String,
+    pub scored: usize,
+    /// Payloads that no longer deserialize into an `AnalyzedTx`.
+    pub skipped: usize,
+    /// Signals whose alert level differs from the original.
+    pub level_changed: usize,
+    /// Mean of `new - original` composite score.
+    pub mean_delta: f64,
+}
+
+/// Score the stored analyses of signals created in `[from, to]` with `engine` and store
+/// the results next to the original scores under a new run id.
+pub fn rescore(
+    db: &SharedDatabase,
+    engine: &SignalEngine,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<RescoreSummary, rusqlite::Error> {
+    let payloads = db.read(|db| db.get_signal_payloads(from, to))?;
+
+    let mut rescores = Vec::with_capacity(payloads.len());
+    let mut skipped = 0;
+    for payload in payloads {
+        let tx: AnalyzedTx = match serde_json::from_str(&payload.analyzed_json) {
+            Ok(tx) => tx,
+            Err(e) => {
+                tracing::debug!("Skipping stored analysis of {}: {e}", payload.txid);
+                skipped += 1;
+                continue;
+            }
+        };
+        let scored = engine.score(&tx);
+        rescores.push(Rescore {
+            signal_id: payload.id,
+            txid: payload.txid,
+            original_score: payload.score,
+            original_alert_level: payload.alert_level,
+            score: scored.composite_score,
+            alert_level: format!("{:?}", scored.alert_level),
+            rule_scores_json: serde_json::to_string(&scored.rule_scores).unwrap_or_default(),
+        });
+    }
+
+    let level_changed = rescores.iter().filter(|r| r.alert_level != r.original_alert_level).count();
+    let mean_delta = if rescores.is_empty() {
+        0.0
+    } else {
+        rescores.iter().map(|r| r.score - r.original_score).sum::<f64>() / rescores.len() as f64
+    };
+    let summary = RescoreSummary {
+        run_id: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        scored: rescores.len(),
+        skipped,
+        level_changed,
+        mean_delta,
+    };
+
+    let run_id = summary.run_id.clone();
+    db.write(move |db| db.store_rescores(&run_id, &rescores))?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SignalConfig;
+    use crate::db::SignalBatchEntry;
+    use crate::signals::rules::tests::make_test_tx;
+    use std::collections::HashMap;
+
+    /// Store `analyzed_json` as the payload of signal `txid`, scored from `tx` by the
+    /// default engine.
+    async fn store_payload(db: &SharedDatabase, txid: &str, tx: &AnalyzedTx, analyzed_json: String) {
+        let original = SignalEngine::new().score(tx);
+        let entry = SignalBatchEntry {
+            txid: txid.into(),
+            score: original.composite_score,
+            alert_level: format!("{:?}", original.alert_level),
+            rule_scores_json: "[]".into(),
+            to_exchange: false,
+            total_input_value: tx.total_input_value,
+            fee_rate: tx.fee_rate,
+            coin_days_destroyed: None,
+            block_height_seen: 1,
+            analyzed_json,
+            raw_tx: None,
+        };
+        db.store_signals_batch(vec![entry]).await.unwrap();
+    }
+
+    /// Rescore the signals stored within the last hour with `engine`.
+    async fn run_rescore(db: &SharedDatabase, engine: SignalEngine) -> RescoreSummary {
+        let now = Utc::now();
+        let db = db.clone();
+        tokio::task::spawn_blocking(move || {
+            rescore(&db, &engine, now - chrono::Duration::hours(1), now + chrono::Duration::hours(1))
+        })
+        .await
+        .unwrap()
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn rescores_stored_payloads() {
+        let path = std::env::temp_dir().join(format!("txradar_rescore_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = SharedDatabase::open(&path).unwrap();
+
+        let mut tx = make_test_tx();
+        tx.economic_value = Some(100_0000_0000);
+        let original = SignalEngine::new().score(&tx);
+        store_payload(&db, "good", &tx, serde_json::to_string(&tx).unwrap()).await;
+        store_payload(&db, "stale", &tx, "{\"txid\":\"stale\"}".into()).await;
+
+        // The value rule dominates now
+        let weights = HashMap::from([("tx_value".to_string(), 100.0)]);
+        let engine = SignalEngine::with_config(&SignalConfig { weights, ..Default::default() });
+        let summary = run_rescore(&db, engine).await;
+
+        assert_eq!((summary.scored, summary.skipped), (1, 1));
+        let rows = db.read(|db| db.get_rescores(&summary.run_id)).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].txid, "good");
+        assert_eq!(rows[0].original_score, original.composite_score);
+        assert!(rows[0].score > original.composite_score);
+        assert!((summary.mean_delta - (rows[0].score - rows[0].original_score)).abs() < 1e-9);
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test]
+    async fn unchanged_engine_keeps_old_scores() {
+        let path = std::env::temp_dir().join(format!("txradar_rescore_same_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = SharedDatabase::open(&path).unwrap();
+
+        // Seen a week ago, spending a coin that was a year old then
+        let mut tx = make_test_tx();
+        tx.seen_at = Utc::now() - chrono::Duration::days(7);
+        tx.oldest_input_time = Some(tx.seen_at - chrono::Duration::days(365));
+        store_payload(&db, &tx.txid, &tx, serde_json::to_string(&tx).unwrap()).await;
+
+        let summary = run_rescore(&db, SignalEngine::new()).await;
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(summary.scored, 1);
+        assert_eq!(summary.mean_delta, 0.0);
+        assert_eq!(summary.level_changed, 0);
+    }
+
+    #[tokio::test]
+    async fn payload_without_economic_value_counts_all_outputs() {
+        let path = std::env::temp_dir().join(format!("txradar_rescore_legacy_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = SharedDatabase::open(&path).unwrap();
+
+        // Stored before change detection: no economic_value in the payload
+        let mut tx = make_test_tx();
+        tx.total_output_value = 100_0000_0000;
+        let mut payload = serde_json::to_value(&tx).unwrap();
+        payload.as_object_mut().unwrap().remove("economic_value");
+        let original = SignalEngine::new().score(&tx);
+        assert!(original.rule_scores.iter().any(|r| r.rule_name == "tx_value" && r.raw_value > 0.9));
+        store_payload(&db, &tx.txid, &tx, payload.to_string()).await;
+
+        let summary = run_rescore(&db, SignalEngine::new()).await;
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(summary.scored, 1);
+        assert_eq!(summary.mean_delta, 0.0);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::config::SignalConfig;
 use crate::core::AnalyzedTx;
 
 /// A scoring rule that evaluates a single aspect of a transaction.
@@ -6,19 +7,27 @@ pub trait Rule {
     fn name(&self) -> &str;
     fn default_weight(&self) -> f64;
     fn evaluate(&self, tx: &AnalyzedTx) -> f64;
+    /// Short explanation shown next to the score when the rule fires.
+    fn detail(&self, _tx: &AnalyzedTx) -> Option<String> {
+        None
+    }
 }
 
 /// Return all default rules with initial weights.
-pub fn default_rules() -> Vec<Box<dyn Rule + Send + Sync>> {
+pub fn default_rules(config: &SignalConfig) -> Vec<Box<dyn Rule + Send + Sync>> {
     vec![
         Box::new(TxValueRule),
         Box::new(UtxoAgeRule),
         Box::new(CoinDaysDestroyedRule),
         Box::new(InputCountRule),
         Box::new(FeeRateRule),
+        Box::new(MempoolCongestionRule),
         Box::new(RbfRule),
         Box::new(ExchangeFlowRule),
         Box::new(CoinJoinRule),
+        Box::new(DormantClusterRule { min_days: config.dormant_entity_days as f64 }),
+        Box::new(ScriptMigrationRule),
+        Box::new(CpfpRule),
     ]
 }
 
@@ -29,7 +38,7 @@ impl Rule for TxValueRule {
     fn name(&self) -> &str { "tx_value" }
     fn default_weight(&self) -> f64 { 6.0 }
     fn evaluate(&self, tx: &AnalyzedTx) -> f64 {
-        let btc = tx.total_input_value as f64 / 100_000_000.0;
+        let btc = tx.economic_value() as f64 / 100_000_000.0;
         // Sigmoid-like: 0 at 0 BTC, ~0.5 at 10 BTC, ~0.9 at 100 BTC
         1.0 - 1.0 / (1.0 + btc / 10.0)
     }
@@ -41,8 +50,9 @@ impl Rule for UtxoAgeRule {
     fn default_weight(&self) -> f64 { 8.0 }
     fn evaluate(&self, tx: &AnalyzedTx) -> f64 {
         match tx.oldest_input_time {
+            // Age when the tx was seen, so rescoring a stored analysis gives the same result
             Some(time) => {
-                let age_days = (chrono::Utc::now() - time).num_days() as f64;
+                let age_days = (tx.seen_at - time).num_days() as f64;
                 // Sigmoid: ~0.5 at 365 days, ~0.9 at 2000 days
                 1.0 - 1.0 / (1.0 + age_days / 365.0)
             }
@@ -77,13 +87,64 @@ impl Rule for InputCountRule {
     }
 }
 
+/// Fee urgency relative to the mempool the tx was seen in.
 struct FeeRateRule;
+impl FeeRateRule {
+    /// How many times the projected next-block minimum the tx pays, if the mempool filled
+    /// a block. A child is only as urgent as the package it has to pull through.
+    fn overpay(tx: &AnalyzedTx) -> Option<f64> {
+        Some(tx.effective_fee_rate() / tx.next_block_min_fee?)
+    }
+}
 impl Rule for FeeRateRule {
     fn name(&self) -> &str { "fee_rate" }
     fn default_weight(&self) -> f64 { 3.0 }
     fn evaluate(&self, tx: &AnalyzedTx) -> f64 {
-        // High fee rate = urgency. ~0.5 at 50 sat/vB, ~0.9 at 500 sat/vB
-        1.0 - 1.0 / (1.0 + tx.fee_rate / 50.0)
+        if let Some(ratio) = Self::overpay(tx) {
+            // Just making the next block is no urgency; 0.5 at 2×, 0.9 at 10×
+            return (1.0 - 1.0 / ratio).max(0.0);
+        }
+        match tx.blocks_pending {
+            // The mempool clears at any fee: only outbidding the other pending txs counts
+            Some(_) => tx.fee_percentile.unwrap_or(0.0),
+            // Mempool state unknown: ~0.5 at 50 sat/vB, ~0.9 at 500 sat/vB
+            None => 1.0 - 1.0 / (1.0 + tx.effective_fee_rate() / 50.0),
+        }
+    }
+    fn detail(&self, tx: &AnalyzedTx) -> Option<String> {
+        let percentile = tx.fee_percentile.map(|p| format!("p{:.0}", p * 100.0));
+        match (Self::overpay(tx), percentile) {
+            (Some(ratio), Some(p)) => Some(format!("{ratio:.1}× next block, {p}")),
+            (Some(ratio), None) => Some(format!("{ratio:.1}× next block")),
+            (None, Some(p)) if tx.blocks_pending.is_some() => Some(format!("{p}, mempool under a block")),
+            _ => None,
+        }
+    }
+}
+
+/// Pending blocks at which the congestion context counts half.
+const CONGESTION_MIDPOINT_BLOCKS: f64 = 10.0;
+
+/// Mempool congestion context: a tx paying its way through a congested mempool says more
+/// than the same tx in an empty one. Txs below the next block count by fee percentile.
+struct MempoolCongestionRule;
+impl Rule for MempoolCongestionRule {
+    fn name(&self) -> &str { "mempool_congestion" }
+    fn default_weight(&self) -> f64 { 3.0 }
+    fn evaluate(&self, tx: &AnalyzedTx) -> f64 {
+        let Some(blocks) = tx.blocks_pending else {
+            return 0.0;
+        };
+        let congestion = 1.0 - 1.0 / (1.0 + blocks / CONGESTION_MIDPOINT_BLOCKS);
+        let competing = match tx.next_block_min_fee {
+            Some(min) if tx.effective_fee_rate() >= min => 1.0,
+            _ => tx.fee_percentile.unwrap_or(0.0),
+        };
+        congestion * competing
+    }
+    fn detail(&self, tx: &AnalyzedTx) -> Option<String> {
+        let blocks = tx.blocks_pending?;
+        Some(format!("{blocks:.1} blocks pending"))
     }
 }
 
@@ -131,18 +192,113 @@ impl Rule for ExchangeFlowRule {
     }
 }
 
+/// A tagged entity or cluster spending again after a long silence.
+struct DormantClusterRule {
+    /// Silence that counts as dormant.
+    min_days: f64,
+}
+impl Rule for DormantClusterRule {
+    fn name(&self) -> &str { "dormant_cluster" }
+    fn default_weight(&self) -> f64 { 7.0 }
+    fn evaluate(&self, tx: &AnalyzedTx) -> f64 {
+        match tx.dormancy_days {
+            // 0.5 at the threshold, 0.75 at twice it, approaching 1.0
+            Some(days) if days >= self.min_days && days > 0.0 => 1.0 - 0.5 * self.min_days / days,
+            _ => 0.0,
+        }
+    }
+    fn detail(&self, tx: &AnalyzedTx) -> Option<String> {
+        let days = tx.dormancy_days.filter(|d| *d >= self.min_days)?;
+        let entity = tx.dormant_entity.as_deref()?;
+        Some(if days >= 365.0 {
+            format!("{entity} silent {:.1}y", days / 365.0)
+        } else {
+            format!("{entity} silent {days:.0}d")
+        })
+    }
+}
+
+/// Script types from before SegWit (bare multisig included).
+const LEGACY_SCRIPT_TYPES: &[&str] = &["pubkey", "pubkeyhash", "multisig"];
+
+/// Native SegWit and Taproot script types.
+const MODERN_SCRIPT_TYPES: &[&str] = &["witness_v0_keyhash", "witness_v0_scripthash", "witness_v1_taproot"];
+
+/// Coins created below this height (end of 2010) count as very early.
+const EARLY_COIN_HEIGHT: u32 = 100_000;
+
+/// Coins moving from legacy scripts to SegWit/Taproot: an ownership change or key
+/// rotation. P2PK inputs (mostly 2009-2010 coinbase outputs) and very early coins weigh more.
+struct ScriptMigrationRule;
+impl ScriptMigrationRule {
+    /// The legacy input type and the modern output type of a migration.
+    fn migration(tx: &AnalyzedTx) -> Option<(&str, &str)> {
+        let is_modern = |t: &&String| MODERN_SCRIPT_TYPES.contains(&t.as_str());
+        // Prefer P2PK when inputs mix legacy types
+        let legacy = if tx.input_script_types.iter().any(|t| t == "pubkey") {
+            "pubkey"
+        } else {
+            tx.input_script_types.iter().find(|t| LEGACY_SCRIPT_TYPES.contains(&t.as_str()))?
+        };
+        // Every spendable output must be modern, or the legacy wallet keeps its change
+        let mut outputs = tx.output_script_types.iter().filter(|t| *t != "nulldata");
+        let modern = outputs.next().filter(is_modern)?;
+        outputs.all(|t| is_modern(&t)).then_some((legacy, modern.as_str()))
+    }
+}
+impl Rule for ScriptMigrationRule {
+    fn name(&self) -> &str { "script_migration" }
+    fn default_weight(&self) -> f64 { 3.0 }
+    fn evaluate(&self, tx: &AnalyzedTx) -> f64 {
+        let Some((legacy, _)) = Self::migration(tx) else {
+            return 0.0;
+        };
+        let mut score: f64 = 0.5;
+        if legacy == "pubkey" {
+            score += 0.3;
+        }
+        if tx.oldest_input_height.is_some_and(|h| h < EARLY_COIN_HEIGHT) {
+            score += 0.2;
+        }
+        score.min(1.0)
+    }
+    fn detail(&self, tx: &AnalyzedTx) -> Option<String> {
+        let (legacy, modern) = Self::migration(tx)?;
+        Some(format!("{legacy} → {modern}"))
+    }
+}
+
+/// A child paying for a stuck parent (CPFP): someone needs the parent confirmed now.
+/// Weighs more when the parent itself scored high.
+struct CpfpRule;
+impl Rule for CpfpRule {
+    fn name(&self) -> &str { "cpfp" }
+    fn default_weight(&self) -> f64 { 2.0 }
+    fn evaluate(&self, tx: &AnalyzedTx) -> f64 {
+        if tx.cpfp_parents.is_empty() {
+            return 0.0;
+        }
+        0.5 + 0.5 * (tx.cpfp_parent_score.unwrap_or(0.0) / 100.0).clamp(0.0, 1.0)
+    }
+    fn detail(&self, tx: &AnalyzedTx) -> Option<String> {
+        let score = tx.cpfp_parent_score.filter(|_| !tx.cpfp_parents.is_empty())?;
+        Some(format!("bumps parent scored {score:.0}"))
+    }
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use chrono::Utc;
 
-    fn make_test_tx() -> AnalyzedTx {
+    pub(crate) fn make_test_tx() -> AnalyzedTx {
         AnalyzedTx {
             txid: "deadbeef".to_string(),
             raw_size: 250,
             vsize: 200,
             total_input_value: 0,
             total_output_value: 0,
+            economic_value: None,
             fee: 2000,
             fee_rate: 10.0,
             input_count: 1,
@@ -153,12 +309,25 @@ mod tests {
             is_rbf_signaling: false,
             seen_at: Utc::now(),
             prevouts_resolved: false,
+            resolved_inputs: 0,
+            unconfirmed_parent_inputs: 0,
             to_exchange: false,
             to_exchange_confidence: 0.0,
             from_exchange: false,
             from_exchange_confidence: 0.0,
             is_coinjoin: false,
             coinjoin_confidence: 0.0,
+            dormant_entity: None,
+            dormancy_days: None,
+            input_script_types: Vec::new(),
+            output_script_types: Vec::new(),
+            ancestor_count: 0,
+            ancestor_fee_rate: None,
+            cpfp_parents: Vec::new(),
+            cpfp_parent_score: None,
+            fee_percentile: None,
+            next_block_min_fee: None,
+            blocks_pending: None,
         }
     }
 
@@ -173,7 +342,7 @@ mod tests {
     fn tx_value_midpoint() {
         let rule = TxValueRule;
         let mut tx = make_test_tx();
-        tx.total_input_value = 10_0000_0000; // 10 BTC
+        tx.economic_value = Some(10_0000_0000); // 10 BTC
         let score = rule.evaluate(&tx);
         assert!((score - 0.5).abs() < 0.01, "Expected ~0.5, got {score}");
     }
@@ -182,11 +351,20 @@ mod tests {
     fn tx_value_high() {
         let rule = TxValueRule;
         let mut tx = make_test_tx();
-        tx.total_input_value = 1000_0000_0000; // 1000 BTC
+        tx.economic_value = Some(1000_0000_0000); // 1000 BTC
         let score = rule.evaluate(&tx);
         assert!(score > 0.98, "Expected ~1.0, got {score}");
     }
 
+    #[test]
+    fn tx_value_ignores_change() {
+        let rule = TxValueRule;
+        let mut tx = make_test_tx();
+        tx.total_input_value = 500_0000_0000; // 500 BTC wallet
+        tx.economic_value = Some(100_0000); // paying 0.01 BTC
+        assert!(rule.evaluate(&tx) < 0.01);
+    }
+
     #[test]
     fn utxo_age_none() {
         let rule = UtxoAgeRule;
@@ -348,13 +526,124 @@ mod tests {
 
     #[test]
     fn default_rules_count() {
-        let rules = default_rules();
-        assert_eq!(rules.len(), 8);
+        let rules = default_rules(&SignalConfig::default());
+        assert_eq!(rules.len(), 12);
+    }
+
+    #[test]
+    fn dormant_cluster() {
+        let rule = DormantClusterRule { min_days: 180.0 };
+        let mut tx = make_test_tx();
+        assert_eq!(rule.evaluate(&tx), 0.0);
+        tx.dormant_entity = Some("Mt. Gox".to_string());
+        tx.dormancy_days = Some(90.0);
+        assert_eq!(rule.evaluate(&tx), 0.0);
+        assert_eq!(rule.detail(&tx), None);
+
+        tx.dormancy_days = Some(180.0);
+        assert!((rule.evaluate(&tx) - 0.5).abs() < 0.001);
+        tx.dormancy_days = Some(4.0 * 365.0);
+        assert!(rule.evaluate(&tx) > 0.9);
+        assert_eq!(rule.detail(&tx).as_deref(), Some("Mt. Gox silent 4.0y"));
+    }
+
+    #[test]
+    fn fee_rate_of_package() {
+        let rule = FeeRateRule;
+        let mut tx = make_test_tx();
+        tx.fee_rate = 50.0;
+        assert!((rule.evaluate(&tx) - 0.5).abs() < 0.001);
+        // The child pays 50 sat/vB, but its package only 10
+        tx.ancestor_fee_rate = Some(10.0);
+        assert!(rule.evaluate(&tx) < 0.2);
+    }
+
+    #[test]
+    fn fee_rate_relative_to_mempool() {
+        let rule = FeeRateRule;
+        let mut tx = make_test_tx();
+        tx.fee_rate = 20.0;
+        // The next block needs 300 sat/vB
+        tx.blocks_pending = Some(40.0);
+        tx.next_block_min_fee = Some(300.0);
+        assert_eq!(rule.evaluate(&tx), 0.0);
+        tx.fee_rate = 600.0;
+        tx.fee_percentile = Some(0.995);
+        assert!((rule.evaluate(&tx) - 0.5).abs() < 0.001);
+        assert_eq!(rule.detail(&tx).as_deref(), Some("2.0× next block, p100"));
+    }
+
+    #[test]
+    fn fee_rate_in_quiet_mempool() {
+        let rule = FeeRateRule;
+        let mut tx = make_test_tx();
+        // Half a block pending: 20 sat/vB is 20× the relay minimum, but a median payer
+        tx.fee_rate = 20.0;
+        tx.blocks_pending = Some(0.5);
+        tx.fee_percentile = Some(0.5);
+        assert!((rule.evaluate(&tx) - 0.5).abs() < 0.001);
+        assert_eq!(rule.detail(&tx).as_deref(), Some("p50, mempool under a block"));
+        tx.fee_percentile = Some(0.1);
+        assert!((rule.evaluate(&tx) - 0.1).abs() < 0.001);
+    }
+
+    #[test]
+    fn mempool_congestion() {
+        let rule = MempoolCongestionRule;
+        let mut tx = make_test_tx();
+        assert_eq!(rule.evaluate(&tx), 0.0);
+        assert_eq!(rule.detail(&tx), None);
+
+        tx.blocks_pending = Some(10.0);
+        tx.next_block_min_fee = Some(8.0);
+        assert!((rule.evaluate(&tx) - 0.5).abs() < 0.001);
+        // Below the next block: it waits, weighted by where it stands
+        tx.next_block_min_fee = Some(25.0);
+        tx.fee_percentile = Some(0.4);
+        assert!((rule.evaluate(&tx) - 0.2).abs() < 0.001);
+        assert_eq!(rule.detail(&tx).as_deref(), Some("10.0 blocks pending"));
+    }
+
+    #[test]
+    fn cpfp() {
+        let rule = CpfpRule;
+        let mut tx = make_test_tx();
+        assert_eq!(rule.evaluate(&tx), 0.0);
+        tx.cpfp_parents = vec!["parent".to_string()];
+        tx.cpfp_parent_score = Some(80.0);
+        assert!((rule.evaluate(&tx) - 0.9).abs() < 0.001);
+        assert_eq!(rule.detail(&tx).as_deref(), Some("bumps parent scored 80"));
+    }
+
+    #[test]
+    fn script_migration() {
+        let rule = ScriptMigrationRule;
+        let types = |t: &[&str]| t.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let mut tx = make_test_tx();
+        tx.input_script_types = types(&["pubkeyhash"]);
+        tx.output_script_types = types(&["witness_v1_taproot", "nulldata"]);
+        assert!((rule.evaluate(&tx) - 0.5).abs() < 0.001);
+        assert_eq!(rule.detail(&tx).as_deref(), Some("pubkeyhash → witness_v1_taproot"));
+
+        // P2PK coinbase coin from 2009
+        tx.input_script_types = types(&["pubkeyhash", "pubkey"]);
+        tx.oldest_input_height = Some(9);
+        assert!((rule.evaluate(&tx) - 1.0).abs() < 0.001);
+        assert_eq!(rule.detail(&tx).as_deref(), Some("pubkey → witness_v1_taproot"));
+
+        // Change back to a legacy script: not a migration
+        tx.output_script_types = types(&["witness_v1_taproot", "pubkeyhash"]);
+        assert_eq!(rule.evaluate(&tx), 0.0);
+        // Modern to modern
+        tx.input_script_types = types(&["witness_v0_keyhash"]);
+        tx.output_script_types = types(&["witness_v1_taproot"]);
+        assert_eq!(rule.evaluate(&tx), 0.0);
+        assert_eq!(rule.detail(&tx), None);
     }
 
     #[test]
     fn all_rules_names_unique() {
-        let rules = default_rules();
+        let rules = default_rules(&SignalConfig::default());
         let mut names: Vec<&str> = rules.iter().map(|r| r.name()).collect();
         let len = names.len();
         names.sort();
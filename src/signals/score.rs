@@ -22,6 +22,7 @@ mod tests {
             raw_value: raw,
             weight,
             weighted_score: raw * weight,
+            detail: None,
         }
     }
 
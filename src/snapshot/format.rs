@@ -0,0 +1,495 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_DUP, OP_EQUAL, OP_EQUALVERIFY, OP_HASH160};
+use bitcoin::script::Builder;
+use bitcoin::{BlockHash, PublicKey, ScriptBuf, Txid};
+
+/// Magic bytes of the snapshot header since Bitcoin Core 28.
+pub const SNAPSHOT_MAGIC: &[u8; 5] = b"utxo\xff";
+
+/// Snapshot format version written by Bitcoin Core 28 and later.
+pub const SNAPSHOT_VERSION: u16 = 2;
+
+/// Scripts longer than this are stored as their length only (`MAX_SCRIPT_SIZE`).
+const MAX_SCRIPT_SIZE: u64 = 10_000;
+
+/// `dumptxoutset` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotMetadata {
+    /// 0 for the headerless format of Core 26/27.
+    pub version: u16,
+    /// Network message start, e.g. `f9beb4d9` for mainnet (not in the legacy format).
+    pub network_magic: Option<[u8; 4]>,
+    pub base_blockhash: BlockHash,
+    pub coins_count: u64,
+}
+
+/// One unspent output from a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotCoin {
+    pub txid: Txid,
+    pub vout: u32,
+    pub height: u32,
+    pub coinbase: bool,
+    pub value: u64,
+    pub script_pubkey: ScriptBuf,
+}
+
+/// Streams the coins of a `dumptxoutset` file.
+///
+/// Core 28+ writes coins grouped by txid (`txid | compactsize count | (compactsize vout |
+/// coin)*`); Core 26/27 write `txid | u32 vout | coin` per coin. A coin is
+/// `VARINT(height * 2 + coinbase) | VARINT(compressed amount) | compressed script`.
+pub struct SnapshotReader<R: Read> {
+    inner: R,
+    metadata: SnapshotMetadata,
+    /// Bytes consumed so far, header included.
+    offset: u64,
+    coins_read: u64,
+    /// Current txid group and its coins still to read (Core 28+ format).
+    group: Option<(Txid, u64)>,
+}
+
+impl<R: Read> SnapshotReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut start = [0u8; 5];
+        inner.read_exact(&mut start)?;
+        let mut offset = start.len() as u64;
+
+        let metadata = if &start == SNAPSHOT_MAGIC {
+            let mut buf = [0u8; 2 + 4 + 32 + 8];
+            inner.read_exact(&mut buf)?;
+            offset += buf.len() as u64;
+            let version = u16::from_le_bytes([buf[0], buf[1]]);
+            if version != SNAPSHOT_VERSION {
+                return Err(invalid(format!("unsupported snapshot version {version}")));
+            }
+            SnapshotMetadata {
+                version,
+                network_magic: Some([buf[2], buf[3], buf[4], buf[5]]),
+                base_blockhash: BlockHash::from_byte_array(buf[6..38].try_into().unwrap()),
+                coins_count: u64::from_le_bytes(buf[38..46].try_into().unwrap()),
+            }
+        } else {
+            // Legacy: the header is just the base block hash and the coin count
+            let mut buf = [0u8; 32 + 8];
+            buf[..5].copy_from_slice(&start);
+            inner.read_exact(&mut buf[5..])?;
+            offset += 35;
+            SnapshotMetadata {
+                version: 0,
+                network_magic: None,
+                base_blockhash: BlockHash::from_byte_array(buf[..32].try_into().unwrap()),
+                coins_count: u64::from_le_bytes(buf[32..40].try_into().unwrap()),
+            }
+        };
+
+        Ok(Self { inner, metadata, offset, coins_read: 0, group: None })
+    }
+
+    pub fn metadata(&self) -> &SnapshotMetadata {
+        &self.metadata
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    pub fn coins_read(&self) -> u64 {
+        self.coins_read
+    }
+
+    /// Between txid groups, where reading can resume from `offset()`.
+    pub fn at_boundary(&self) -> bool {
+        self.group.is_none()
+    }
+
+    /// Read the next coin; `Ok(None)` once all `coins_count` coins are read.
+    pub fn next_coin(&mut self) -> io::Result<Option<SnapshotCoin>> {
+        if self.coins_read >= self.metadata.coins_count {
+            return Ok(None);
+        }
+        let (txid, vout) = if self.metadata.version == 0 {
+            let txid = Txid::from_byte_array(self.read_array()?);
+            (txid, u32::from_le_bytes(self.read_array()?))
+        } else {
+            let txid = match self.group {
+                Some((txid, _)) => txid,
+                None => {
+                    let txid = Txid::from_byte_array(self.read_array()?);
+                    let count = self.read_compact_size()?;
+                    if count == 0 {
+                        return Err(invalid(format!("empty coin group for {txid}")));
+                    }
+                    self.group = Some((txid, count));
+                    txid
+                }
+            };
+            let vout = self.read_compact_size()?;
+            let vout = u32::try_from(vout).map_err(|_| invalid(format!("invalid vout {vout}")))?;
+            self.group = match self.group {
+                Some((txid, remaining)) if remaining > 1 => Some((txid, remaining - 1)),
+                _ => None,
+            };
+            (txid, vout)
+        };
+
+        let code = self.read_varint()?;
+        let height = u32::try_from(code >> 1).map_err(|_| invalid(format!("invalid height code {code}")))?;
+        let value = decompress_amount(self.read_varint()?);
+        let script_pubkey = self.read_script()?;
+        self.coins_read += 1;
+        Ok(Some(SnapshotCoin { txid, vout, height, coinbase: code & 1 == 1, value, script_pubkey }))
+    }
+
+    fn read_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; len];
+        self.inner.read_exact(&mut buf)?;
+        self.offset += len as u64;
+        Ok(buf)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.inner.read_exact(&mut buf)?;
+        self.offset += N as u64;
+        Ok(buf)
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    /// Bitcoin's CompactSize (as used for vector lengths).
+    fn read_compact_size(&mut self) -> io::Result<u64> {
+        Ok(match self.read_u8()? {
+            0xfd => u16::from_le_bytes(self.read_array()?) as u64,
+            0xfe => u32::from_le_bytes(self.read_array()?) as u64,
+            0xff => u64::from_le_bytes(self.read_array()?),
+            n => n as u64,
+        })
+    }
+
+    /// Bitcoin Core's `VARINT`: base-128, most significant group first, with an offset
+    /// of one per continuation byte so every number has a single encoding.
+    fn read_varint(&mut self) -> io::Result<u64> {
+        let mut n: u64 = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if n > u64::MAX >> 7 {
+                return Err(invalid("VARINT too large".to_string()));
+            }
+            n = (n << 7) | (byte & 0x7f) as u64;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+            n = n.checked_add(1).ok_or_else(|| invalid("VARINT too large".to_string()))?;
+        }
+    }
+
+    /// Core's `ScriptCompression`: sizes 0-5 are templates (P2PKH, P2SH, P2PK), larger
+    /// sizes are raw scripts of `size - 6` bytes.
+    fn read_script(&mut self) -> io::Result<ScriptBuf> {
+        let size = self.read_varint()?;
+        match size {
+            0 => {
+                let hash: [u8; 20] = self.read_array()?;
+                Ok(Builder::new()
+                    .push_opcode(OP_DUP)
+                    .push_opcode(OP_HASH160)
+                    .push_slice(hash)
+                    .push_opcode(OP_EQUALVERIFY)
+                    .push_opcode(OP_CHECKSIG)
+                    .into_script())
+            }
+            1 => {
+                let hash: [u8; 20] = self.read_array()?;
+                Ok(Builder::new()
+                    .push_opcode(OP_HASH160)
+                    .push_slice(hash)
+                    .push_opcode(OP_EQUAL)
+                    .into_script())
+            }
+            2..=5 => {
+                let x: [u8; 32] = self.read_array()?;
+                let mut key = [0u8; 33];
+                key[0] = if size < 4 { size as u8 } else { size as u8 - 2 };
+                key[1..].copy_from_slice(&x);
+                let mut pubkey = PublicKey::from_slice(&key).map_err(|e| invalid(format!("invalid P2PK key: {e}")))?;
+                // Sizes 4/5 stand for the uncompressed form of the key
+                pubkey.compressed = size < 4;
+                Ok(ScriptBuf::new_p2pk(&pubkey))
+            }
+            _ => {
+                let len = size - 6;
+                if len > MAX_SCRIPT_SIZE {
+                    // Unspendable; Core keeps just OP_RETURN
+                    self.skip(len)?;
+                    return Ok(ScriptBuf::new_op_return([]));
+                }
+                Ok(ScriptBuf::from_bytes(self.read_bytes(len as usize)?))
+            }
+        }
+    }
+
+    fn skip(&mut self, len: u64) -> io::Result<()> {
+        let skipped = io::copy(&mut (&mut self.inner).take(len), &mut io::sink())?;
+        self.offset += skipped;
+        if skipped < len {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated script"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> SnapshotReader<R> {
+    /// Continue an earlier import from a position returned by `offset()` at a boundary.
+    pub fn resume_at(&mut self, offset: u64, coins_read: u64) -> io::Result<()> {
+        self.inner.seek(SeekFrom::Start(offset))?;
+        self.offset = offset;
+        self.coins_read = coins_read;
+        self.group = None;
+        Ok(())
+    }
+}
+
+/// Inverse of Core's `CompressAmount`, which drops trailing zeros of the satoshi value.
+fn decompress_amount(x: u64) -> u64 {
+    if x == 0 {
+        return 0;
+    }
+    let mut x = x - 1;
+    let mut e = x % 10;
+    x /= 10;
+    let mut n = if e < 9 {
+        let d = (x % 9) + 1;
+        x /= 9;
+        x * 10 + d
+    } else {
+        x + 1
+    };
+    while e > 0 {
+        n *= 10;
+        e -= 1;
+    }
+    n
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    /// Core's `CompressAmount`, to build test snapshots.
+    fn compress_amount(mut n: u64) -> u64 {
+        if n == 0 {
+            return 0;
+        }
+        let mut e = 0;
+        while n.is_multiple_of(10) && e < 9 {
+            n /= 10;
+            e += 1;
+        }
+        if e < 9 {
+            let d = n % 10;
+            n /= 10;
+            1 + (n * 9 + d - 1) * 10 + e
+        } else {
+            1 + (n - 1) * 10 + 9
+        }
+    }
+
+    fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+        let mut tmp = Vec::new();
+        loop {
+            tmp.push((n & 0x7f) as u8 | if tmp.is_empty() { 0 } else { 0x80 });
+            if n <= 0x7f {
+                break;
+            }
+            n = (n >> 7) - 1;
+        }
+        tmp.reverse();
+        out.extend_from_slice(&tmp);
+    }
+
+    fn write_compact_size(out: &mut Vec<u8>, n: u64) {
+        match n {
+            0..0xfd => out.push(n as u8),
+            0xfd..=0xffff => {
+                out.push(0xfd);
+                out.extend_from_slice(&(n as u16).to_le_bytes());
+            }
+            _ => {
+                out.push(0xfe);
+                out.extend_from_slice(&(n as u32).to_le_bytes());
+            }
+        }
+    }
+
+    /// Serialize a coin the way Core does; scripts other than P2PKH/P2SH are stored raw.
+    fn write_coin(out: &mut Vec<u8>, coin: &SnapshotCoin) {
+        write_varint(out, (coin.height as u64) * 2 + coin.coinbase as u64);
+        write_varint(out, compress_amount(coin.value));
+        let script = coin.script_pubkey.as_bytes();
+        if coin.script_pubkey.is_p2pkh() {
+            write_varint(out, 0);
+            out.extend_from_slice(&script[3..23]);
+        } else if coin.script_pubkey.is_p2sh() {
+            write_varint(out, 1);
+            out.extend_from_slice(&script[2..22]);
+        } else {
+            write_varint(out, script.len() as u64 + 6);
+            out.extend_from_slice(script);
+        }
+    }
+
+    /// A Core 28+ snapshot of `coins` (grouped by consecutive txid).
+    pub(crate) fn snapshot_bytes(base: BlockHash, coins: &[SnapshotCoin]) -> Vec<u8> {
+        let mut out = SNAPSHOT_MAGIC.to_vec();
+        out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        out.extend_from_slice(&[0xf9, 0xbe, 0xb4, 0xd9]);
+        out.extend_from_slice(base.as_byte_array());
+        out.extend_from_slice(&(coins.len() as u64).to_le_bytes());
+        for group in coins.chunk_by(|a, b| a.txid == b.txid) {
+            out.extend_from_slice(group[0].txid.as_byte_array());
+            write_compact_size(&mut out, group.len() as u64);
+            for coin in group {
+                write_compact_size(&mut out, coin.vout as u64);
+                write_coin(&mut out, coin);
+            }
+        }
+        out
+    }
+
+    pub(crate) fn coin(txid: u8, vout: u32, height: u32, value: u64, script_pubkey: ScriptBuf) -> SnapshotCoin {
+        SnapshotCoin {
+            txid: Txid::from_byte_array([txid; 32]),
+            vout,
+            height,
+            coinbase: false,
+            value,
+            script_pubkey,
+        }
+    }
+
+    pub(crate) fn sample_coins() -> Vec<SnapshotCoin> {
+        let p2pkh = ScriptBuf::new_p2pkh(&bitcoin::PubkeyHash::from_byte_array([1; 20]));
+        let p2sh = ScriptBuf::new_p2sh(&bitcoin::ScriptHash::from_byte_array([2; 20]));
+        let p2wpkh = ScriptBuf::new_p2wpkh(&bitcoin::WPubkeyHash::from_byte_array([3; 20]));
+        vec![
+            SnapshotCoin { coinbase: true, ..coin(1, 0, 1, 5_000_000_000, p2pkh) },
+            coin(2, 0, 500, 123_456_789, p2sh),
+            coin(2, 7, 500, 1, p2wpkh.clone()),
+            coin(3, 300, 800, 21_000_000 * 100_000_000, p2wpkh),
+        ]
+    }
+
+    #[test]
+    fn varint_and_amount_roundtrip() {
+        for n in [0u64, 1, 127, 128, 255, 16_511, 16_512, 1 << 32, u64::MAX >> 1] {
+            let mut buf = Vec::new();
+            write_varint(&mut buf, n);
+            let mut reader = SnapshotReader {
+                inner: Cursor::new(buf),
+                metadata: SnapshotMetadata {
+                    version: 2,
+                    network_magic: None,
+                    base_blockhash: BlockHash::all_zeros(),
+                    coins_count: 0,
+                },
+                offset: 0,
+                coins_read: 0,
+                group: None,
+            };
+            assert_eq!(reader.read_varint().unwrap(), n);
+        }
+        for n in [0u64, 1, 9, 10, 50_000, 123_456_789, 5_000_000_000, 2_100_000_000_000_000] {
+            assert_eq!(decompress_amount(compress_amount(n)), n);
+        }
+        // Known encodings from Core's compress_tests
+        assert_eq!(compress_amount(100_000_000), 9);
+        assert_eq!(compress_amount(50 * 100_000_000), 50);
+    }
+
+    #[test]
+    fn reads_grouped_snapshot() {
+        let base = BlockHash::from_byte_array([0xaa; 32]);
+        let coins = sample_coins();
+        let bytes = snapshot_bytes(base, &coins);
+        let len = bytes.len() as u64;
+
+        let mut reader = SnapshotReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.metadata().base_blockhash, base);
+        assert_eq!(reader.metadata().coins_count, 4);
+        assert_eq!(reader.metadata().network_magic, Some([0xf9, 0xbe, 0xb4, 0xd9]));
+        let read: Vec<SnapshotCoin> = std::iter::from_fn(|| reader.next_coin().unwrap()).collect();
+        assert_eq!(read, coins);
+        assert_eq!(reader.offset(), len);
+        assert!(reader.at_boundary());
+    }
+
+    #[test]
+    fn reads_legacy_snapshot_and_p2pk() {
+        let base = BlockHash::from_byte_array([0xbb; 32]);
+        // Generator point: x coordinate, even y
+        let x: [u8; 32] = bitcoin::hex::FromHex::from_hex(
+            "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let mut bytes = base.as_byte_array().to_vec();
+        bytes.extend_from_slice(&2u64.to_le_bytes());
+        for (vout, size) in [(0u32, 2u64), (1, 4)] {
+            bytes.extend_from_slice(&[9; 32]);
+            bytes.extend_from_slice(&vout.to_le_bytes());
+            write_varint(&mut bytes, 170 * 2);
+            write_varint(&mut bytes, compress_amount(1_000));
+            write_varint(&mut bytes, size);
+            bytes.extend_from_slice(&x);
+        }
+
+        let mut reader = SnapshotReader::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(reader.metadata().version, 0);
+        assert_eq!(reader.metadata().base_blockhash, base);
+        let compressed = reader.next_coin().unwrap().unwrap();
+        let uncompressed = reader.next_coin().unwrap().unwrap();
+        assert!(reader.next_coin().unwrap().is_none());
+
+        assert_eq!((compressed.height, compressed.value, compressed.vout), (170, 1_000, 0));
+        assert!(compressed.script_pubkey.is_p2pk());
+        assert_eq!(compressed.script_pubkey.len(), 35);
+        assert!(uncompressed.script_pubkey.is_p2pk());
+        assert_eq!(uncompressed.script_pubkey.len(), 67);
+    }
+
+    #[test]
+    fn resumes_at_boundary() {
+        let coins = sample_coins();
+        let bytes = snapshot_bytes(BlockHash::all_zeros(), &coins);
+        let mut reader = SnapshotReader::new(Cursor::new(bytes.clone())).unwrap();
+        reader.next_coin().unwrap();
+        assert!(reader.at_boundary());
+        reader.next_coin().unwrap();
+        assert!(!reader.at_boundary());
+        reader.next_coin().unwrap();
+        let (offset, done) = (reader.offset(), reader.coins_read());
+
+        let mut resumed = SnapshotReader::new(Cursor::new(bytes)).unwrap();
+        resumed.resume_at(offset, done).unwrap();
+        assert_eq!(resumed.next_coin().unwrap().as_ref(), Some(&coins[3]));
+        assert!(resumed.next_coin().unwrap().is_none());
+    }
+
+    #[test]
+    fn truncated_snapshot_errors() {
+        let mut bytes = snapshot_bytes(BlockHash::all_zeros(), &sample_coins());
+        bytes.truncate(bytes.len() - 3);
+        let mut reader = SnapshotReader::new(Cursor::new(bytes)).unwrap();
+        let result: io::Result<Vec<_>> = std::iter::from_fn(|| reader.next_coin().transpose()).collect();
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,347 @@
+pub mod format;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use bitcoin::{Address, Network};
+use serde_json::json;
+use tracing::info;
+
+use crate::core::tx::script_type;
+use crate::db::{CachedUtxo, SharedDatabase, SnapshotImport};
+use crate::rpc::{BitcoinRpc, RpcError};
+use format::{SnapshotCoin, SnapshotReader};
+
+/// Coins per database transaction; progress is saved with each.
+const IMPORT_BATCH: usize = 50_000;
+
+/// Heights per `getblockhash`/`getblockheader` batch when filling `block_times`.
+const BLOCK_TIME_BATCH: usize = 1_000;
+
+/// How often import progress is logged.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    Db(rusqlite::Error),
+    Rpc(RpcError),
+    /// The node can't place the snapshot, e.g. it is for another network.
+    UnknownBase(String),
+    /// A coin's block time is missing from `block_times`.
+    MissingBlockTime(u32),
+}
+
+impl std::fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot read error: {e}"),
+            SnapshotError::Db(e) => write!(f, "database error: {e}"),
+            SnapshotError::Rpc(e) => write!(f, "{e}"),
+            SnapshotError::UnknownBase(hash) => write!(f, "the node doesn't know snapshot base block {hash}"),
+            SnapshotError::MissingBlockTime(height) => write!(f, "no block time for height {height}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<std::io::Error> for SnapshotError {
+    fn from(e: std::io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<rusqlite::Error> for SnapshotError {
+    fn from(e: rusqlite::Error) -> Self {
+        SnapshotError::Db(e)
+    }
+}
+
+impl From<RpcError> for SnapshotError {
+    fn from(e: RpcError) -> Self {
+        SnapshotError::Rpc(e)
+    }
+}
+
+/// Outcome of an import run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportSummary {
+    pub base_blockhash: String,
+    pub base_height: u32,
+    pub coins_total: u64,
+    /// Coins imported by this run (less than the total when resuming).
+    pub coins_imported: u64,
+    pub already_done: bool,
+}
+
+/// Seed `utxo_cache` from a `bitcoin-cli dumptxoutset` file.
+///
+/// The node supplies the base block's height and the times of all blocks up to it
+/// (cached in `block_times`), so every imported coin gets its age. Progress is saved with
+/// each batch: running the import again after an interruption continues where it
+/// stopped, and a finished snapshot is skipped.
+pub async fn import_snapshot(path: &Path, db: &SharedDatabase, rpc: &BitcoinRpc) -> Result<ImportSummary, SnapshotError> {
+    let reader = SnapshotReader::new(BufReader::new(File::open(path)?))?;
+    let metadata = reader.metadata().clone();
+    let base_blockhash = metadata.base_blockhash.to_string();
+    info!(
+        "Snapshot {}: {} coins at block {base_blockhash} (format v{})",
+        path.display(),
+        metadata.coins_count,
+        metadata.version
+    );
+
+    let base_height = match rpc.call("getblockheader", vec![json!(base_blockhash)]).await {
+        Ok(header) => header
+            .get("height")
+            .and_then(|h| h.as_u64())
+            .ok_or_else(|| SnapshotError::UnknownBase(base_blockhash.clone()))? as u32,
+        Err(RpcError::Rpc(_)) => return Err(SnapshotError::UnknownBase(base_blockhash)),
+        Err(e) => return Err(e.into()),
+    };
+
+    let progress = db.get_snapshot_import(&base_blockhash)?;
+    if progress.as_ref().is_some_and(|p| p.finished) {
+        info!("Snapshot at block {base_blockhash} was already imported");
+        return Ok(ImportSummary {
+            base_blockhash,
+            base_height,
+            coins_total: metadata.coins_count,
+            coins_imported: 0,
+            already_done: true,
+        });
+    }
+
+    let block_times = fill_block_times(db, rpc, base_height).await?;
+
+    let path = path.to_path_buf();
+    let db = db.clone();
+    let coins_imported = tokio::task::spawn_blocking(move || import_coins(&path, &db, &block_times, progress))
+        .await
+        .map_err(|e| SnapshotError::Io(std::io::Error::other(e)))??;
+    Ok(ImportSummary {
+        base_blockhash,
+        base_height,
+        coins_total: metadata.coins_count,
+        coins_imported,
+        already_done: false,
+    })
+}
+
+/// Make sure `block_times` covers heights `0..=tip`, fetching what's missing from the
+/// node. Returns all block times, indexed by height.
+async fn fill_block_times(db: &SharedDatabase, rpc: &BitcoinRpc, tip: u32) -> Result<Vec<i64>, SnapshotError> {
+    let mut known: HashMap<u32, i64> = db.all_block_times()?;
+    let missing: Vec<u32> = (0..=tip).filter(|h| !known.contains_key(h)).collect();
+    if !missing.is_empty() {
+        info!("Fetching {} block times from the node", missing.len());
+    }
+    let mut last_log = Instant::now();
+    for (i, chunk) in missing.chunks(BLOCK_TIME_BATCH).enumerate() {
+        let fetched = rpc.block_times(chunk).await?;
+        let rows: Vec<(u32, i64)> = fetched.iter().map(|(&h, &t)| (h, t)).collect();
+        db.store_block_times(&rows)?;
+        known.extend(fetched);
+        if last_log.elapsed() >= PROGRESS_INTERVAL {
+            info!("Block times: {}/{}", ((i + 1) * BLOCK_TIME_BATCH).min(missing.len()), missing.len());
+            last_log = Instant::now();
+        }
+    }
+
+    let mut times = vec![0; tip as usize + 1];
+    for (height, time) in known {
+        if let Some(slot) = times.get_mut(height as usize) {
+            *slot = time;
+        }
+    }
+    Ok(times)
+}
+
+/// Stream the snapshot's coins into the cache, resuming from `progress` if given.
+/// Returns the number of coins imported by this run.
+fn import_coins(
+    path: &Path,
+    db: &SharedDatabase,
+    block_times: &[i64],
+    progress: Option<SnapshotImport>,
+) -> Result<u64, SnapshotError> {
+    let mut reader = SnapshotReader::new(BufReader::new(File::open(path)?))?;
+    let coins_total = reader.metadata().coins_count;
+    let mut progress = progress.unwrap_or_else(|| SnapshotImport {
+        base_blockhash: reader.metadata().base_blockhash.to_string(),
+        coins_total,
+        coins_done: 0,
+        offset: reader.offset(),
+        finished: false,
+    });
+    if progress.coins_done > 0 {
+        info!("Resuming snapshot import at coin {}/{coins_total}", progress.coins_done);
+        reader.resume_at(progress.offset, progress.coins_done)?;
+    }
+    let resumed_from = progress.coins_done;
+
+    let started = Instant::now();
+    let mut last_log = started;
+    let mut batch: Vec<(String, u32, CachedUtxo)> = Vec::with_capacity(IMPORT_BATCH);
+    loop {
+        let coin = reader.next_coin()?;
+        if let Some(coin) = coin {
+            batch.push(cache_row(coin, block_times)?);
+            // Only checkpoint between txid groups: resuming mid-group isn't possible
+            if batch.len() < IMPORT_BATCH || !reader.at_boundary() {
+                continue;
+            }
+        }
+
+        progress.coins_done = reader.coins_read();
+        progress.offset = reader.offset();
+        progress.finished = progress.coins_done >= coins_total;
+        db.import_utxo_batch(std::mem::take(&mut batch), &progress)?;
+
+        if progress.finished {
+            break;
+        }
+        if last_log.elapsed() >= PROGRESS_INTERVAL {
+            let imported = progress.coins_done - resumed_from;
+            let rate = imported as f64 / started.elapsed().as_secs_f64().max(1e-3);
+            let eta_min = (coins_total - progress.coins_done) as f64 / rate.max(1.0) / 60.0;
+            info!(
+                "Imported {}/{coins_total} coins ({:.1}%), {rate:.0} coins/s, ~{eta_min:.0} min left",
+                progress.coins_done,
+                progress.coins_done as f64 * 100.0 / coins_total.max(1) as f64,
+            );
+            last_log = Instant::now();
+        }
+    }
+
+    let imported = progress.coins_done - resumed_from;
+    info!(
+        "Snapshot import finished: {imported} coins in {:.0}s",
+        started.elapsed().as_secs_f64()
+    );
+    Ok(imported)
+}
+
+/// A snapshot coin as a `utxo_cache` row.
+fn cache_row(coin: SnapshotCoin, block_times: &[i64]) -> Result<(String, u32, CachedUtxo), SnapshotError> {
+    let block_time = block_times
+        .get(coin.height as usize)
+        .copied()
+        .filter(|&t| t > 0)
+        .ok_or(SnapshotError::MissingBlockTime(coin.height))?;
+    let utxo = CachedUtxo {
+        value: coin.value,
+        script_type: script_type(&coin.script_pubkey).to_string(),
+        address: Address::from_script(&coin.script_pubkey, Network::Bitcoin)
+            .ok()
+            .map(|a| a.to_string()),
+        script_pubkey: coin.script_pubkey.into_bytes(),
+        block_height: coin.height,
+        block_time,
+    };
+    Ok((coin.txid.to_string(), coin.vout, utxo))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rpc::test_server;
+    use bitcoin::BlockHash;
+    use bitcoin::hashes::Hash;
+    use format::tests::{sample_coins, snapshot_bytes};
+    use serde_json::Value;
+    use std::path::PathBuf;
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("txradar_{name}_{}", std::process::id()))
+    }
+
+    /// Node stub with the snapshot base at height 1000 and block time = 1_000_000 + height.
+    async fn node(base: BlockHash) -> BitcoinRpc {
+        test_server::spawn(Duration::ZERO, move |request| {
+            let respond = |call: &Value| {
+                let result = match call["method"].as_str().unwrap() {
+                    "getblockheader" if call["params"][0] == json!(base.to_string()) => json!({"height": 1_000}),
+                    "getblockheader" => {
+                        let height: u64 = call["params"][0].as_str().unwrap()[1..].parse().unwrap();
+                        json!({"time": 1_000_000 + height})
+                    }
+                    "getblockhash" => json!(format!("h{}", call["params"][0])),
+                    _ => return json!({"id": call["id"], "result": null, "error": {"code": -5}}),
+                };
+                json!({"id": call["id"], "result": result, "error": null})
+            };
+            match request.as_array() {
+                Some(calls) => Value::Array(calls.iter().map(respond).collect()),
+                None => respond(&request),
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn imports_and_resumes() {
+        let base = BlockHash::from_byte_array([0xcc; 32]);
+        let coins = sample_coins();
+        let path = temp_path("snapshot_import.dat");
+        std::fs::write(&path, snapshot_bytes(base, &coins)).unwrap();
+        let db_path = temp_path("snapshot_import.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db = SharedDatabase::open(&db_path).unwrap();
+        let rpc = node(base).await;
+
+        // An interrupted earlier run got through the first txid group
+        let mut reader = SnapshotReader::new(BufReader::new(File::open(&path).unwrap())).unwrap();
+        reader.next_coin().unwrap();
+        db.import_utxo_batch(
+            Vec::new(),
+            &SnapshotImport {
+                base_blockhash: base.to_string(),
+                coins_total: 4,
+                coins_done: 1,
+                offset: reader.offset(),
+                finished: false,
+            },
+        )
+        .unwrap();
+
+        let summary = import_snapshot(&path, &db, &rpc).await.unwrap();
+        assert_eq!((summary.base_height, summary.coins_total, summary.coins_imported), (1_000, 4, 3));
+        assert!(db.get_utxo(&coins[0].txid.to_string(), 0).unwrap().is_none());
+
+        let row = db.get_utxo(&coins[1].txid.to_string(), 0).unwrap().unwrap();
+        assert_eq!(row.value, 123_456_789);
+        assert_eq!(row.script_type, "scripthash");
+        assert_eq!((row.block_height, row.block_time), (500, 1_000_500));
+        assert!(row.address.unwrap().starts_with('3'));
+        let row = db.get_utxo(&coins[3].txid.to_string(), 300).unwrap().unwrap();
+        assert_eq!(row.script_type, "witness_v0_keyhash");
+        assert_eq!(row.script_pubkey, coins[3].script_pubkey.to_bytes());
+        assert_eq!(db.all_block_times().unwrap().len(), 1_001);
+
+        // A finished snapshot isn't imported again
+        let again = import_snapshot(&path, &db, &rpc).await.unwrap();
+        assert!(again.already_done);
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&db_path);
+    }
+
+    #[tokio::test]
+    async fn unknown_base_block() {
+        let path = temp_path("snapshot_unknown.dat");
+        std::fs::write(&path, snapshot_bytes(BlockHash::all_zeros(), &sample_coins())).unwrap();
+        let db_path = temp_path("snapshot_unknown.db");
+        let _ = std::fs::remove_file(&db_path);
+        let db = SharedDatabase::open(&db_path).unwrap();
+        let rpc = node(BlockHash::from_byte_array([1; 32])).await;
+
+        let result = import_snapshot(&path, &db, &rpc).await;
+        assert!(matches!(result, Err(SnapshotError::UnknownBase(_))));
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&db_path);
+    }
+}
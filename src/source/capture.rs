@@ -0,0 +1,201 @@
+use std::io::{self, Read, Write};
+
+/// Magic bytes at the start of every capture file.
+pub const CAPTURE_MAGIC: &[u8; 6] = b"TXRCAP";
+
+/// Current capture format version. Readers accept any version up to this one.
+pub const CAPTURE_VERSION: u16 = 1;
+
+/// Header flag: the `sequence` topic was subscribed while recording.
+pub const FLAG_SEQUENCE: u16 = 1 << 0;
+
+/// Upper bound for a single record, to fail fast on corrupt length prefixes.
+const MAX_RECORD_LEN: u32 = 64 * 1024 * 1024;
+
+/// Capture file header: magic, version and flags (10 bytes).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CaptureHeader {
+    pub version: u16,
+    pub flags: u16,
+}
+
+impl CaptureHeader {
+    pub fn has_sequence(&self) -> bool {
+        self.flags & FLAG_SEQUENCE != 0
+    }
+}
+
+/// One raw multipart ZMQ message as received.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    /// Receive time in microseconds since the Unix epoch.
+    pub received_at_us: u64,
+    /// Message frames: topic, body and (usually) the 4-byte sequence frame.
+    pub frames: Vec<Vec<u8>>,
+}
+
+/// Appends records to a capture stream, writing the header first.
+pub struct CaptureWriter<W: Write> {
+    inner: W,
+    bytes_written: u64,
+}
+
+impl<W: Write> CaptureWriter<W> {
+    pub fn new(mut inner: W, flags: u16) -> io::Result<Self> {
+        inner.write_all(CAPTURE_MAGIC)?;
+        inner.write_all(&CAPTURE_VERSION.to_le_bytes())?;
+        inner.write_all(&flags.to_le_bytes())?;
+        Ok(Self {
+            inner,
+            bytes_written: (CAPTURE_MAGIC.len() + 4) as u64,
+        })
+    }
+
+    /// Record layout: `u32 len | u64 received_at_us | u16 frame_count | (u32 len | bytes)*`,
+    /// all little-endian; `len` covers everything after itself.
+    pub fn write_record(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        let payload_len = 8 + 2 + record.frames.iter().map(|f| 4 + f.len()).sum::<usize>();
+        let mut buf = Vec::with_capacity(4 + payload_len);
+        buf.extend_from_slice(&(payload_len as u32).to_le_bytes());
+        buf.extend_from_slice(&record.received_at_us.to_le_bytes());
+        buf.extend_from_slice(&(record.frames.len() as u16).to_le_bytes());
+        for frame in &record.frames {
+            buf.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            buf.extend_from_slice(frame);
+        }
+        self.inner.write_all(&buf)?;
+        self.bytes_written += buf.len() as u64;
+        Ok(())
+    }
+
+    /// Total bytes written so far, header included.
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads records from a capture stream after validating its header.
+pub struct CaptureReader<R: Read> {
+    inner: R,
+    header: CaptureHeader,
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut buf = [0u8; 10];
+        inner.read_exact(&mut buf)?;
+        if &buf[..6] != CAPTURE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not a TxRadar capture file"));
+        }
+        let version = u16::from_le_bytes([buf[6], buf[7]]);
+        if version == 0 || version > CAPTURE_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported capture version {version} (supported: 1..={CAPTURE_VERSION})"),
+            ));
+        }
+        let flags = u16::from_le_bytes([buf[8], buf[9]]);
+        Ok(Self { inner, header: CaptureHeader { version, flags } })
+    }
+
+    pub fn header(&self) -> CaptureHeader {
+        self.header
+    }
+
+    /// Read the next record. Returns `Ok(None)` at the end of the stream, including a
+    /// record truncated by an unclean shutdown.
+    pub fn next_record(&mut self) -> io::Result<Option<CaptureRecord>> {
+        let mut len_buf = [0u8; 4];
+        match self.inner.read_exact(&mut len_buf) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let len = u32::from_le_bytes(len_buf);
+        if !(10..=MAX_RECORD_LEN).contains(&len) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("invalid record length {len}")));
+        }
+        let mut payload = vec![0u8; len as usize];
+        match self.inner.read_exact(&mut payload) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        parse_payload(&payload)
+            .map(Some)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed capture record"))
+    }
+}
+
+/// Parse a record payload. Bytes after the last frame are ignored so later versions can
+/// append fields without breaking older readers.
+fn parse_payload(payload: &[u8]) -> Option<CaptureRecord> {
+    let received_at_us = u64::from_le_bytes(payload.get(..8)?.try_into().ok()?);
+    let frame_count = u16::from_le_bytes(payload.get(8..10)?.try_into().ok()?);
+    let mut pos = 10;
+    let mut frames = Vec::with_capacity(frame_count as usize);
+    for _ in 0..frame_count {
+        let len = u32::from_le_bytes(payload.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        pos += 4;
+        frames.push(payload.get(pos..pos + len)?.to_vec());
+        pos += len;
+    }
+    Some(CaptureRecord { received_at_us, frames })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ts: u64, topic: &[u8], body: &[u8]) -> CaptureRecord {
+        CaptureRecord {
+            received_at_us: ts,
+            frames: vec![topic.to_vec(), body.to_vec(), 7u32.to_le_bytes().to_vec()],
+        }
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut writer = CaptureWriter::new(Vec::new(), FLAG_SEQUENCE).unwrap();
+        let records = vec![record(1_000, b"rawtx", &[0xaa; 100]), record(2_500, b"hashblock", &[0x11; 32])];
+        for r in &records {
+            writer.write_record(r).unwrap();
+        }
+        let bytes_written = writer.bytes_written();
+        let buf = writer.inner;
+        assert_eq!(bytes_written, buf.len() as u64);
+
+        let mut reader = CaptureReader::new(buf.as_slice()).unwrap();
+        assert_eq!(reader.header(), CaptureHeader { version: CAPTURE_VERSION, flags: FLAG_SEQUENCE });
+        assert!(reader.header().has_sequence());
+        assert_eq!(reader.next_record().unwrap(), Some(records[0].clone()));
+        assert_eq!(reader.next_record().unwrap(), Some(records[1].clone()));
+        assert_eq!(reader.next_record().unwrap(), None);
+    }
+
+    #[test]
+    fn truncated_tail_ends_stream() {
+        let mut writer = CaptureWriter::new(Vec::new(), 0).unwrap();
+        writer.write_record(&record(1, b"rawtx", &[1, 2, 3])).unwrap();
+        writer.write_record(&record(2, b"rawtx", &[4, 5, 6])).unwrap();
+        let mut buf = writer.inner;
+        buf.truncate(buf.len() - 2);
+
+        let mut reader = CaptureReader::new(buf.as_slice()).unwrap();
+        assert!(reader.next_record().unwrap().is_some());
+        assert_eq!(reader.next_record().unwrap(), None);
+    }
+
+    #[test]
+    fn rejects_bad_magic_and_future_version() {
+        assert!(CaptureReader::new(&b"NOTCAP\x01\x00\x00\x00"[..]).is_err());
+        let mut future = CAPTURE_MAGIC.to_vec();
+        future.extend_from_slice(&(CAPTURE_VERSION + 1).to_le_bytes());
+        future.extend_from_slice(&0u16.to_le_bytes());
+        assert!(CaptureReader::new(future.as_slice()).is_err());
+    }
+}
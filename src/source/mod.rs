@@ -0,0 +1,120 @@
+pub mod capture;
+pub mod record;
+pub mod replay;
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+use crate::core::MempoolEvent;
+
+/// A producer of `MempoolEvent`s for the pipeline: the live ZMQ subscriber
+/// (`rpc::zmq_sub::ZmqSource`) or a capture file replay (`replay::ReplaySource`).
+pub trait EventSource: Send {
+    /// Short description for logs.
+    fn describe(&self) -> String;
+
+    /// Start producing events on a background thread. The source stops once `tx` is
+    /// closed or the handle is stopped; finite sources (replays) also stop at their end,
+    /// closing the channel.
+    fn start(self: Box<Self>, tx: mpsc::UnboundedSender<MempoolEvent>) -> SourceHandle;
+}
+
+/// A running event source's thread and its stop flag.
+pub struct SourceHandle {
+    stop: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl SourceHandle {
+    /// Run `f` on a new thread; it should return soon after the flag passed to it is set.
+    pub fn spawn(f: impl FnOnce(Arc<AtomicBool>) + Send + 'static) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = stop.clone();
+        Self { stop, thread: std::thread::spawn(move || f(flag)) }
+    }
+
+    /// Ask the source to stop and wait for it, so open capture files get flushed.
+    pub fn stop(self) -> std::thread::Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join()
+    }
+
+    /// Wait for the source to end on its own.
+    #[allow(dead_code)]
+    pub fn join(self) -> std::thread::Result<()> {
+        self.thread.join()
+    }
+}
+
+/// How fast a capture is replayed relative to the recorded receive times.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Original pacing.
+    RealTime,
+    /// Original pacing sped up by the given factor.
+    Accelerated(f64),
+    /// No pacing at all.
+    Max,
+}
+
+impl ReplaySpeed {
+    /// Wall-clock time at which an event recorded `offset` after the first one is due,
+    /// relative to the replay start. `None` means immediately.
+    pub fn scale(&self, offset: Duration) -> Option<Duration> {
+        match self {
+            ReplaySpeed::RealTime => Some(offset),
+            ReplaySpeed::Accelerated(factor) => Some(offset.div_f64(*factor)),
+            ReplaySpeed::Max => None,
+        }
+    }
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+
+    /// Accepts `realtime`, `max`, or a factor such as `10x` / `2.5`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "realtime" | "1x" | "1" => Ok(ReplaySpeed::RealTime),
+            "max" => Ok(ReplaySpeed::Max),
+            other => {
+                let factor: f64 = other
+                    .trim_end_matches('x')
+                    .parse()
+                    .map_err(|_| format!("invalid replay speed '{s}' (expected realtime, max or e.g. 10x)"))?;
+                if factor.is_finite() && factor > 0.0 {
+                    Ok(ReplaySpeed::Accelerated(factor))
+                } else {
+                    Err(format!("replay speed factor must be positive, got '{s}'"))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_speed() {
+        assert_eq!("realtime".parse::<ReplaySpeed>(), Ok(ReplaySpeed::RealTime));
+        assert_eq!("MAX".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Max));
+        assert_eq!("10x".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Accelerated(10.0)));
+        assert_eq!("2.5".parse::<ReplaySpeed>(), Ok(ReplaySpeed::Accelerated(2.5)));
+        assert!("0x".parse::<ReplaySpeed>().is_err());
+        assert!("fast".parse::<ReplaySpeed>().is_err());
+    }
+
+    #[test]
+    fn scale_offsets() {
+        let offset = Duration::from_secs(10);
+        assert_eq!(ReplaySpeed::RealTime.scale(offset), Some(offset));
+        assert_eq!(ReplaySpeed::Accelerated(4.0).scale(offset), Some(Duration::from_millis(2500)));
+        assert_eq!(ReplaySpeed::Max.scale(offset), None);
+    }
+}
@@ -0,0 +1,281 @@
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::Utc;
+use tracing::{error, info};
+
+use super::capture::{CaptureRecord, CaptureWriter};
+use crate::config::CaptureConfig;
+
+/// How often buffered records are flushed to disk.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Writes raw ZMQ messages to capture files, rotating by size or age.
+///
+/// Files are named `<stem>-<UTC yyyymmddTHHMMSSZ>-<nnn>.<ext>` next to the configured
+/// path (`txradar-….cap` inside it if it is a directory), so a directory of captures
+/// sorts, and replays, in recording order. A file is only created once the first
+/// message arrives.
+pub struct CaptureRecorder {
+    dir: PathBuf,
+    stem: String,
+    ext: String,
+    flags: u16,
+    max_bytes: Option<u64>,
+    max_age: Option<Duration>,
+    current: Option<OpenCapture>,
+    /// Files opened so far; keeps names unique and ordered within the same second.
+    opened: u32,
+    /// Set after a write error; recording stays off for the rest of the session.
+    failed: bool,
+}
+
+struct OpenCapture {
+    writer: CaptureWriter<BufWriter<File>>,
+    path: PathBuf,
+    opened_at: Instant,
+    last_flush: Instant,
+    records: u64,
+}
+
+impl CaptureRecorder {
+    pub fn new(path: &Path, config: &CaptureConfig) -> io::Result<Self> {
+        let base = if path.is_dir() { path.join("txradar.cap") } else { path.to_path_buf() };
+        let dir = base.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        std::fs::create_dir_all(dir)?;
+        let stem = base
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "txradar".to_string());
+        let ext = base
+            .extension()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "cap".to_string());
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            stem,
+            ext,
+            flags: 0,
+            max_bytes: (config.max_file_mb > 0).then(|| config.max_file_mb * 1024 * 1024),
+            max_age: (config.rotate_minutes > 0).then(|| Duration::from_secs(config.rotate_minutes * 60)),
+            current: None,
+            opened: 0,
+            failed: false,
+        })
+    }
+
+    /// Header flags for files opened from now on.
+    pub fn set_flags(&mut self, flags: u16) {
+        self.flags = flags;
+    }
+
+    /// Describe where captures go, for logs.
+    pub fn describe(&self) -> String {
+        self.dir.join(format!("{}-*.{}", self.stem, self.ext)).display().to_string()
+    }
+
+    /// Record a message received now.
+    pub fn record(&mut self, frames: &[Vec<u8>], now: Instant) {
+        let received_at_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or(0);
+        self.write(&CaptureRecord { received_at_us, frames: frames.to_vec() }, now);
+    }
+
+    fn write(&mut self, record: &CaptureRecord, now: Instant) {
+        if self.failed {
+            return;
+        }
+        if let Err(e) = self.try_write(record, now) {
+            error!("Capture write failed: {e}, recording disabled");
+            self.failed = true;
+            self.current = None;
+        }
+    }
+
+    fn try_write(&mut self, record: &CaptureRecord, now: Instant) -> io::Result<()> {
+        if self.current.as_ref().is_some_and(|c| self.should_rotate(c, now)) {
+            self.close()?;
+        }
+        let current = match self.current.as_mut() {
+            Some(c) => c,
+            None => {
+                let opened = self.open(now)?;
+                self.current.insert(opened)
+            }
+        };
+        current.writer.write_record(record)?;
+        current.records += 1;
+        self.try_flush(now)
+    }
+
+    /// Flush buffered records once `FLUSH_INTERVAL` has passed since the last flush.
+    /// Called from the subscriber's poll loop, so a quiet stream still reaches disk.
+    pub fn tick(&mut self, now: Instant) {
+        if self.failed {
+            return;
+        }
+        if let Err(e) = self.try_flush(now) {
+            error!("Capture flush failed: {e}, recording disabled");
+            self.failed = true;
+            self.current = None;
+        }
+    }
+
+    fn try_flush(&mut self, now: Instant) -> io::Result<()> {
+        if let Some(current) = self.current.as_mut()
+            && now.duration_since(current.last_flush) >= FLUSH_INTERVAL
+        {
+            current.writer.flush()?;
+            current.last_flush = now;
+        }
+        Ok(())
+    }
+
+    fn should_rotate(&self, current: &OpenCapture, now: Instant) -> bool {
+        self.max_bytes.is_some_and(|max| current.writer.bytes_written() >= max)
+            || self.max_age.is_some_and(|max| now.duration_since(current.opened_at) >= max)
+    }
+
+    fn open(&mut self, now: Instant) -> io::Result<OpenCapture> {
+        let stamp = Utc::now().format("%Y%m%dT%H%M%SZ");
+        let path = loop {
+            let path = self.dir.join(format!("{}-{stamp}-{:03}.{}", self.stem, self.opened, self.ext));
+            self.opened += 1;
+            if !path.exists() {
+                break path;
+            }
+        };
+        let file = File::options().write(true).create_new(true).open(&path)?;
+        let writer = CaptureWriter::new(BufWriter::new(file), self.flags)?;
+        info!("Recording capture to {}", path.display());
+        Ok(OpenCapture { writer, path, opened_at: now, last_flush: now, records: 0 })
+    }
+
+    /// Flush and close the current file, if any.
+    pub fn close(&mut self) -> io::Result<()> {
+        if let Some(mut current) = self.current.take() {
+            current.writer.flush()?;
+            info!(
+                "Closed capture {} ({} messages, {} bytes)",
+                current.path.display(),
+                current.records,
+                current.writer.bytes_written()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Drop for CaptureRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            error!("Capture flush failed: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::capture::{CaptureReader, FLAG_SEQUENCE};
+    use std::io::BufReader;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("txradar_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn capture_files(dir: &Path) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        files.sort();
+        files
+    }
+
+    fn read_all(path: &Path) -> Vec<CaptureRecord> {
+        let mut reader = CaptureReader::new(BufReader::new(File::open(path).unwrap())).unwrap();
+        assert!(reader.header().has_sequence());
+        std::iter::from_fn(|| reader.next_record().unwrap()).collect()
+    }
+
+    fn record(ts: u64) -> CaptureRecord {
+        CaptureRecord { received_at_us: ts, frames: vec![b"rawtx".to_vec(), vec![0xab; 600_000]] }
+    }
+
+    #[test]
+    fn rotates_by_size() {
+        let dir = temp_dir("record_size");
+        let config = CaptureConfig { max_file_mb: 1, rotate_minutes: 0 };
+        let mut recorder = CaptureRecorder::new(&dir.join("incident.cap"), &config).unwrap();
+        recorder.set_flags(FLAG_SEQUENCE);
+        let now = Instant::now();
+        for ts in 0..5 {
+            recorder.write(&record(ts), now);
+        }
+        drop(recorder);
+
+        let files = capture_files(&dir);
+        let per_file: Vec<Vec<u64>> = files
+            .iter()
+            .map(|f| read_all(f).iter().map(|r| r.received_at_us).collect())
+            .collect();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Two 600 KB messages cross 1 MiB, so each file holds two
+        assert_eq!(per_file, vec![vec![0, 1], vec![2, 3], vec![4]]);
+        assert!(files.iter().all(|f| {
+            let name = f.file_name().unwrap().to_string_lossy();
+            name.starts_with("incident-") && name.ends_with(".cap")
+        }));
+    }
+
+    #[test]
+    fn rotates_by_age_into_directory() {
+        let dir = temp_dir("record_age");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = CaptureConfig { max_file_mb: 0, rotate_minutes: 60 };
+        let mut recorder = CaptureRecorder::new(&dir, &config).unwrap();
+        recorder.set_flags(FLAG_SEQUENCE);
+        let start = Instant::now();
+        recorder.write(&record(1), start);
+        recorder.write(&record(2), start + Duration::from_secs(59 * 60));
+        recorder.write(&record(3), start + Duration::from_secs(60 * 60));
+        drop(recorder);
+
+        let files = capture_files(&dir);
+        let counts: Vec<usize> = files.iter().map(|f| read_all(f).len()).collect();
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(counts, vec![2, 1]);
+        assert!(files[0].file_name().unwrap().to_string_lossy().starts_with("txradar-"));
+    }
+
+    #[test]
+    fn tick_flushes_quiet_stream() {
+        let dir = temp_dir("record_tick");
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = CaptureConfig { max_file_mb: 0, rotate_minutes: 0 };
+        let mut recorder = CaptureRecorder::new(&dir, &config).unwrap();
+        recorder.set_flags(FLAG_SEQUENCE);
+        let start = Instant::now();
+        recorder.write(&CaptureRecord { received_at_us: 1, frames: vec![b"rawtx".to_vec(), vec![1]] }, start);
+        let file = capture_files(&dir).remove(0);
+        assert_eq!(std::fs::metadata(&file).unwrap().len(), 0);
+
+        recorder.tick(start + Duration::from_millis(500));
+        assert_eq!(std::fs::metadata(&file).unwrap().len(), 0);
+        recorder.tick(start + FLUSH_INTERVAL);
+        let flushed = read_all(&file).len();
+        drop(recorder);
+        let _ = std::fs::remove_dir_all(&dir);
+
+        assert_eq!(flushed, 1);
+    }
+}
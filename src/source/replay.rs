@@ -0,0 +1,341 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::sync::mpsc;
+use tracing::{error, info};
+
+use super::capture::CaptureReader;
+use super::{EventSource, ReplaySpeed, SourceHandle};
+use crate::core::MempoolEvent;
+use crate::rpc::zmq_sub::ZmqDecoder;
+
+/// Replays a capture file, or a directory of rotated capture files, through the same
+/// decoder as the live ZMQ subscriber.
+///
+/// The decoder runs on recorded time (receive timestamps), so tx classification and
+/// resync coalescing behave as they did live regardless of the replay speed.
+pub struct ReplaySource {
+    path: PathBuf,
+    speed: ReplaySpeed,
+}
+
+impl ReplaySource {
+    pub fn new(path: PathBuf, speed: ReplaySpeed) -> Self {
+        Self { path, speed }
+    }
+
+    /// The capture files to replay: the path itself, or the `.cap` files in it sorted by
+    /// name (the recorder's names sort in recording order).
+    fn files(&self) -> std::io::Result<Vec<PathBuf>> {
+        if !self.path.is_dir() {
+            return Ok(vec![self.path.clone()]);
+        }
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.path)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "cap") {
+                files.push(path);
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Replay all captures, sending events until the end, until `tx` closes or until
+    /// `stop` is set.
+    fn run(&self, tx: &mpsc::UnboundedSender<MempoolEvent>, stop: &AtomicBool) -> std::io::Result<u64> {
+        let mut decoder: Option<ZmqDecoder> = None;
+        let mut events = Vec::new();
+        let started = Instant::now();
+        let mut first_ts: Option<u64> = None;
+        let mut recorded_now = started;
+        let mut count = 0u64;
+
+        for file in self.files()? {
+            let mut reader = CaptureReader::new(BufReader::new(File::open(&file)?))?;
+            // One decoder across files, so rotation boundaries are invisible to it
+            let decoder = decoder.get_or_insert_with(|| ZmqDecoder::new(reader.header().has_sequence()));
+
+            while let Some(record) = reader.next_record()? {
+                if stop.load(Ordering::Relaxed) {
+                    return Ok(count);
+                }
+                let first = *first_ts.get_or_insert(record.received_at_us);
+                let offset = Duration::from_micros(record.received_at_us.saturating_sub(first));
+                recorded_now = started + offset;
+                if let Some(due) = self.speed.scale(offset) {
+                    let elapsed = started.elapsed();
+                    if due > elapsed {
+                        std::thread::sleep(due - elapsed);
+                    }
+                }
+
+                decoder.on_message(&record.frames, recorded_now, &mut events);
+                decoder.tick(recorded_now, &mut events);
+                for event in events.drain(..) {
+                    if tx.send(event).is_err() {
+                        return Ok(count);
+                    }
+                }
+                count += 1;
+            }
+        }
+
+        if let Some(decoder) = decoder.as_mut() {
+            decoder.finish(recorded_now, &mut events);
+        }
+        for event in events.drain(..) {
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl EventSource for ReplaySource {
+    fn describe(&self) -> String {
+        format!("replay {} ({:?})", self.path.display(), self.speed)
+    }
+
+    fn start(self: Box<Self>, tx: mpsc::UnboundedSender<MempoolEvent>) -> SourceHandle {
+        SourceHandle::spawn(move |stop| match self.run(&tx, &stop) {
+            Ok(count) => info!("Replay of {} finished: {count} messages", self.path.display()),
+            Err(e) => error!("Replay of {} failed: {e}", self.path.display()),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::core::pipeline::{PipelineOutput, run_pipeline};
+    use crate::db::SharedDatabase;
+    use crate::rpc::{BitcoinRpc, test_server};
+    use crate::source::capture::{CaptureRecord, CaptureWriter, FLAG_SEQUENCE};
+    use crate::tags::TagLookup;
+    use bitcoin::hashes::Hash;
+    use serde_json::{Value, json};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn sample_tx(n: u8) -> bitcoin::Transaction {
+        bitcoin::Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: bitcoin::OutPoint {
+                    txid: bitcoin::Txid::from_byte_array([n; 32]),
+                    vout: 0,
+                },
+                ..Default::default()
+            }],
+            output: vec![bitcoin::TxOut {
+                value: bitcoin::Amount::from_sat(50_000),
+                script_pubkey: bitcoin::ScriptBuf::new(),
+            }],
+        }
+    }
+
+    fn message(ts: u64, topic: &[u8], body: Vec<u8>, seq: u32) -> CaptureRecord {
+        CaptureRecord {
+            received_at_us: ts,
+            frames: vec![topic.to_vec(), body, seq.to_le_bytes().to_vec()],
+        }
+    }
+
+    fn sequence_body(tx: &bitcoin::Transaction, label: u8, mempool_seq: u64) -> Vec<u8> {
+        let mut body = tx.compute_txid().to_byte_array().to_vec();
+        body.reverse();
+        body.push(label);
+        body.extend_from_slice(&mempool_seq.to_le_bytes());
+        body
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("txradar_{name}_{}", std::process::id()))
+    }
+
+    /// A mempool arrival (rawtx + 'A') and a tx that only showed up in a block.
+    fn write_capture(path: &PathBuf) -> (bitcoin::Transaction, bitcoin::Transaction) {
+        let mempool_tx = sample_tx(1);
+        let block_tx = sample_tx(2);
+        let mut writer = CaptureWriter::new(File::create(path).unwrap(), FLAG_SEQUENCE).unwrap();
+        let records = [
+            message(1_000_000, b"rawtx", bitcoin::consensus::serialize(&mempool_tx), 0),
+            message(1_000_100, b"sequence", sequence_body(&mempool_tx, b'A', 1), 0),
+            message(1_500_000, b"rawtx", bitcoin::consensus::serialize(&block_tx), 1),
+        ];
+        for r in &records {
+            writer.write_record(r).unwrap();
+        }
+        writer.flush().unwrap();
+        (mempool_tx, block_tx)
+    }
+
+    #[test]
+    fn replay_decodes_capture() {
+        let path = temp_path("replay_decode.cap");
+        let (mempool_tx, block_tx) = write_capture(&path);
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        Box::new(ReplaySource::new(path.clone(), ReplaySpeed::Max)).start(tx).join().unwrap();
+
+        let mut events = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            events.push(event);
+        }
+        let _ = std::fs::remove_file(&path);
+
+        assert_eq!(events.len(), 2);
+        let txid_of = |t: &bitcoin::Transaction| {
+            let mut id = t.compute_txid().to_byte_array();
+            id.reverse();
+            id
+        };
+        assert!(matches!(&events[0], MempoolEvent::TxAdded { txid, mempool_sequence: Some(1), .. } if *txid == txid_of(&mempool_tx)));
+        assert!(matches!(&events[1], MempoolEvent::TxSeenInBlock { txid, .. } if *txid == txid_of(&block_tx)));
+    }
+
+    #[test]
+    fn replay_directory_in_name_order() {
+        let dir = temp_path("replay_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        // Written out of order: the later recording first
+        let tx_a = sample_tx(3);
+        let tx_b = sample_tx(4);
+        for (name, tx, ts, seq) in [("cap-2.cap", &tx_b, 2_000_000, 2), ("cap-1.cap", &tx_a, 1_000_000, 1)] {
+            let mut writer = CaptureWriter::new(File::create(dir.join(name)).unwrap(), FLAG_SEQUENCE).unwrap();
+            writer.write_record(&message(ts, b"rawtx", bitcoin::consensus::serialize(tx), 0)).unwrap();
+            writer.write_record(&message(ts + 100, b"sequence", sequence_body(tx, b'A', seq), 0)).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), b"not a capture").unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        Box::new(ReplaySource::new(dir.clone(), ReplaySpeed::Max)).start(tx).join().unwrap();
+        let mut added = Vec::new();
+        while let Ok(event) = rx.try_recv() {
+            if let MempoolEvent::TxAdded { txid, .. } = event {
+                added.push(txid);
+            }
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let txid_of = |t: &bitcoin::Transaction| {
+            let mut id = t.compute_txid().to_byte_array();
+            id.reverse();
+            id
+        };
+        assert_eq!(added, vec![txid_of(&tx_a), txid_of(&tx_b)]);
+    }
+
+    #[tokio::test]
+    async fn replay_drives_pipeline_without_node() {
+        let path = temp_path("replay_pipeline.cap");
+        let db_path = temp_path("replay_pipeline.db");
+        let (mempool_tx, _) = write_capture(&path);
+
+        let db = SharedDatabase::open(&db_path).unwrap();
+        let tag_lookup = Arc::new(Mutex::new(TagLookup::load_from_db(&db)));
+        // Nothing listens here: prevout lookups fail fast and the pipeline degrades
+        let rpc = BitcoinRpc::new("127.0.0.1", 1, "user", "pass");
+        let mut config = Config::default();
+        config.notifications.enabled = false;
+
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+        let source = Box::new(ReplaySource::new(path.clone(), ReplaySpeed::Max));
+        let handle = source.start(event_tx);
+        run_pipeline(event_rx, ui_tx, db, rpc, tag_lookup, config, true).await;
+        handle.join().unwrap();
+
+        let mut new_txs = Vec::new();
+        let mut block_only = 0;
+        while let Ok(output) = ui_rx.try_recv() {
+            match output {
+                PipelineOutput::NewTx(scored) => new_txs.push(scored.tx.txid),
+                PipelineOutput::BlockOnlyTx(_) => block_only += 1,
+                _ => {}
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&db_path);
+
+        assert_eq!(new_txs, vec![mempool_tx.compute_txid().to_string()]);
+        assert_eq!(block_only, 1);
+    }
+
+    #[tokio::test]
+    async fn replay_ignores_sequence_gaps() {
+        let path = temp_path("replay_gap.cap");
+        let db_path = temp_path("replay_gap.db");
+        // Two recording sessions in one stream: the second starts with fresh ZMQ counters
+        let (tx_a, tx_b) = (sample_tx(5), sample_tx(6));
+        let mut writer = CaptureWriter::new(File::create(&path).unwrap(), FLAG_SEQUENCE).unwrap();
+        let records = [
+            message(1_000_000, b"rawtx", bitcoin::consensus::serialize(&tx_a), 7),
+            message(1_000_100, b"sequence", sequence_body(&tx_a, b'A', 40), 7),
+            message(9_000_000, b"rawtx", bitcoin::consensus::serialize(&tx_b), 0),
+            message(9_000_100, b"sequence", sequence_body(&tx_b, b'A', 3), 0),
+        ];
+        for r in &records {
+            writer.write_record(r).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let db = SharedDatabase::open(&db_path).unwrap();
+        let tag_lookup = Arc::new(Mutex::new(TagLookup::load_from_db(&db)));
+        // A node whose mempool is empty: a resync would drop the replayed txs
+        let node_calls = Arc::new(AtomicUsize::new(0));
+        let calls = node_calls.clone();
+        let rpc = test_server::spawn(Duration::ZERO, move |request| match request.as_array() {
+            Some(batch) => Value::Array(
+                batch
+                    .iter()
+                    .map(|call| json!({"id": call["id"], "result": null, "error": {"code": -5}}))
+                    .collect(),
+            ),
+            None => {
+                calls.fetch_add(1, Ordering::SeqCst);
+                let result = match request["method"].as_str() {
+                    Some("getrawmempool") => json!({"txids": [], "mempool_sequence": 100}),
+                    _ => json!({"blocks": 900_000}),
+                };
+                json!({"id": request["id"], "result": result, "error": null})
+            }
+        })
+        .await;
+        let mut config = Config::default();
+        config.notifications.enabled = false;
+
+        let (event_tx, event_rx) = mpsc::unbounded_channel();
+        let (ui_tx, mut ui_rx) = mpsc::unbounded_channel();
+        let handle = Box::new(ReplaySource::new(path.clone(), ReplaySpeed::Max)).start(event_tx);
+        run_pipeline(event_rx, ui_tx, db, rpc, tag_lookup, config, true).await;
+        handle.join().unwrap();
+
+        let mut new_txs = Vec::new();
+        let mut resynced = false;
+        let mut pending = None;
+        while let Ok(output) = ui_rx.try_recv() {
+            match output {
+                PipelineOutput::NewTx(scored) => new_txs.push(scored.tx.txid),
+                PipelineOutput::Resynced { .. } => resynced = true,
+                PipelineOutput::MempoolStats { pending_count, .. } => pending = Some(pending_count),
+                _ => {}
+            }
+        }
+        let _ = std::fs::remove_file(&path);
+        let _ = std::fs::remove_file(&db_path);
+
+        assert_eq!(new_txs, vec![tx_a.compute_txid().to_string(), tx_b.compute_txid().to_string()]);
+        assert!(!resynced);
+        assert_ne!(pending, Some(0));
+        assert_eq!(node_calls.load(Ordering::SeqCst), 0);
+    }
+}
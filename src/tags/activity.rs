@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::db::SharedDatabase;
+
+/// The longest silence of an entity ended by a tx.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dormancy {
+    pub entity: String,
+    pub days: f64,
+}
+
+/// Last outgoing activity per tagged entity (clusters share their seed tag's entity).
+///
+/// Loaded from `entity_activity` at startup and kept current in memory; updates are
+/// queued to the database if `persist` was set.
+pub struct EntityActivity {
+    last_outgoing: HashMap<String, i64>,
+    db: Option<SharedDatabase>,
+}
+
+impl EntityActivity {
+    pub fn load_from_db(db: &SharedDatabase, persist: bool) -> Self {
+        let last_outgoing = db.read(|db| db.entity_activity()).unwrap_or_else(|e| {
+            tracing::warn!("Failed to load entity activity: {e}");
+            HashMap::new()
+        });
+        tracing::info!("Loaded last activity of {} entities", last_outgoing.len());
+        Self { last_outgoing, db: persist.then(|| db.clone()) }
+    }
+
+    #[cfg(test)]
+    pub fn empty() -> Self {
+        Self { last_outgoing: HashMap::new(), db: None }
+    }
+
+    /// The longest silence a spend by `entities` at `time` (unix seconds) would end. An
+    /// entity never seen spending before falls back to `newest_input_time`: none of the
+    /// coins it spends moved since then.
+    pub fn dormancy(&self, entities: &[&str], time: i64, newest_input_time: Option<i64>) -> Option<Dormancy> {
+        let mut longest: Option<Dormancy> = None;
+        for &entity in entities {
+            let last = self.last_outgoing.get(entity).copied().or(newest_input_time);
+            if let Some(last) = last {
+                let days = (time - last).max(0) as f64 / 86_400.0;
+                if longest.as_ref().is_none_or(|d| days > d.days) {
+                    longest = Some(Dormancy { entity: entity.to_string(), days });
+                }
+            }
+        }
+        longest
+    }
+
+    /// Record that `entities` spent in `txid` at `time` and return the longest silence
+    /// this ends (see `dormancy`).
+    pub fn record_spend(
+        &mut self,
+        entities: &[&str],
+        txid: &str,
+        time: i64,
+        newest_input_time: Option<i64>,
+    ) -> Option<Dormancy> {
+        let longest = self.dormancy(entities, time, newest_input_time);
+        for &entity in entities {
+            if self.last_outgoing.get(entity).is_some_and(|&t| t >= time) {
+                continue;
+            }
+            self.last_outgoing.insert(entity.to_string(), time);
+            if let Some(db) = &self.db {
+                let (entity, txid) = (entity.to_string(), txid.to_string());
+                db.write_detached("record entity activity", move |db| {
+                    db.record_entity_activity(&entity, time, &txid)
+                });
+            }
+        }
+        longest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 86_400;
+
+    #[test]
+    fn longest_silence_across_entities() {
+        let mut activity = EntityActivity::empty();
+        assert_eq!(activity.record_spend(&["A"], "t1", 10 * DAY, None), None);
+        activity.record_spend(&["B"], "t2", 40 * DAY, None);
+
+        let dormancy = activity.record_spend(&["A", "B"], "t3", 100 * DAY, None).unwrap();
+        assert_eq!(dormancy, Dormancy { entity: "A".into(), days: 90.0 });
+        // Both are active now
+        let dormancy = activity.record_spend(&["B"], "t4", 101 * DAY, None).unwrap();
+        assert_eq!(dormancy.days, 1.0);
+    }
+
+    #[test]
+    fn unknown_entity_uses_input_age() {
+        let mut activity = EntityActivity::empty();
+        let dormancy = activity.record_spend(&["Whale"], "t1", 1_000 * DAY, Some(200 * DAY)).unwrap();
+        assert_eq!(dormancy.days, 800.0);
+        // Recorded activity wins over the inputs from now on
+        let dormancy = activity.record_spend(&["Whale"], "t2", 1_010 * DAY, Some(200 * DAY)).unwrap();
+        assert_eq!(dormancy.days, 10.0);
+    }
+
+    #[test]
+    fn persists_latest_activity() {
+        let path = std::env::temp_dir().join(format!("txradar_activity_{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let db = SharedDatabase::open(&path).unwrap();
+
+        let mut activity = EntityActivity::load_from_db(&db, true);
+        activity.record_spend(&["A"], "t2", 20 * DAY, None);
+        // An older tx (e.g. from the startup sync) doesn't move it back
+        activity.record_spend(&["A"], "t1", 10 * DAY, None);
+        db.flush();
+
+        let mut reloaded = EntityActivity::load_from_db(&db, false);
+        assert_eq!(reloaded.last_outgoing.get("A"), Some(&(20 * DAY)));
+        // A replay only keeps what it sees in memory
+        reloaded.record_spend(&["A"], "t3", 30 * DAY, None);
+        db.flush();
+        assert_eq!(db.read(|db| db.entity_activity()).unwrap().get("A"), Some(&(20 * DAY)));
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod activity;
+
 use std::collections::HashMap;
 use std::sync::atomic::{AtomicU64, Ordering};
 
@@ -105,10 +107,9 @@ impl TagLookup {
         matches
     }
 
-    /// Check all inputs of a transaction against known addresses (requires prevout scripts).
-    /// Since we don't have prevout scripts in the raw tx, this checks witness program / 
-    /// script_sig patterns. In practice, input address extraction from raw tx is limited.
-    /// We accept pre-resolved input addresses instead.
+    /// Check the input addresses of a transaction against known addresses. Pass
+    /// `core::tx::input_addresses`: addresses derived from the inputs' scriptSig/witness,
+    /// completed by resolved prevouts for inputs that don't reveal theirs.
     pub fn check_input_addresses(&self, addresses: &[String]) -> Vec<TagMatch> {
         let mut matches = Vec::new();
         for addr_str in addresses {
@@ -174,11 +175,10 @@ impl TagLookup {
             // Insert into in-memory map
             self.map.insert(addr.clone(), new_tag.clone());
 
-            // Persist to DB
+            // Persist to DB (queued; the in-memory map is authoritative meanwhile)
             if let Some(ref db) = self.db {
-                if let Err(e) = db.insert_tag_if_higher(&new_tag) {
-                    tracing::warn!("Failed to persist cluster tag for {}: {e}", addr);
-                }
+                let tag = new_tag.clone();
+                db.write_detached("persist cluster tag", move |db| db.insert_tag_if_higher(&tag).map(|_| ()));
             }
 
             new_count += 1;
@@ -257,6 +257,7 @@ mod tests {
         assert!((t2.confidence - 0.63).abs() < 0.001);
 
         // Persisted to DB
+        db.flush();
         let db_tag = db.lookup_address("addr_unknown1").unwrap();
         assert_eq!(db_tag.entity, "Binance");
     }
@@ -1,10 +1,13 @@
 use dioxus::prelude::*;
 
+use crate::core::cpfp::CpfpBump;
+use crate::core::rbf::ReplacementDiff;
 use crate::core::{AlertLevel, ScoredTx};
 
 #[component]
-pub fn AlertPanel(txs: Signal<Vec<ScoredTx>>) -> Element {
+pub fn AlertPanel(txs: Signal<Vec<ScoredTx>>, bumps: Signal<Vec<CpfpBump>>) -> Element {
     let binding = txs.read();
+    let bumps = bumps.read();
     let alerts: Vec<&ScoredTx> = binding
         .iter()
         .filter(|tx| matches!(tx.alert_level, AlertLevel::Critical | AlertLevel::High))
@@ -17,14 +20,17 @@ pub fn AlertPanel(txs: Signal<Vec<ScoredTx>>) -> Element {
                 p { style: "color: #666;", "No high-priority signals yet." }
             }
             for tx in alerts.iter().rev().take(20) {
-                AlertRow { tx: (*tx).clone() }
+                AlertRow {
+                    tx: (*tx).clone(),
+                    bump: bumps.iter().rev().find(|b| b.parent_txid == tx.tx.txid).cloned(),
+                }
             }
         }
     }
 }
 
 #[component]
-fn AlertRow(tx: ScoredTx) -> Element {
+fn AlertRow(tx: ScoredTx, bump: Option<CpfpBump>) -> Element {
     let btc = tx.tx.total_input_value as f64 / 100_000_000.0;
     let btc_display = if btc >= 1.0 {
         format!("{btc:.4}")
@@ -49,10 +55,44 @@ fn AlertRow(tx: ScoredTx) -> Element {
                 },
                 "{tx.tx.txid}"
             }
+            if let Some(bump) = bump {
+                div { style: "font-size: 11px; color: #aaa; margin-top: 4px;",
+                    title: "{bump.child_txid}",
+                    "⏫ {bump.summary()}"
+                }
+            }
             div { style: "font-size: 11px; color: #aaa; margin-top: 4px;",
                 for rule in tx.rule_scores.iter().filter(|r| r.weighted_score > 0.1) {
                     span { style: "margin-right: 8px;",
                         "{rule.rule_name}: {rule.weighted_score:.1}"
+                        if let Some(detail) = &rule.detail {
+                            " ({detail})"
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[component]
+pub fn ReplacementPanel(replacements: Signal<Vec<ReplacementDiff>>) -> Element {
+    let binding = replacements.read();
+    if binding.is_empty() {
+        return rsx! {};
+    }
+
+    rsx! {
+        div { style: "margin-top: 16px;",
+            h2 { style: "color: #f7931a;", "🔁 Replaced Alerts ({binding.len()})" }
+            for diff in binding.iter().rev().take(20) {
+                div {
+                    style: "background: #1a1a3a; border-left: 3px solid #e94560; padding: 8px; margin: 4px 0; border-radius: 4px;",
+                    div { style: "font-weight: bold;",
+                        "{diff.old_alert_level.emoji()} {diff.summary()}"
+                    }
+                    div { style: "font-size: 11px; color: #888; user-select: all;",
+                        "{diff.old_txid} → {diff.new_txid}"
                     }
                 }
             }
@@ -51,6 +51,13 @@ fn TxRow(tx: ScoredTx) -> Element {
                     if tx.tx.is_coinjoin {
                         span { style: "color: #8888ff;", "🔄 " }
                     }
+                    if tx.tx.unconfirmed_parent_inputs > 0 {
+                        span {
+                            style: "color: #88aa88;",
+                            title: "Spends {tx.tx.unconfirmed_parent_inputs} unconfirmed parent output(s); age inherited from the parent",
+                            "⛓ "
+                        }
+                    }
                     span {
                         style: "color: #888; cursor: pointer; user-select: all;",
                         title: "{txid_full}",
@@ -73,7 +80,15 @@ fn TxRow(tx: ScoredTx) -> Element {
             div { style: "display: flex; justify-content: space-between; color: #888; font-size: 11px;",
                 span { "Score: {tx.composite_score:.0}" }
                 span { "{tx.tx.fee_rate:.1} sat/vB" }
-                span { "ins: {tx.tx.input_count} outs: {tx.tx.output_count}" }
+                if tx.tx.resolved_inputs < tx.tx.input_count {
+                    span {
+                        style: "color: #aa8844;",
+                        title: "Prevouts partially resolved: value, fee and age are incomplete",
+                        "ins: {tx.tx.resolved_inputs}/{tx.tx.input_count} resolved outs: {tx.tx.output_count}"
+                    }
+                } else {
+                    span { "ins: {tx.tx.input_count} outs: {tx.tx.output_count}" }
+                }
             }
         }
     }
@@ -1,6 +1,6 @@
 use dioxus::prelude::*;
 
-use crate::db::SignalRecord;
+use crate::db::{SignalOutcome, SignalRecord};
 
 #[component]
 pub fn HistoryPanel(signals: Signal<Vec<SignalRecord>>, signal_stats: Signal<SignalStats>) -> Element {
@@ -54,6 +54,12 @@ fn SignalRow(signal: SignalRecord) -> Element {
     let txid_full = signal.txid.clone();
     let txid_display = signal.txid.clone();
     let exchange_badge = if signal.to_exchange { "📤" } else { "" };
+    let reorg_badge = if signal.reorged { "⛓️‍💥 reorged" } else { "" };
+    let outcome = signal.outcome.as_ref().map(outcome_label);
+    let bump = signal.bumped_by.as_ref().map(|child| match signal.package_fee_rate {
+        Some(rate) => format!("⏫ bumped by child {}… to {rate:.1} sat/vB", &child[..child.len().min(16)]),
+        None => format!("⏫ bumped by child {}…", &child[..child.len().min(16)]),
+    });
     let alert_emoji = match signal.alert_level.as_str() {
         "Critical" => "🔴",
         "High" => "🟠",
@@ -77,6 +83,7 @@ fn SignalRow(signal: SignalRecord) -> Element {
                         "{txid_display}"
                     }
                     " {exchange_badge}"
+                    span { style: "color: #e94560; font-size: 11px;", "{reorg_badge}" }
                 }
                 span { style: "font-weight: bold;",
                     "Score {signal.score:.0}"
@@ -87,6 +94,42 @@ fn SignalRow(signal: SignalRecord) -> Element {
                 span { "{signal.fee_rate:.1} sat/vB" }
                 span { "{signal.created_at}" }
             }
+            if let Some(bump) = bump {
+                div { style: "color: #aaa; font-size: 11px; margin-top: 2px;", "{bump}" }
+            }
+            if let Some(outcome) = outcome {
+                div { style: "color: #aaa; font-size: 11px; margin-top: 2px;", "{outcome}" }
+            }
         }
     }
 }
+
+/// One line describing how the tx left the mempool, e.g. `✅ confirmed in 850000 after 12m`.
+fn outcome_label(outcome: &SignalOutcome) -> String {
+    let after = format_duration(outcome.mempool_secs);
+    match outcome.outcome.as_str() {
+        "confirmed" => match outcome.confirmed_height {
+            Some(height) => format!("✅ confirmed in {height} after {after}"),
+            None => format!("✅ confirmed after {after}"),
+        },
+        "replaced" => match &outcome.replaced_by {
+            Some(txid) => format!("🔁 replaced by {}… after {after}", &txid[..txid.len().min(16)]),
+            None => format!("🔁 replaced after {after}"),
+        },
+        "evicted" => format!("🗑️ evicted after {after}"),
+        "conflicted" => match outcome.confirmed_height {
+            Some(height) => format!("⚔️ conflicted by block {height} after {after}"),
+            None => format!("⚔️ conflicted by a block after {after}"),
+        },
+        other => format!("{other} after {after}"),
+    }
+}
+
+fn format_duration(secs: i64) -> String {
+    match secs {
+        s if s < 60 => format!("{s}s"),
+        s if s < 3_600 => format!("{}m", s / 60),
+        s if s < 86_400 => format!("{}h {}m", s / 3_600, s % 3_600 / 60),
+        s => format!("{}d {}h", s / 86_400, s % 86_400 / 3_600),
+    }
+}
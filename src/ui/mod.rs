@@ -5,14 +5,21 @@ pub mod stats;
 use dioxus::prelude::*;
 
 use crate::core::ScoredTx;
+use crate::core::block::{BlockStats, ReorgInfo};
+use crate::core::cpfp::CpfpBump;
 use crate::core::mempool::RemovalStats;
+use crate::core::prevout::PrevoutStats;
 use crate::core::pipeline::PipelineOutput;
+use crate::core::rbf::ReplacementDiff;
+use crate::db::UtxoCacheStats;
 use crate::db::SignalRecord;
 
 /// Root UI component.
 #[component]
 pub fn App() -> Element {
     let mut alert_txs = use_signal(Vec::<ScoredTx>::new);
+    let mut replacements = use_signal(Vec::<ReplacementDiff>::new);
+    let mut cpfp_bumps = use_signal(Vec::<CpfpBump>::new);
     let mut tx_count = use_signal(|| 0u64);
     let mut block_height = use_signal(|| 0u32);
     let mut pending_count = use_signal(|| 0usize);
@@ -20,6 +27,12 @@ pub fn App() -> Element {
     let mut total_fees = use_signal(|| 0u64);
     let mut fee_histogram = use_signal(Vec::<(String, usize)>::new);
     let mut removal_stats = use_signal(RemovalStats::default);
+    let mut prevout_stats = use_signal(PrevoutStats::default);
+    let mut utxo_cache = use_signal(|| Option::<UtxoCacheStats>::None);
+    let mut last_resync = use_signal(|| Option::<String>::None);
+    let mut block_only_count = use_signal(|| 0u64);
+    let mut last_block = use_signal(|| Option::<BlockStats>::None);
+    let mut reorg = use_signal(|| Option::<(String, ReorgInfo)>::None);
     let mut history_signals = use_signal(Vec::<SignalRecord>::new);
     let mut signal_stats = use_signal(history::SignalStats::default);
 
@@ -80,6 +93,9 @@ pub fn App() -> Element {
                         }
                     }
                 }
+                PipelineOutput::BlockOnlyTx(_) => {
+                    block_only_count += 1;
+                }
                 PipelineOutput::BlockConnected { height } => {
                     if height > 0 {
                         block_height.set(height);
@@ -88,18 +104,53 @@ pub fn App() -> Element {
                         refresh_history(db, &mut history_signals, &mut signal_stats);
                     }
                 }
+                PipelineOutput::BlockStats(stats) => {
+                    last_block.set(Some(stats));
+                }
+                PipelineOutput::Replacement(diff) => {
+                    let mut writer = replacements.write();
+                    writer.push(diff);
+                    // Keep last 50 replacements
+                    if writer.len() > 50 {
+                        writer.remove(0);
+                    }
+                }
+                PipelineOutput::CpfpBump(bump) => {
+                    let mut writer = cpfp_bumps.write();
+                    writer.push(bump);
+                    // Keep last 50 bumps
+                    if writer.len() > 50 {
+                        writer.remove(0);
+                    }
+                }
+                PipelineOutput::Reorg(info) => {
+                    let at = chrono::Local::now().format("%H:%M:%S").to_string();
+                    reorg.set(Some((at, info)));
+                    if let Some(ref db) = db {
+                        refresh_history(db, &mut history_signals, &mut signal_stats);
+                    }
+                }
                 PipelineOutput::MempoolStats {
                     pending_count: pc,
                     total_vsize: tv,
                     total_fees: tf,
                     fee_histogram: fh,
                     removal_stats: rs,
+                    prevout_stats: ps,
                 } => {
                     pending_count.set(pc);
                     total_vsize.set(tv);
                     total_fees.set(tf);
                     fee_histogram.set(fh);
                     removal_stats.set(rs);
+                    prevout_stats.set(ps);
+                }
+                PipelineOutput::UtxoCacheStats(stats) => {
+                    utxo_cache.set(Some(stats));
+                }
+                PipelineOutput::Resynced { reason, added, removed } => {
+                    let at = chrono::Local::now().format("%H:%M:%S");
+                    last_resync.set(Some(format!("{at} after {reason} (+{added} / -{removed})")));
                 }
             }
         }
@@ -116,6 +167,19 @@ pub fn App() -> Element {
                 "Txs processed: {tx_count}"
             }
 
+            {match reorg() {
+                Some((at, info)) => rsx! {
+                    div { style: "background: #e94560; color: #fff; padding: 8px 12px; border-radius: 4px; margin-bottom: 16px; display: flex; justify-content: space-between;",
+                        span {
+                            "⚠️ Chain reorg at {at}: depth {info.depth}, fork at height {info.fork_height}, "
+                            "{info.affected_signals} signals affected, {info.unconfirmed_txs} txs back to pending"
+                        }
+                        span { style: "cursor: pointer;", onclick: move |_| reorg.set(None), "✕" }
+                    }
+                },
+                None => rsx! {},
+            }}
+
             div { style: "display: flex; gap: 16px;",
                 // Left: Stats + Alerts
                 div { style: "flex: 1;",
@@ -126,8 +190,14 @@ pub fn App() -> Element {
                         total_fees,
                         fee_histogram,
                         removal_stats,
+                        prevout_stats,
+                        utxo_cache,
+                        last_resync,
+                        block_only_count,
+                        last_block,
                     }
-                    alerts::AlertPanel { txs: alert_txs }
+                    alerts::AlertPanel { txs: alert_txs, bumps: cpfp_bumps }
+                    alerts::ReplacementPanel { replacements }
                 }
 
                 // Right: History
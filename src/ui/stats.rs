@@ -1,6 +1,9 @@
 use dioxus::prelude::*;
 
+use crate::core::block::BlockStats;
 use crate::core::mempool::RemovalStats;
+use crate::core::prevout::PrevoutStats;
+use crate::db::UtxoCacheStats;
 
 #[component]
 pub fn MempoolStats(
@@ -10,6 +13,11 @@ pub fn MempoolStats(
     total_fees: Signal<u64>,
     fee_histogram: Signal<Vec<(String, usize)>>,
     removal_stats: Signal<RemovalStats>,
+    prevout_stats: Signal<PrevoutStats>,
+    utxo_cache: Signal<Option<UtxoCacheStats>>,
+    last_resync: Signal<Option<String>>,
+    block_only_count: Signal<u64>,
+    last_block: Signal<Option<BlockStats>>,
 ) -> Element {
     let fees_btc = *total_fees.read() as f64 / 100_000_000.0;
     let vsize_mb = *total_vsize.read() as f64 / 1_000_000.0;
@@ -25,9 +33,33 @@ pub fn MempoolStats(
                 if *block_height.read() > 0 {
                     p { "Last block: {block_height}" }
                 }
+                {
+                    match last_block.read().as_ref() {
+                        Some(b) => {
+                            let block_fees = b.total_fees as f64 / 100_000_000.0;
+                            rsx! {
+                                p { style: "font-size: 11px; color: #aaa;",
+                                    "{b.tx_count} txs · fees {block_fees:.4} BTC · tracked {b.tracked_count} · signals {b.signal_count}"
+                                }
+                            }
+                        }
+                        None => rsx! {},
+                    }
+                }
                 p { "Pending: {pending_count}" }
                 p { "Total vSize: {vsize_mb:.2} MB" }
                 p { "Total fees: {fees_btc:.4} BTC" }
+                if *block_only_count.read() > 0 {
+                    p { style: "font-size: 11px; color: #aaa;", "Seen only in block: {block_only_count}" }
+                }
+                {
+                    match last_resync.read().as_ref() {
+                        Some(resync) => rsx! {
+                            p { style: "font-size: 11px; color: #aaa;", "🔄 Resynced {resync}" }
+                        },
+                        None => rsx! {},
+                    }
+                }
 
                 // Removal stats
                 {
@@ -38,7 +70,7 @@ pub fn MempoolStats(
                             div { style: "margin-top: 8px; padding-top: 8px; border-top: 1px solid #333;",
                                 p { style: "color: #f7931a; font-weight: bold;", "Removed: {total_removed}" }
                                 p { style: "font-size: 11px; color: #aaa;",
-                                    "confirmed: {rs.confirmed} · replaced: {rs.replaced} · evicted: {rs.evicted} · unknown: {rs.unknown}"
+                                    "confirmed: {rs.confirmed} · replaced: {rs.replaced} · evicted: {rs.evicted} · conflict: {rs.conflict} · unknown: {rs.unknown}"
                                 }
                             }
                         }
@@ -47,6 +79,53 @@ pub fn MempoolStats(
                     }
                 }
 
+                // Prevout resolution stats
+                {
+                    let ps = prevout_stats.read();
+                    let resolved = ps.resolved();
+                    let unresolved = ps.unresolved() - ps.coinbase;
+                    if resolved + unresolved > 0 {
+                        let pct = resolved as f64 * 100.0 / (resolved + unresolved) as f64;
+                        rsx! {
+                            div { style: "margin-top: 8px; padding-top: 8px; border-top: 1px solid #333;",
+                                p { style: "color: #f7931a; font-weight: bold;", "Prevouts: {pct:.1}% resolved" }
+                                p { style: "font-size: 11px; color: #aaa;",
+                                    "cache: {ps.cached} · mempool: {ps.mempool} · fetched: {ps.fetched}"
+                                }
+                                if unresolved > 0 {
+                                    p { style: "font-size: 11px; color: #aa8844;",
+                                        "spent: {ps.spent} · not found: {ps.not_found} · failed: {ps.failed} · timed out: {ps.timed_out}"
+                                    }
+                                }
+                            }
+                        }
+                    } else {
+                        rsx! {}
+                    }
+                }
+
+                // UTXO cache size and hit rate
+                {
+                    match utxo_cache.read().as_ref() {
+                        Some(cache) => {
+                            let size_mb = cache.disk_bytes as f64 / 1_000_000.0;
+                            let hit_rate = match prevout_stats.read().cache_hit_rate() {
+                                Some(rate) => format!("{:.1}%", rate * 100.0),
+                                None => "–".to_string(),
+                            };
+                            rsx! {
+                                div { style: "margin-top: 8px; padding-top: 8px; border-top: 1px solid #333;",
+                                    p { style: "color: #f7931a; font-weight: bold;", "UTXO cache: {hit_rate} hit rate" }
+                                    p { style: "font-size: 11px; color: #aaa;",
+                                        "rows: {cache.rows} · on disk: {size_mb:.1} MB · last block: -{cache.spent_removed} spent, -{cache.evicted} evicted"
+                                    }
+                                }
+                            }
+                        }
+                        None => rsx! {},
+                    }
+                }
+
                 if !histogram.is_empty() {
                     h3 { style: "color: #f7931a; margin-top: 8px; font-size: 13px;",
                         "Fee Rate Distribution (sat/vB)"